@@ -0,0 +1,126 @@
+use crate::error::MalError;
+use crate::interpreter::{require, Permission};
+use crate::reader::{MalKey, MalMap};
+use crate::MalValue;
+use std::fs;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// An archive argument is either a path to read or the archive's bytes, e.g.
+// from slurp-bytes or an HTTP response
+fn archive_bytes(value: Option<&MalValue>, name: &str) -> Result<Vec<u8>> {
+    match value {
+        Some(MalValue::Bytes(b)) => Ok(b.clone()),
+        Some(MalValue::String(path)) => {
+            require(Permission::FsRead, name)?;
+            fs::read(path).map_err(|e| format!("{}: {}: {}", name, path, e).into())
+        }
+        _ => Err(format!("{} requires a path or bytes as its first argument", name).into()),
+    }
+}
+
+fn entry_name<'a>(args: &'a [MalValue], name: &str) -> Result<&'a str> {
+    match args {
+        [_, MalValue::String(entry)] => Ok(entry),
+        _ => Err(format!("{} requires an archive and an entry name", name).into()),
+    }
+}
+
+// Entries are described the same way for both formats
+fn entry_info(name: String, size: u64, is_dir: bool) -> MalValue {
+    MalValue::Map(MalMap::from([
+        (MalKey::keyword("name"), MalValue::String(name)),
+        (MalKey::keyword("size"), MalValue::Number(size as i64)),
+        (MalKey::keyword("dir?"), MalValue::Bool(is_dir)),
+    ]))
+}
+
+fn open_zip(args: &[MalValue], name: &str) -> Result<ZipArchive<Cursor<Vec<u8>>>> {
+    let bytes = archive_bytes(args.first(), name)?;
+    ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("{}: {}", name, e).into())
+}
+
+// (zip/entries archive) is a vector of {:name :size :dir?} maps in archive order
+pub fn zip_entries(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("zip/entries requires exactly one archive".into());
+    }
+    let mut archive = open_zip(args, "zip/entries")?;
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| format!("zip/entries: {}", e))?;
+        entries.push(entry_info(file.name().to_string(), file.size(), file.is_dir()));
+    }
+    Ok(MalValue::Square(entries))
+}
+
+// (zip/slurp-entry archive name) is the entry's uncompressed contents as bytes
+pub fn zip_slurp_entry(args: &[MalValue]) -> Result<MalValue> {
+    let entry = entry_name(args, "zip/slurp-entry")?;
+    let mut archive = open_zip(args, "zip/slurp-entry")?;
+    let mut file = archive
+        .by_name(entry)
+        .map_err(|e| format!("zip/slurp-entry: {}: {}", entry, e))?;
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .map_err(|e| format!("zip/slurp-entry: {}: {}", entry, e))?;
+    Ok(MalValue::Bytes(contents))
+}
+
+// Calls f on each entry of an uncompressed tar archive until it returns Some
+fn find_tar_entry<T>(
+    args: &[MalValue],
+    name: &str,
+    mut f: impl FnMut(&mut tar::Entry<Cursor<Vec<u8>>>, String) -> Result<Option<T>>,
+) -> Result<Option<T>> {
+    let bytes = archive_bytes(args.first(), name)?;
+    let mut archive = tar::Archive::new(Cursor::new(bytes));
+    let entries = archive.entries().map_err(|e| format!("{}: {}", name, e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("{}: {}", name, e))?;
+        let path = entry
+            .path()
+            .map_err(|e| format!("{}: {}", name, e))?
+            .to_string_lossy()
+            .into_owned();
+        if let Some(found) = f(&mut entry, path)? {
+            return Ok(Some(found));
+        }
+    }
+    Ok(None)
+}
+
+pub fn tar_entries(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("tar/entries requires exactly one archive".into());
+    }
+    let mut entries = Vec::new();
+    find_tar_entry(args, "tar/entries", |entry, path| {
+        let is_dir = entry.header().entry_type().is_dir();
+        entries.push(entry_info(path, entry.size(), is_dir));
+        Ok(None::<()>)
+    })?;
+    Ok(MalValue::Square(entries))
+}
+
+pub fn tar_slurp_entry(args: &[MalValue]) -> Result<MalValue> {
+    let wanted = entry_name(args, "tar/slurp-entry")?;
+    let found = find_tar_entry(args, "tar/slurp-entry", |entry, path| {
+        if path != wanted {
+            return Ok(None);
+        }
+        let mut contents = Vec::new();
+        entry
+            .read_to_end(&mut contents)
+            .map_err(|e| format!("tar/slurp-entry: {}: {}", wanted, e))?;
+        Ok(Some(contents))
+    })?;
+    found
+        .map(MalValue::Bytes)
+        .ok_or_else(|| format!("tar/slurp-entry: {}: no such entry", wanted).into())
+}
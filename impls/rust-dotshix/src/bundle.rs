@@ -0,0 +1,512 @@
+use crate::core::{expand_macro, macro_call};
+use crate::error::MalError;
+use crate::interpreter::{reader_limits, EvalCtx, Interpreter};
+use crate::printer::pr_str;
+use crate::reader::{format_pest_error, map_from_entries, read_forms_spanned, Span, Spanned};
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// A compiled script (.malc) is MAGIC, the format VERSION, and then the script's
+// top-level forms as one list, with every macro call outside quoted code
+// already expanded. Loading one skips both reading and macro expansion.
+const MAGIC: &[u8] = b"MALC";
+const VERSION: u8 = 1;
+
+// One tag byte per value, followed by its payload. Integers, floats, and
+// lengths are little-endian; strings, symbols, and keywords are UTF-8.
+const NIL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const INTEGER: u8 = 3;
+const FLOAT: u8 = 4;
+const STRING: u8 = 5;
+const SYMBOL: u8 = 6;
+const KEYWORD: u8 = 7;
+const LIST: u8 = 8;
+const VECTOR: u8 = 9;
+const MAP: u8 = 10;
+const SET: u8 = 11;
+const TAGGED: u8 = 12;
+const BYTES: u8 = 13;
+
+// Compiled forms keep no source positions
+const NO_SPAN: Span = Span {
+    start: 0,
+    end: 0,
+    line: 0,
+    column: 0,
+    end_line: 0,
+};
+
+pub fn is_bundle(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+// Forms left exactly as written: their arguments are data, or syntax that
+// binds names in ways expand_all doesn't track. Whatever macro calls they hold
+// are expanded when the compiled script runs, as they would be from source.
+const UNEXPANDED: &[&str] = &[
+    "quote",
+    "quasiquote",
+    "quasiquoteexpand",
+    "macroexpand",
+    "macroexpand-1",
+    "defrecord",
+    "defprotocol",
+    "match",
+];
+
+// Every symbol in form, for the names a parameter list or binding form binds
+// and the globals a definition refers to
+fn symbols(form: &MalValue, out: &mut HashSet<String>) {
+    match form {
+        MalValue::Symbol(name) => {
+            out.insert(name.clone());
+        }
+        MalValue::Round(items) | MalValue::Square(items) | MalValue::Set(items) => {
+            items.iter().for_each(|item| symbols(item, out))
+        }
+        MalValue::Map(map) => map.values().for_each(|value| symbols(value, out)),
+        _ => {}
+    }
+}
+
+fn expand_each(
+    items: &[MalValue],
+    env: &Rc<RefCell<Env>>,
+    bound: &HashSet<String>,
+    ctx: &EvalCtx,
+) -> Result<Vec<MalValue>> {
+    items.iter().map(|item| expand_all(item, env, bound, ctx)).collect()
+}
+
+// head followed by the body forms expanded with names bound as well
+fn with_body(
+    head: &[MalValue],
+    body: &[MalValue],
+    env: &Rc<RefCell<Env>>,
+    bound: &HashSet<String>,
+    ctx: &EvalCtx,
+) -> Result<MalValue> {
+    let mut items = head.to_vec();
+    items.extend(expand_each(body, env, bound, ctx)?);
+    Ok(MalValue::Round(items))
+}
+
+// (let* [name value ...] body...) and the forms shaped like it: names stay as
+// they are, and each value and the body see the names bound before them
+fn expand_bindings(
+    items: &[MalValue],
+    env: &Rc<RefCell<Env>>,
+    bound: &HashSet<String>,
+    ctx: &EvalCtx,
+) -> Result<MalValue> {
+    let (head, bindings, body) = match items {
+        [head, MalValue::Square(bindings) | MalValue::Round(bindings), body @ ..] => {
+            (head, bindings, body)
+        }
+        _ => return Ok(MalValue::Round(items.to_vec())),
+    };
+    let mut bound = bound.clone();
+    if matches!(head, MalValue::Symbol(head) if head == "letrec*") {
+        bindings.iter().step_by(2).for_each(|name| symbols(name, &mut bound));
+    }
+    let mut expanded = Vec::new();
+    for pair in bindings.chunks(2) {
+        expanded.push(pair[0].clone());
+        if let Some(value) = pair.get(1) {
+            expanded.push(expand_all(value, env, &bound, ctx)?);
+        }
+        symbols(&pair[0], &mut bound);
+    }
+    let bindings = match &items[1] {
+        MalValue::Square(_) => MalValue::Square(expanded),
+        _ => MalValue::Round(expanded),
+    };
+    with_body(&[head.clone(), bindings], body, env, &bound, ctx)
+}
+
+// Expands macro calls throughout form. Parameter lists and binding names are
+// left alone, and a call whose head is locally bound is not a macro call even
+// when a macro of that name exists.
+fn expand_all(
+    form: &MalValue,
+    env: &Rc<RefCell<Env>>,
+    bound: &HashSet<String>,
+    ctx: &EvalCtx,
+) -> Result<MalValue> {
+    let mut form = form.clone();
+    while let Some((name, def)) = macro_call(&form, env) {
+        if bound.contains(&name) {
+            break;
+        }
+        form = expand_macro(&form, &def, ctx)?;
+    }
+    let items = match &form {
+        MalValue::Round(items) => items,
+        MalValue::Square(items) => {
+            return Ok(MalValue::Square(expand_each(items, env, bound, ctx)?))
+        }
+        MalValue::Map(map) => {
+            return Ok(MalValue::Map(
+                map.iter()
+                    .map(|(key, value)| Ok((key.clone(), expand_all(value, env, bound, ctx)?)))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        _ => return Ok(form),
+    };
+    let head = match items.first() {
+        Some(MalValue::Symbol(head)) if !bound.contains(head) => head.as_str(),
+        _ => return Ok(MalValue::Round(expand_each(items, env, bound, ctx)?)),
+    };
+    match head {
+        _ if UNEXPANDED.contains(&head) => Ok(form.clone()),
+        "let*" | "letrec*" | "binding" | "with-open" => expand_bindings(items, env, bound, ctx),
+        // (fn* [name] params body...) and (defmethod name dispatch params body...)
+        "fn*" | "defmethod" => {
+            let params = items
+                .iter()
+                .skip(1)
+                .position(|item| matches!(item, MalValue::Round(_) | MalValue::Square(_)))
+                .map_or(items.len(), |index| index + 2);
+            let mut bound = bound.clone();
+            items[1..params].iter().for_each(|item| symbols(item, &mut bound));
+            with_body(&items[..params], &items[params..], env, &bound, ctx)
+        }
+        "as->" => match items.as_slice() {
+            [head, expr, name, forms @ ..] => {
+                let head = [head.clone(), expand_all(expr, env, bound, ctx)?, name.clone()];
+                let mut bound = bound.clone();
+                symbols(name, &mut bound);
+                with_body(&head, forms, env, &bound, ctx)
+            }
+            _ => Ok(form.clone()),
+        },
+        "try*" => match items.as_slice() {
+            [head, expr, MalValue::Round(catch)] => {
+                let catch = match catch.as_slice() {
+                    [catch_head, name, handler @ ..] => {
+                        let mut bound = bound.clone();
+                        symbols(name, &mut bound);
+                        with_body(&[catch_head.clone(), name.clone()], handler, env, &bound, ctx)?
+                    }
+                    _ => MalValue::Round(catch.clone()),
+                };
+                Ok(MalValue::Round(vec![head.clone(), expand_all(expr, env, bound, ctx)?, catch]))
+            }
+            _ => Ok(MalValue::Round(expand_each(items, env, bound, ctx)?)),
+        },
+        _ => Ok(MalValue::Round(expand_each(items, env, bound, ctx)?)),
+    }
+}
+
+// The form and name of a top-level (def! name ...) or (defmacro! name ...)
+fn definition(form: &MalValue) -> Option<(&str, &str)> {
+    match form {
+        MalValue::Round(items) => match items.as_slice() {
+            [MalValue::Symbol(head), MalValue::Symbol(name), ..]
+                if head == "def!" || head == "defmacro!" =>
+            {
+                Some((head, name))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Evaluates the definitions macros need while compiling. Every defmacro! runs
+// as it is reached, along with the earlier def! forms it refers to, directly
+// or through other definitions; a def! a macro refers to that comes after the
+// macro runs as it is reached. Each in-ns runs too, since it decides what the
+// ::key forms after it read as. Nothing else in the script runs.
+struct Compiler<'a> {
+    env: Rc<RefCell<Env>>,
+    ctx: &'a EvalCtx,
+    // def! forms not yet evaluated, by name
+    pending: HashMap<String, MalValue>,
+    // Names referred to by the definitions evaluated so far
+    needed: HashSet<String>,
+}
+
+impl Compiler<'_> {
+    fn evaluate(&mut self, form: &MalValue) -> Result<()> {
+        let mut names = HashSet::new();
+        symbols(form, &mut names);
+        for name in &names {
+            if let Some(definition) = self.pending.remove(name) {
+                self.evaluate(&definition)?;
+            }
+        }
+        self.needed.extend(names);
+        crate::eval(form, Rc::clone(&self.env), self.ctx).map(drop)
+    }
+
+    fn define(&mut self, form: &MalValue) -> Result<()> {
+        if let MalValue::Round(items) = form {
+            if let Some(MalValue::Symbol(head)) = items.first() {
+                if head == "in-ns" {
+                    return crate::eval(form, Rc::clone(&self.env), self.ctx).map(drop);
+                }
+            }
+        }
+        match definition(form) {
+            Some(("defmacro!", _)) => self.evaluate(form),
+            Some((_, name)) if self.needed.contains(name) => self.evaluate(form),
+            Some((_, name)) => {
+                self.pending.insert(name.to_string(), form.clone());
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// Reads source and expands its forms in order, using the interpreter's macros
+// and those the file defines
+pub fn compile(interpreter: &Interpreter, source: &str) -> Result<Vec<u8>> {
+    let _active = interpreter.activate();
+    let (env, ctx) = (interpreter.env(), interpreter.ctx());
+    let forms = read_forms_spanned(source).map_err(|e| format_pest_error(*e))?;
+    let mut compiler = Compiler {
+        env: Rc::clone(&env),
+        ctx,
+        pending: HashMap::new(),
+        needed: HashSet::new(),
+    };
+    let max_nesting = reader_limits().max_nesting;
+    let mut expanded = Vec::new();
+    for form in forms {
+        let form = form.map_err(|e| format_pest_error(*e))?;
+        if let MalValue::Comment(_) = form.value {
+            continue;
+        }
+        let form = expand_all(&form.value, &env, &HashSet::new(), ctx)?;
+        // An expansion can nest deeper than its source, but must still load
+        if nesting(&form) > max_nesting {
+            return Err(nesting_error(max_nesting).into());
+        }
+        compiler.define(&form)?;
+        expanded.push(form);
+    }
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    write_value(&MalValue::Round(expanded), &mut out)?;
+    Ok(out)
+}
+
+pub fn compile_file(interpreter: &Interpreter, input: &str, output: &str) -> Result<()> {
+    let source = fs::read_to_string(input).map_err(|e| format!("{}: {}", input, e))?;
+    let bundle = compile(interpreter, &source).map_err(|e| format!("{}: {}", input, e))?;
+    fs::write(output, bundle).map_err(|e| format!("{}: {}", output, e).into())
+}
+
+// The forms of a compiled script
+pub fn load(bytes: &[u8]) -> Result<Vec<Spanned>> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err("not a compiled script".into());
+    };
+    let mut decoder = Decoder {
+        input: rest,
+        pos: 0,
+        depth: 0,
+        max_nesting: reader_limits().max_nesting,
+    };
+    if decoder.byte()? != VERSION {
+        return Err("compiled by a different version; compile the script again".into());
+    }
+    if decoder.byte()? != LIST {
+        return Err("malformed compiled script".into());
+    }
+    let forms = decoder.items()?;
+    if decoder.pos != rest.len() {
+        return Err("trailing bytes in compiled script".into());
+    }
+    Ok(forms
+        .into_iter()
+        .map(|value| Spanned {
+            value,
+            span: NO_SPAN,
+            children: Vec::new(),
+            leading_comments: Vec::new(),
+            trailing_comments: Vec::new(),
+        })
+        .collect())
+}
+
+fn nesting_error(max_nesting: usize) -> String {
+    format!("compiled script nests more than {} levels deep", max_nesting)
+}
+
+// How many collections deep value goes
+fn nesting(value: &MalValue) -> usize {
+    let deepest = |items: &mut dyn Iterator<Item = &MalValue>| items.map(nesting).max();
+    match value {
+        MalValue::Round(items) | MalValue::Square(items) | MalValue::Set(items) => {
+            1 + deepest(&mut items.iter()).unwrap_or(0)
+        }
+        MalValue::Map(map) => 1 + deepest(&mut map.values()).unwrap_or(0),
+        MalValue::Tagged(_, inner) => 1 + nesting(inner),
+        _ => 0,
+    }
+}
+
+fn write_len(len: usize, out: &mut Vec<u8>) -> Result<()> {
+    let len = u32::try_from(len).map_err(|_| "value too large to compile".to_string())?;
+    out.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn write_text(tag: u8, text: &str, out: &mut Vec<u8>) -> Result<()> {
+    out.push(tag);
+    write_len(text.len(), out)?;
+    out.extend_from_slice(text.as_bytes());
+    Ok(())
+}
+
+fn write_items(tag: u8, items: &[MalValue], out: &mut Vec<u8>) -> Result<()> {
+    out.push(tag);
+    write_len(items.len(), out)?;
+    items.iter().try_for_each(|item| write_value(item, out))
+}
+
+fn write_value(value: &MalValue, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        MalValue::Nil => out.push(NIL),
+        MalValue::Bool(false) => out.push(FALSE),
+        MalValue::Bool(true) => out.push(TRUE),
+        MalValue::Number(n) => {
+            out.push(INTEGER);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        MalValue::Float(f) => {
+            out.push(FLOAT);
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        MalValue::String(s) => write_text(STRING, s, out)?,
+        MalValue::Symbol(s) => write_text(SYMBOL, s, out)?,
+        MalValue::Atom(k) => write_text(KEYWORD, k, out)?,
+        MalValue::Round(items) => write_items(LIST, items, out)?,
+        MalValue::Square(items) => write_items(VECTOR, items, out)?,
+        MalValue::Set(items) => write_items(SET, items, out)?,
+        MalValue::Map(map) => {
+            out.push(MAP);
+            write_len(map.len(), out)?;
+            for (key, value) in map {
+                write_value(&key.clone().into(), out)?;
+                write_value(value, out)?;
+            }
+        }
+        MalValue::Tagged(tag, inner) => {
+            write_text(TAGGED, tag, out)?;
+            write_value(inner, out)?;
+        }
+        MalValue::Bytes(b) => {
+            out.push(BYTES);
+            write_len(b.len(), out)?;
+            out.extend_from_slice(b);
+        }
+        // A macro can put any value into the code it returns
+        other => {
+            return Err(format!(
+                "{} cannot be stored in a compiled script",
+                pr_str(other, true)
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
+
+// Collections nest no deeper than the reader allows, as they would have been
+// read from source
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+    max_nesting: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.input.len() - self.pos < n {
+            return Err("compiled script ends unexpectedly".into());
+        }
+        let bytes = &self.input[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn eight(&mut self) -> Result<[u8; 8]> {
+        Ok(self.take(8)?.try_into().expect("took eight bytes"))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?.try_into().expect("took four bytes");
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn text(&mut self) -> Result<String> {
+        let len = self.len()?;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| "invalid UTF-8 in compiled script".into())
+    }
+
+    fn items(&mut self) -> Result<Vec<MalValue>> {
+        let len = self.len()?;
+        (0..len).map(|_| self.value()).collect()
+    }
+
+    fn nested<T>(&mut self, decode: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == self.max_nesting {
+            return Err(nesting_error(self.max_nesting).into());
+        }
+        self.depth += 1;
+        let result = decode(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn value(&mut self) -> Result<MalValue> {
+        Ok(match self.byte()? {
+            NIL => MalValue::Nil,
+            FALSE => MalValue::Bool(false),
+            TRUE => MalValue::Bool(true),
+            INTEGER => MalValue::Number(i64::from_le_bytes(self.eight()?)),
+            FLOAT => MalValue::Float(f64::from_le_bytes(self.eight()?)),
+            STRING => MalValue::String(self.text()?),
+            SYMBOL => MalValue::Symbol(self.text()?),
+            KEYWORD => MalValue::Atom(self.text()?),
+            LIST => MalValue::Round(self.nested(Self::items)?),
+            VECTOR => MalValue::Square(self.nested(Self::items)?),
+            SET => MalValue::Set(self.nested(Self::items)?),
+            MAP => {
+                let entries = self.nested(|decoder| {
+                    let len = decoder.len()?;
+                    (0..2 * len).map(|_| decoder.value()).collect::<Result<Vec<_>>>()
+                })?;
+                MalValue::Map(map_from_entries(&entries)?)
+            }
+            TAGGED => {
+                let tag = self.text()?;
+                MalValue::Tagged(tag, Box::new(self.nested(Self::value)?))
+            }
+            BYTES => {
+                let len = self.len()?;
+                MalValue::Bytes(self.take(len)?.to_vec())
+            }
+            tag => return Err(format!("unknown tag {} in compiled script", tag).into()),
+        })
+    }
+}
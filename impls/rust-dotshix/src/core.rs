@@ -1,82 +1,797 @@
+#[cfg(feature = "stdlib")]
+use crate::archive::{tar_entries, tar_slurp_entry, zip_entries, zip_slurp_entry};
+use crate::coverage;
+use crate::edn::{edn_read, edn_write};
+use crate::error::MalError;
+use crate::host::{host_value, tag, tagged, tagged_question, untag};
+#[cfg(feature = "stdlib")]
+use crate::http::http_serve;
+use crate::interpreter::{
+    active_ctx, cancel_timer, division_mode, eval_step, forbid_in_deterministic, global_env,
+    logical_sleep, logical_time, next_random, next_timer, notify_def, print_readably, require,
+    schedule, set_namespace, strict_redef, DivisionMode, EvalCtx, Permission,
+};
+#[cfg(feature = "stdlib")]
+use crate::msgpack::{msgpack_decode, msgpack_encode};
+use crate::multimethod::{defmethod, defmulti, derive, isa_question};
+use crate::output::{capture, flush_out, write_err, write_out, Stream};
 use crate::printer::pr_str;
+use crate::pattern::match_form;
+use crate::process::{process_spawn, process_wait};
+use crate::quasiquote::{quasiquote, quasiquoteexpand};
+use crate::protocol::{
+    defprotocol, extend, extension, satisfies_question, COUNT_IMPL, SEQ_IMPL,
+};
+use crate::reader::{
+    format_pest_error, map_from_entries, map_get, parse_input, MalKey, MalMap,
+};
+use crate::record::{defrecord, record_entries, with_entries};
+use crate::resource::{
+    close, file_open, file_read_all, file_read_line, file_write, kv_delete, kv_get, kv_keys,
+    kv_open, kv_put, open_question,
+};
+use crate::script::Source;
+use crate::spec::{
+    defspec, explain, explain_data, spec_and, spec_coll_of, spec_nilable, spec_or, valid_question,
+};
+use crate::template::render;
+use crate::term::{
+    progress_bar, progress_tick, term_clear, term_color, term_height, term_move, term_width,
+    tty_question,
+};
+use crate::zipper::{
+    zip_down, zip_edit, zip_left, zip_node, zip_replace, zip_right, zip_root, zip_up, zip_zipper,
+};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use regex::RegexBuilder;
+use crate::env::{BuiltinFn, FnDef, Partial, SpecialFormFn};
 use crate::Function;
-use std::cell::RefCell;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
 use std::rc::Rc;
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
 use crate::eval;
 use crate::MalValue;
 use crate::Env;
 
 use std::result::Result as StdResult;
-type Result<T> = StdResult<T, String>;
+type Result<T> = StdResult<T, MalError>;
 
 
 // Utility Functions for Arithmetic Operations
-fn validate_and_extract(args: &[MalValue], func_name: &str) -> Result<(i64, i64)> {
+
+// Two numeric arguments; an integer paired with a float is promoted to a float
+enum Operands {
+    Ints(i64, i64),
+    Floats(f64, f64),
+}
+
+impl Operands {
+    // Integers compare exactly, even beyond the 53 bits a float holds; None
+    // when either float is NaN
+    fn compare(self) -> Option<Ordering> {
+        match self {
+            Operands::Ints(a, b) => Some(a.cmp(&b)),
+            Operands::Floats(a, b) => a.partial_cmp(&b),
+        }
+    }
+}
+
+fn validate_and_extract(args: &[MalValue], func_name: &str) -> Result<Operands> {
     if args.len() != 2 {
-        return Err(format!("Expected exactly two arguments for {} function", func_name));
+        return Err(format!("Expected exactly two arguments for {} function", func_name).into());
     }
 
-    if let (MalValue::Number(a), MalValue::Number(b)) = (&args[0], &args[1]) {
-        Ok((*a, *b))
-    } else {
-        Err("Expected number arguments".into())
+    match (&args[0], &args[1]) {
+        (MalValue::Number(a), MalValue::Number(b)) => Ok(Operands::Ints(*a, *b)),
+        (MalValue::Float(a), MalValue::Float(b)) => Ok(Operands::Floats(*a, *b)),
+        (MalValue::Number(a), MalValue::Float(b)) => Ok(Operands::Floats(*a as f64, *b)),
+        (MalValue::Float(a), MalValue::Number(b)) => Ok(Operands::Floats(*a, *b as f64)),
+        _ => Err("Expected number arguments".into()),
     }
 }
 
+fn int_result(value: Option<i64>, func_name: &str) -> Result<MalValue> {
+    value
+        .map(MalValue::Number)
+        .ok_or_else(|| format!("Integer overflow in {}", func_name).into())
+}
+
+// Variadic arithmetic and comparison, so (apply f xs) works for any length xs
+// that makes sense:
+//
+//   builtin         arity   zero args   one arg
+//   + *             0+      0, 1        the argument
+//   - /             1+      error       (- 0 x), (/ 1 x)
+//   = < <= > >=     1+      error       true; more args compare each adjacent pair
+//   min max         1+      error       the argument
+//   quot            2       error       error
+//
+// Everything else in BUILTINS takes the arity its doc string describes.
+
+// Applies a two-argument operation left to right across args, which must be numbers
+fn fold_numbers(args: &[MalValue], op: BuiltinFn, func_name: &str) -> Result<MalValue> {
+    let Some((first, rest)) = args.split_first() else {
+        return Err(format!("{} requires at least one argument", func_name).into());
+    };
+    if !matches!(first, MalValue::Number(_) | MalValue::Float(_)) {
+        return Err("Expected number arguments".into());
+    }
+    rest.iter().try_fold(first.clone(), |acc, arg| op(&[acc, arg.clone()]))
+}
 
 // Builtin Functions
+fn add2(args: &[MalValue]) -> Result<MalValue> {
+    match validate_and_extract(args, "add")? {
+        Operands::Ints(a, b) => int_result(a.checked_add(b), "add"),
+        Operands::Floats(a, b) => Ok(MalValue::Float(a + b)),
+    }
+}
+
 fn add(args: &[MalValue]) -> Result<MalValue> {
-    let (a, b) = validate_and_extract(args, "add")?;
-    Ok(MalValue::Number(a + b))
+    match args {
+        [] => Ok(MalValue::Number(0)),
+        _ => fold_numbers(args, add2, "add"),
+    }
+}
+
+fn sub2(args: &[MalValue]) -> Result<MalValue> {
+    match validate_and_extract(args, "subtract")? {
+        Operands::Ints(a, b) => int_result(a.checked_sub(b), "subtract"),
+        Operands::Floats(a, b) => Ok(MalValue::Float(a - b)),
+    }
 }
 
 fn sub(args: &[MalValue]) -> Result<MalValue> {
-    let (a, b) = validate_and_extract(args, "subtract")?;
-    Ok(MalValue::Number(a - b))
+    match args {
+        [MalValue::Float(f)] => Ok(MalValue::Float(-f)),
+        [x] => sub2(&[MalValue::Number(0), x.clone()]),
+        _ => fold_numbers(args, sub2, "-"),
+    }
+}
+
+fn mult2(args: &[MalValue]) -> Result<MalValue> {
+    match validate_and_extract(args, "multiply")? {
+        Operands::Ints(a, b) => int_result(a.checked_mul(b), "multiply"),
+        Operands::Floats(a, b) => Ok(MalValue::Float(a * b)),
+    }
 }
 
 fn mult(args: &[MalValue]) -> Result<MalValue> {
-    let (a, b) = validate_and_extract(args, "multiply")?;
-    Ok(MalValue::Number(a * b))
+    match args {
+        [] => Ok(MalValue::Number(1)),
+        _ => fold_numbers(args, mult2, "multiply"),
+    }
+}
+
+// Integer division by zero is an error; float division follows IEEE 754, so
+// (/ 1.0 0.0) is ##Inf. Integer results depend on the interpreter's DivisionMode.
+fn divide2(args: &[MalValue]) -> Result<MalValue> {
+    match validate_and_extract(args, "divide")? {
+        Operands::Ints(_, 0) => Err("Division by 0".into()),
+        Operands::Ints(a, b) if division_mode() == DivisionMode::Float && a % b != 0 => {
+            Ok(MalValue::Float(a as f64 / b as f64))
+        }
+        Operands::Ints(a, b) => int_result(a.checked_div(b), "divide"),
+        Operands::Floats(a, b) => Ok(MalValue::Float(a / b)),
+    }
 }
 
 fn divide(args: &[MalValue]) -> Result<MalValue> {
-    let (a, b) = validate_and_extract(args, "divide")?;
-    if b != 0 {
-        Ok(MalValue::Number(a / b))
+    match args {
+        [x] => divide2(&[MalValue::Number(1), x.clone()]),
+        _ => fold_numbers(args, divide2, "/"),
+    }
+}
+
+// (min x ...) and (max x ...) return the winning argument itself, so an
+// integer stays an integer even when compared against floats. An argument
+// replaces the best so far when it compares to it as keep_new.
+fn extremum(args: &[MalValue], func_name: &str, keep_new: Ordering) -> Result<MalValue> {
+    let Some((first, rest)) = args.split_first() else {
+        return Err(format!("{} requires at least one argument", func_name).into());
+    };
+    let mut best = first;
+    if !matches!(best, MalValue::Number(_) | MalValue::Float(_)) {
+        return Err("Expected number arguments".into());
+    }
+    for arg in rest {
+        let pair = [arg.clone(), best.clone()];
+        if validate_and_extract(&pair, func_name)?.compare() == Some(keep_new) {
+            best = arg;
+        }
+    }
+    Ok(best.clone())
+}
+
+fn min(args: &[MalValue]) -> Result<MalValue> {
+    extremum(args, "min", Ordering::Less)
+}
+
+fn max(args: &[MalValue]) -> Result<MalValue> {
+    extremum(args, "max", Ordering::Greater)
+}
+
+// (quot a b) always truncates toward zero, whatever the division mode
+fn quot(args: &[MalValue]) -> Result<MalValue> {
+    match validate_and_extract(args, "quot")? {
+        Operands::Ints(_, 0) => Err("Division by 0".into()),
+        Operands::Ints(a, b) => int_result(a.checked_div(b), "quot"),
+        Operands::Floats(_, 0.0) => Err("Division by 0".into()),
+        Operands::Floats(a, b) => Ok(MalValue::Float((a / b).trunc())),
+    }
+}
+
+fn inc(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [x] => add2(&[x.clone(), MalValue::Number(1)]),
+        _ => Err("inc requires exactly one argument".into()),
+    }
+}
+
+fn dec(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [x] => sub2(&[x.clone(), MalValue::Number(1)]),
+        _ => Err("dec requires exactly one argument".into()),
+    }
+}
+
+// zero?, pos?, and neg? accept integers and floats
+fn sign_question(args: &[MalValue], name: &str, test: fn(f64) -> bool) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(n)] => Ok(MalValue::bool(test(*n as f64))),
+        [MalValue::Float(f)] => Ok(MalValue::bool(test(*f))),
+        [other] => Err(format!("{}: {} is not a number", name, pr_str(other, true)).into()),
+        _ => Err(format!("{} requires exactly one argument", name).into()),
+    }
+}
+
+fn zero_question(args: &[MalValue]) -> Result<MalValue> {
+    sign_question(args, "zero?", |n| n == 0.0)
+}
+
+fn pos_question(args: &[MalValue]) -> Result<MalValue> {
+    sign_question(args, "pos?", |n| n > 0.0)
+}
+
+fn neg_question(args: &[MalValue]) -> Result<MalValue> {
+    sign_question(args, "neg?", |n| n < 0.0)
+}
+
+// even? and odd? only accept integers
+fn parity_question(args: &[MalValue], name: &str, even: bool) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(n)] => Ok(MalValue::bool((n % 2 == 0) == even)),
+        [other] => Err(format!("{}: {} is not an integer", name, pr_str(other, true)).into()),
+        _ => Err(format!("{} requires exactly one argument", name).into()),
+    }
+}
+
+fn even_question(args: &[MalValue]) -> Result<MalValue> {
+    parity_question(args, "even?", true)
+}
+
+fn odd_question(args: &[MalValue]) -> Result<MalValue> {
+    parity_question(args, "odd?", false)
+}
+
+// Text of a caught panic, for reporting it as an ordinary error
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+thread_local! {
+    // How many catch_panic calls are running on this thread
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+}
+
+// Runs f, turning a panic into an error, so a bug in a builtin, a special form,
+// or the reader becomes a catchable error instead of ending the session
+pub fn catch_panic<T>(what: &str, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let caught = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+    caught.unwrap_or_else(|payload| {
+        Err(format!("Internal error in {}: {}", what, panic_message(&*payload)).into())
+    })
+}
+
+// Keeps the panic hook from printing "thread 'main' panicked at ..." for the
+// panics catch_panic reports as errors. Any other panic reaches the hook that
+// was installed before.
+pub fn quiet_caught_panics() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.with(Cell::get) == 0 {
+                report(info);
+            }
+        }));
+    });
+}
+
+// Calls a function value with already-evaluated arguments
+pub fn apply(func: &Function, args: &[MalValue]) -> Result<MalValue> {
+    match func {
+        Function::Builtin(func) => catch_panic("builtin", || func(args)),
+        Function::UserDefined(def) => {
+            let new_env = bind_params(def, args)?;
+            let ctx = active_ctx();
+            let mut result = MalValue::Nil;
+            for expr in def.body.iter() {
+                result = eval(expr, Rc::clone(&new_env), &ctx)?;
+            }
+            Ok(result)
+        }
+        Function::Partial(partial) => {
+            let mut all = partial.args.clone();
+            all.extend_from_slice(args);
+            call(&partial.func, &all)
+        }
+        Function::Composed(funcs) => {
+            let Some((innermost, rest)) = funcs.split_last() else {
+                return match args {
+                    [value] => Ok(value.clone()),
+                    _ => Err("(comp) requires exactly one argument".into()),
+                };
+            };
+            rest.iter()
+                .rev()
+                .try_fold(call(innermost, args)?, |value, func| {
+                    call(func, std::slice::from_ref(&value))
+                })
+        }
+    }
+}
+
+// The form a macro call expands to. eval caches the expansion of each call form
+// (see cached_expansion), so a macro's side effects happen once per form.
+pub fn expand_macro(form: &MalValue, def: &Rc<FnDef>, ctx: &EvalCtx) -> Result<MalValue> {
+    let args = match form {
+        MalValue::Round(items) => &items[1..],
+        _ => return Err("macro call must be a list".into()),
+    };
+    let macro_env = bind_params(def, args)?;
+    let mut expansion = MalValue::Nil;
+    for expr in def.body.iter() {
+        expansion = eval(expr, Rc::clone(&macro_env), ctx)?;
+    }
+    Ok(expansion)
+}
+
+// The environment a user-defined function's body runs in, with its parameters
+// bound to args, and a named fn*'s name to the function
+pub fn bind_params(def: &Rc<FnDef>, args: &[MalValue]) -> Result<Rc<RefCell<Env>>> {
+    let FnDef {
+        params,
+        rest_param,
+        env: func_env,
+        name,
+        ..
+    } = &**def;
+    coverage::body_entered(&def.body);
+    let num_fixed_params = params.len();
+    let num_args = args.len();
+
+    if num_args < num_fixed_params {
+        return Err(format!(
+            "Expected at least {} arguments but got {}",
+            num_fixed_params, num_args
+        )
+        .into());
+    }
+
+    // Create a new environment for the function
+    let new_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+        &func_env.borrow().get_bindings(),
+    )))));
+
+    // Parameters shadow the function's own name
+    if let Some(name) = name {
+        let func = MalValue::BuiltinFunction(Function::UserDefined(Rc::clone(def)));
+        new_env.borrow_mut().set(name.clone(), func);
+    }
+
+    // Bind fixed parameters
+    for (param, arg) in params.iter().zip(args.iter()) {
+        new_env.borrow_mut().set(param.clone(), arg.clone());
+    }
+
+    // Handle rest parameter
+    if let Some(rest_param_name) = rest_param {
+        let rest_args = args[num_fixed_params..].to_vec();
+        new_env
+            .borrow_mut()
+            .set(rest_param_name.clone(), MalValue::Round(rest_args));
+    } else if num_args > num_fixed_params {
+        return Err(format!(
+            "Expected {} arguments but got {}",
+            num_fixed_params, num_args
+        )
+        .into());
+    }
+
+    Ok(new_env)
+}
+
+// A function value that calls builtin with data and a list of the arguments it
+// was given, like (fn* (& args) (builtin data args)). It is how builtins such as
+// protocol methods and record constructors get a value to close over.
+pub fn bound_builtin(builtin: BuiltinFn, data: MalValue, env: &Rc<RefCell<Env>>) -> MalValue {
+    const ARGS: &str = "args";
+    let body = MalValue::Round(vec![
+        MalValue::BuiltinFunction(Function::Builtin(builtin)),
+        MalValue::Round(vec![MalValue::Symbol("quote".to_string()), data]),
+        MalValue::Symbol(ARGS.to_string()),
+    ]);
+    MalValue::BuiltinFunction(Function::UserDefined(Rc::new(FnDef {
+        params: Vec::new(),
+        rest_param: Some(ARGS.to_string()),
+        body: vec![body],
+        env: Rc::clone(env),
+        name: None,
+        is_macro: false,
+    })))
+}
+
+// Values that can appear in call position: functions, plus keywords, maps, and
+// vectors, which look themselves up in their argument or look up their argument
+pub fn is_callable(value: &MalValue) -> bool {
+    matches!(
+        value,
+        MalValue::BuiltinFunction(_)
+            | MalValue::Atom(_)
+            | MalValue::Map(_)
+            | MalValue::Square(_)
+    )
+}
+
+// Calls any callable value with already evaluated arguments
+pub fn call(func: &MalValue, args: &[MalValue]) -> Result<MalValue> {
+    match (func, args) {
+        (MalValue::BuiltinFunction(func), _) => apply(func, args),
+        // (:key m) and (:key m default)
+        (MalValue::Atom(_), [coll]) => get(&[coll.clone(), func.clone()]),
+        (MalValue::Atom(_), [coll, default]) => {
+            get(&[coll.clone(), func.clone(), default.clone()])
+        }
+        (MalValue::Atom(k), _) => Err(format!(
+            "Keyword {} called with {} arguments, expected a map and an optional default",
+            k,
+            args.len()
+        )
+        .into()),
+        // (m key) and (m key default)
+        (MalValue::Map(_), [key]) => get(&[func.clone(), key.clone()]),
+        (MalValue::Map(_), [key, default]) => {
+            get(&[func.clone(), key.clone(), default.clone()])
+        }
+        (MalValue::Map(_), _) => Err(format!(
+            "Map called with {} arguments, expected a key and an optional default",
+            args.len()
+        )
+        .into()),
+        // ([a b c] 1) is nth, so a missing index is an error rather than nil
+        (MalValue::Square(_), [index @ MalValue::Number(_)]) => {
+            nth(&[func.clone(), index.clone()])
+        }
+        (MalValue::Square(_), _) => {
+            Err("Vector called as a function requires an index".into())
+        }
+        _ => Err("First element is not a function".into()),
+    }
+}
+
+fn function_arg<'a>(value: &'a MalValue, name: &str) -> Result<&'a MalValue> {
+    if is_callable(value) {
+        Ok(value)
     } else {
-        Err("Division by 0".into())
+        Err(format!("{} requires a function as its first argument", name).into())
+    }
+}
+
+fn seq_arg<'a>(
+    value: &'a MalValue,
+    name: &str,
+) -> Result<Box<dyn Iterator<Item = MalValue> + 'a>> {
+    if let Some(seq) = extension(SEQ_IMPL, value)? {
+        return match seq {
+            MalValue::Round(items) | MalValue::Square(items) => Ok(Box::new(items.into_iter())),
+            MalValue::Nil => Ok(Box::new(std::iter::empty())),
+            other => Err(format!(
+                "{}: seq-impl returned {}, not a list or vector",
+                name,
+                pr_str(&other, true)
+            )
+            .into()),
+        };
+    }
+    // A record is a sequence of its fields' entries, like the map it holds
+    let value = match value {
+        MalValue::Tagged(_, fields) if record_entries(value).is_some() => fields,
+        _ => value,
+    };
+    value
+        .iter_seq()
+        .ok_or_else(|| format!("{}: {} is not a sequence", name, pr_str(value, true)).into())
+}
+
+pub fn is_truthy(value: &MalValue) -> bool {
+    !matches!(value, MalValue::Nil | MalValue::Bool(false))
+}
+
+pub fn map(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 2 {
+        return Err("map requires a function and a sequence".into());
+    }
+    let func = function_arg(&args[0], "map")?;
+    seq_arg(&args[1], "map")?
+        .map(|item| call(func, &[item]))
+        .collect::<Result<Vec<MalValue>>>()
+        .map(MalValue::Round)
+}
+
+pub fn filter(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 2 {
+        return Err("filter requires a predicate and a sequence".into());
+    }
+    let func = function_arg(&args[0], "filter")?;
+    let mut kept = Vec::new();
+    for item in seq_arg(&args[1], "filter")? {
+        if is_truthy(&call(func, std::slice::from_ref(&item))?) {
+            kept.push(item);
+        }
+    }
+    Ok(MalValue::Round(kept))
+}
+
+// (apply f a b coll) calls f with a, b, and the elements of coll
+pub fn apply_fn(args: &[MalValue]) -> Result<MalValue> {
+    let (func, rest) = match args.split_first() {
+        Some((func, rest)) if !rest.is_empty() => (function_arg(func, "apply")?, rest),
+        _ => return Err("apply requires a function and a sequence".into()),
+    };
+    let (coll, leading) = rest.split_last().unwrap();
+    let mut call_args = leading.to_vec();
+    call_args.extend(seq_arg(coll, "apply")?);
+    call(func, &call_args)
+}
+
+// (partial f a b) is a function of more arguments that calls f with a and b
+// before them. A partial of a partial shares the original function.
+pub fn partial(args: &[MalValue]) -> Result<MalValue> {
+    let Some((func, leading)) = args.split_first() else {
+        return Err("partial requires a function".into());
+    };
+    let func = function_arg(func, "partial")?;
+    let partial = match func {
+        MalValue::BuiltinFunction(Function::Partial(inner)) => Partial {
+            func: inner.func.clone(),
+            args: inner.args.iter().chain(leading).cloned().collect(),
+        },
+        _ => Partial {
+            func: func.clone(),
+            args: leading.to_vec(),
+        },
+    };
+    Ok(MalValue::BuiltinFunction(Function::Partial(Rc::new(partial))))
+}
+
+// (comp f g) is a function that calls g on its arguments and f on the result;
+// (comp) returns its single argument
+pub fn comp(args: &[MalValue]) -> Result<MalValue> {
+    let mut funcs = Vec::new();
+    for func in args {
+        match func {
+            MalValue::BuiltinFunction(Function::Composed(inner)) => {
+                funcs.extend(inner.iter().cloned())
+            }
+            _ if is_callable(func) => funcs.push(func.clone()),
+            _ => return Err(format!("comp: {} is not a function", pr_str(func, true)).into()),
+        }
+    }
+    Ok(MalValue::BuiltinFunction(Function::Composed(Rc::new(funcs))))
+}
+
+fn repeat_count(value: &MalValue, name: &str) -> Result<usize> {
+    match value {
+        MalValue::Number(n) if *n >= 0 => Ok(*n as usize),
+        _ => Err(format!("{} requires a non-negative count", name).into()),
+    }
+}
+
+// The longest list repeat builds, so a huge count fails cleanly instead of
+// aborting on the allocation
+const MAX_REPEAT: usize = 1 << 24;
+
+pub fn repeat(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [n, x] => match repeat_count(n, "repeat")? {
+            n if n > MAX_REPEAT => {
+                Err(format!("repeat: count is larger than {}", MAX_REPEAT).into())
+            }
+            n => Ok(MalValue::Round(vec![x.clone(); n])),
+        },
+        _ => Err("repeat requires a count and a value".into()),
+    }
+}
+
+// (repeatedly n f) calls f with no arguments n times, collecting the results
+pub fn repeatedly(args: &[MalValue]) -> Result<MalValue> {
+    let (n, func) = match args {
+        [n, f] => (repeat_count(n, "repeatedly")?, f),
+        _ => return Err("repeatedly requires a count and a function".into()),
+    };
+    if !is_callable(func) {
+        return Err("repeatedly requires a function as its second argument".into());
+    }
+    (0..n)
+        .map(|_| call(func, &[]))
+        .collect::<Result<Vec<MalValue>>>()
+        .map(MalValue::Round)
+}
+
+// (reduce f coll) seeds with the first element; (reduce f init coll) with init
+pub fn reduce(args: &[MalValue]) -> Result<MalValue> {
+    let (func, init, coll) = match args {
+        [f, coll] => (function_arg(f, "reduce")?, None, coll),
+        [f, init, coll] => (function_arg(f, "reduce")?, Some(init.clone()), coll),
+        _ => return Err("reduce requires a function, an optional init, and a sequence".into()),
+    };
+
+    let mut items = seq_arg(coll, "reduce")?;
+    let mut acc = match init.or_else(|| items.next()) {
+        Some(acc) => acc,
+        // Reducing an empty sequence without an initial value calls f with no arguments
+        None => return call(func, &[]),
+    };
+    for item in items {
+        acc = call(func, &[acc, item])?;
+    }
+    Ok(acc)
+}
+
+// Global definitions that shadow a builtin or special form warn, or fail under --strict-redef
+pub fn check_redefinition(form: &str, key: &str, env: &Rc<RefCell<Env>>) -> Result<()> {
+    if !env.borrow().is_global() || builtin_doc(key).is_none() {
+        return Ok(());
     }
+    if strict_redef() {
+        return Err(format!("{}: cannot redefine builtin {}", form, key).into());
+    }
+    write_err(&format!("Warning: {} redefines builtin {}\n", form, key))
 }
 
-pub fn def_bang(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
+pub fn def_bang(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
     if args.len() != 2 {
-        return Err("def! requires exactly two arguments".to_string());
+        return Err("def! requires exactly two arguments".into());
     }
 
     let key = match &args[0] {
         MalValue::Symbol(s) => s.clone(),
-        _ => return Err("def! first argument must be a symbol".to_string()),
+        _ => return Err("def! first argument must be a symbol".into()),
     };
 
-    let value = eval(&args[1], env.clone())?;
+    check_redefinition("def!", &key, &env)?;
+    let value = eval(&args[1], env.clone(), ctx)?;
     env.borrow_mut().set(key.clone(), value.clone());
+    notify_def(&key, &value);
+    Ok(value)
+}
+
+// (defmacro! name (fn* ...)) defines name as a macro
+pub fn defmacro_bang(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (key, value) = match args {
+        [MalValue::Symbol(key), value] => (key, value),
+        _ => return Err("defmacro! requires a symbol and a function".into()),
+    };
+    check_redefinition("defmacro!", key, &env)?;
+    let def = match eval(value, env.clone(), ctx)? {
+        MalValue::BuiltinFunction(Function::UserDefined(def)) => def,
+        other => {
+            let message = format!("defmacro!: {} is not a fn* function", pr_str(&other, true));
+            return Err(message.into());
+        }
+    };
+    let value = MalValue::BuiltinFunction(Function::UserDefined(Rc::new(FnDef {
+        params: def.params.clone(),
+        rest_param: def.rest_param.clone(),
+        body: def.body.clone(),
+        env: Rc::clone(&def.env),
+        name: def.name.clone(),
+        is_macro: true,
+    })));
+    env.borrow().set(key.clone(), value.clone());
+    notify_def(key, &value);
     Ok(value)
 }
 
-pub fn do_func(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
+// The macro a form calls, with the symbol it is called by
+pub fn macro_call(form: &MalValue, env: &Rc<RefCell<Env>>) -> Option<(String, Rc<FnDef>)> {
+    let MalValue::Round(items) = form else {
+        return None;
+    };
+    let Some(MalValue::Symbol(name)) = items.first() else {
+        return None;
+    };
+    match env.borrow().get(name)? {
+        MalValue::BuiltinFunction(Function::UserDefined(def)) if def.is_macro => {
+            Some((name.clone(), def))
+        }
+        _ => None,
+    }
+}
+
+// The form given to macroexpand or macroexpand-1; a quoted form is unwrapped,
+// so (macroexpand '(m x)) and (macroexpand (m x)) agree
+fn expansion_arg<'a>(args: &'a [MalValue], name: &str) -> Result<&'a MalValue> {
+    match args {
+        [MalValue::Round(items)] => match items.as_slice() {
+            [MalValue::Symbol(quote), form] if quote == "quote" => Ok(form),
+            _ => Ok(&args[0]),
+        },
+        [form] => Ok(form),
+        _ => Err(format!("{} requires exactly one form", name).into()),
+    }
+}
+
+// (macroexpand-1 (m x)) is the form the macro call expands to, unevaluated;
+// any other form is returned as it is
+pub fn macroexpand_1(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let form = expansion_arg(args, "macroexpand-1")?;
+    match macro_call(form, &env) {
+        Some((_, def)) => expand_macro(form, &def, ctx),
+        None => Ok(form.clone()),
+    }
+}
+
+// Expands until the form is no longer a macro call
+pub fn macroexpand(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let mut form = expansion_arg(args, "macroexpand")?.clone();
+    while let Some((_, def)) = macro_call(&form, &env) {
+        ctx.step()?;
+        form = expand_macro(&form, &def, ctx)?;
+    }
+    Ok(form)
+}
+
+// (quote x) returns x exactly as read
+pub fn quote(args: &[MalValue], _env: Rc<RefCell<Env>>, _ctx: &EvalCtx) -> Result<MalValue> {
+    match args {
+        [form] => Ok(form.clone()),
+        _ => Err("quote requires exactly one argument".into()),
+    }
+}
+
+pub fn do_func(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
     let mut res = MalValue::Nil;
 
     for expr in args {
-        res = eval(expr, Rc::clone(&env))?;
+        res = eval(expr, Rc::clone(&env), ctx)?;
     }
 
     Ok(res)
 }
 
-pub fn if_special_form(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
+pub fn if_special_form(
+    args: &[MalValue],
+    env: Rc<RefCell<Env>>,
+    ctx: &EvalCtx,
+) -> Result<MalValue> {
     if args.len() < 2 || args.len() > 3 {
-        return Err("if requires two or three arguments".to_string());
+        return Err("if requires two or three arguments".into());
     }
 
     let condition = &args[0];
@@ -88,30 +803,30 @@ pub fn if_special_form(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalVa
     };
 
     // eval condition
-    let condition_res = eval(condition, Rc::clone(&env))?;
-
-    // Determine if the condition is truthy (anything other than nil or false)
-    let is_truthy = match condition_res {
-        MalValue::Nil => false,
-        MalValue::Bool(false) => false,
-        _ => true,
-    };
+    let condition_res = eval(condition, Rc::clone(&env), ctx)?;
 
-    if is_truthy {
+    if is_truthy(&condition_res) {
         // Evaluate and return then_expr
-        eval(then_expr, env)
+        eval(then_expr, env, ctx)
     } else if let Some(else_expr) = else_expr {
         // Evaluate and return else_expr
-        eval(else_expr, env)
+        eval(else_expr, env, ctx)
     } else {
         // No else_expr provided, return nil
         Ok(MalValue::Nil)
     }
 }
 
-pub fn fn_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
-    if args.len() != 2 {
-        return Err("fn* requires exactly two arguments".to_string());
+pub fn fn_star(args: &[MalValue], env: Rc<RefCell<Env>>, _ctx: &EvalCtx) -> Result<MalValue> {
+    // (fn* name [params] body...) binds name to the function in each call, so
+    // it can call itself without a global def!
+    let (name, args) = match args.first() {
+        Some(symbol @ MalValue::Symbol(_)) => (Some(bindable_symbol("fn*", symbol)?), &args[1..]),
+        _ => (None, args),
+    };
+
+    if args.is_empty() {
+        return Err("fn* requires a parameter list".into());
     }
 
     let param_list = match &args[0] {
@@ -120,7 +835,7 @@ pub fn fn_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
         _ => {
             return Err(
                 "fn* first argument must be a vector that defines the function's parameters"
-                    .to_string(),
+                    .to_string().into(),
             )
         }
     };
@@ -134,26 +849,23 @@ pub fn fn_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
         Some(pos) => {
             // '&' must not be the last element
             if pos + 1 >= param_list.len() {
-                return Err("Expected symbol after &".to_string());
+                return Err("Expected symbol after &".into());
             }
             // '&' must be followed by exactly one symbol
             if pos + 2 != param_list.len() {
-                return Err("Unexpected parameter after rest parameter".to_string());
+                return Err("Unexpected parameter after rest parameter".into());
             }
 
             // Extract the variadic parameter name
             let rest_param = match &param_list[pos + 1] {
-                MalValue::Symbol(s) => s.clone(),
-                _ => return Err("Expected symbol after &".to_string()),
+                symbol @ MalValue::Symbol(_) => bindable_symbol("fn*", symbol)?,
+                _ => return Err("Expected symbol after &".into()),
             };
 
             // Collect fixed parameters before '&'
             let fixed_params = param_list[..pos]
                 .iter()
-                .map(|p| match p {
-                    MalValue::Symbol(s) => Ok(s.clone()),
-                    _ => Err("fn* Parameters must be Symbols".to_string()),
-                })
+                .map(|p| bindable_symbol("fn*", p))
                 .collect::<Result<Vec<String>>>()?;
 
             (fixed_params, Some(rest_param))
@@ -162,41 +874,101 @@ pub fn fn_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
             // No variadic parameter; collect all as fixed parameters
             let fixed_params = param_list
                 .iter()
-                .map(|p| match p {
-                    MalValue::Symbol(s) => Ok(s.clone()),
-                    _ => Err("fn* Parameters must be Symbols".to_string()),
-                })
+                .map(|p| bindable_symbol("fn*", p))
                 .collect::<Result<Vec<String>>>()?;
             (fixed_params, None)
         }
     };
 
-    let body = vec![args[1].clone()]; // Store the body as a vector of expressions
+    // Everything after the parameters is the body, evaluated as an implicit do
+    let body = args[1..].to_vec();
 
-    let func = Function::UserDefined {
+    Ok(MalValue::BuiltinFunction(Function::UserDefined(Rc::new(FnDef {
         params: fixed_params,
         rest_param,
         body,
-        env: Rc::clone(&env),
+        env,
+        name,
+        is_macro: false,
+    }))))
+}
+
+pub fn let_star(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let new_env = let_bindings(args, &env, ctx)?;
+    // Evaluate the body forms in the new let_env environment as an implicit do
+    do_func(&args[1..], new_env, ctx)
+}
+
+// (binding [name value ...] body...) gives top-level vars new values while
+// body runs and restores them afterwards, even when body fails. Unlike let*,
+// the new values are seen by everything body calls, e.g. prn seeing
+// *print-readably*.
+pub fn binding(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (bindings, body) = match args {
+        [MalValue::Square(bindings) | MalValue::Round(bindings), body @ ..]
+            if bindings.len().is_multiple_of(2) =>
+        {
+            (bindings, body)
+        }
+        _ => return Err("binding requires a vector of name and value pairs".into()),
+    };
+    let global = global_env().ok_or("binding requires an active interpreter")?;
+    // Every value is evaluated before any var changes
+    let mut values = Vec::new();
+    for pair in bindings.chunks(2) {
+        let MalValue::Symbol(name) = &pair[0] else {
+            return Err(format!("binding: {} is not a symbol", pr_str(&pair[0], true)).into());
+        };
+        if global.borrow().get(name).is_none() {
+            return Err(format!("binding: {} is not defined", name).into());
+        }
+        values.push((name.clone(), eval(&pair[1], Rc::clone(&env), ctx)?));
+    }
+    let mut restore = Rebound {
+        env: Rc::clone(&global),
+        saved: Vec::new(),
     };
+    for (name, value) in values {
+        let saved = global.borrow().get(&name).unwrap_or(MalValue::Nil);
+        global.borrow().set(name.clone(), value);
+        restore.saved.push((name, saved));
+    }
+    do_func(body, env, ctx)
+}
 
-    Ok(MalValue::BuiltinFunction(func))
+// The values binding replaced, put back when it drops
+struct Rebound {
+    env: Rc<RefCell<Env>>,
+    saved: Vec<(String, MalValue)>,
 }
 
-pub fn let_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
-    if args.len() != 2 {
-        return Err("let* requires exactly two arguments".to_string());
+impl Drop for Rebound {
+    fn drop(&mut self) {
+        for (name, value) in self.saved.drain(..).rev() {
+            self.env.borrow().set(name, value);
+        }
+    }
+}
+
+// Evaluates a let* binding list into a new environment for its body
+pub fn let_bindings(
+    args: &[MalValue],
+    env: &Rc<RefCell<Env>>,
+    ctx: &EvalCtx,
+) -> Result<Rc<RefCell<Env>>> {
+    if args.is_empty() {
+        return Err("let* requires a list of bindings".into());
     }
 
     let bindings_list = match &args[0] {
         MalValue::Round(v) => v,
         MalValue::Square(v) => v,
-        _ => return Err("let* first argument must be a list of bindings".to_string()),
+        _ => return Err("let* first argument must be a list of bindings".into()),
     };
 
     // Ensure bindings list has an even number of elements
     if bindings_list.len() % 2 != 0 {
-        return Err("Bindings must be pairs".to_string());
+        return Err("Bindings must be pairs".into());
     }
 
     // Create a new environment using the current environment as the outer value
@@ -212,199 +984,2429 @@ pub fn let_star(args: &[MalValue], env: Rc<RefCell<Env>>) -> Result<MalValue> {
     // Iterate over bindings in pairs
     for pair in bindings_list.chunks(2) {
         if pair.len() != 2 {
-            return Err("Bindings must be pairs".to_string());
+            return Err("Bindings must be pairs".into());
         }
 
         // Extract key and value
         let key = match &pair[0] {
-            MalValue::Symbol(s) => s.clone(),
-            _ => return Err("Bindings must start with a symbol".to_string()),
+            symbol @ MalValue::Symbol(_) => bindable_symbol("let*", symbol)?,
+            _ => return Err("Bindings must start with a symbol".into()),
         };
 
         let value = &pair[1];
         // Evaluate the value in the new_env environment
-        let evaluated_value = eval(value, Rc::clone(&new_env))?;
+        let evaluated_value = eval(value, Rc::clone(&new_env), ctx)?;
         // Set the evaluated value in the new let_env environment
         new_env.borrow_mut().set(key, evaluated_value);
     }
 
-    // Evaluate the body of the let* form in the new let_env environment
-    let body = args[1].clone();
-    eval(&body, Rc::clone(&new_env))
-}
-
-pub fn list(args: &[MalValue]) -> Result<MalValue> {
-    Ok(MalValue::Round(args.to_vec()))
+    Ok(new_env)
 }
 
-pub fn list_question(args: &[MalValue]) -> Result<MalValue> {
-    if args.len() != 1 {
-        return Err("list? requires at least one argument".to_string());
-    }
-    match args[0] {
-        MalValue::Round(_) => Ok(MalValue::Bool(true)),
-        _ => Ok(MalValue::Bool(false)),
+// (letrec* [name value ...] body...) declares every name (as nil) before any
+// value is evaluated, so local functions can refer to each other regardless of
+// binding order
+pub fn letrec_star(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let bindings_list = match args.first() {
+        Some(MalValue::Square(v)) | Some(MalValue::Round(v)) => v,
+        _ => return Err("letrec* first argument must be a list of bindings".into()),
+    };
+    if bindings_list.len() % 2 != 0 {
+        return Err("Bindings must be pairs".into());
     }
-}
 
-pub fn empty_question(args: &[MalValue]) -> Result<MalValue> {
-    if args.len() != 1 {
-        return Err("empty? requires exactly one argument".to_string());
-    }
+    let new_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+        &env.borrow().get_bindings(),
+    )))));
 
-    match &args[0] {
-        MalValue::Round(list) | MalValue::Square(list) => Ok(MalValue::Bool(list.is_empty())),
-        MalValue::String(s) => Ok(MalValue::Bool(s.is_empty())),
-        _ => Ok(MalValue::Bool(false)), // Non-collection types are not empty
+    let names = bindings_list
+        .iter()
+        .step_by(2)
+        .map(|name| bindable_symbol("letrec*", name))
+        .collect::<Result<Vec<String>>>()?;
+    for name in &names {
+        new_env.borrow().set(name.clone(), MalValue::Nil);
     }
-}
 
-pub fn count(args: &[MalValue]) -> Result<MalValue> {
-    if args.len() != 1 {
-        return Err("Count requires exactly one argument".to_string());
-    }
+    let values = bindings_list.iter().skip(1).step_by(2);
+    let result = names
+        .iter()
+        .zip(values)
+        .try_for_each(|(name, value)| {
+            let evaluated_value = eval(value, Rc::clone(&new_env), ctx)?;
+            new_env.borrow().set(name.clone(), evaluated_value);
+            Ok(())
+        })
+        .and_then(|_| do_func(&args[1..], Rc::clone(&new_env), ctx));
+    break_letrec_cycle(&new_env, names);
+    result
+}
 
-    match &args[0] {
-        MalValue::Round(list) | MalValue::Square(list) => Ok(MalValue::Number(list.len() as i64)),
-        MalValue::String(list) => Ok(MalValue::Number(list.len() as i64)),
-        MalValue::Nil => Ok(MalValue::Number(0)),
-        _ => Ok(MalValue::Nil),
+// The functions letrec* binds close over the environment that binds them, a
+// cycle reference counting never frees. Once the body is done, if nothing but
+// those functions still reaches the environment, it is emptied to break the
+// cycle. A function that escaped (returned, stored in an atom, or captured by
+// another closure) keeps the environment alive, and it is leaked, one
+// environment per letrec* evaluation; calling the functions leaks nothing more.
+fn break_letrec_cycle(env: &Rc<RefCell<Env>>, mut names: Vec<String>) {
+    names.sort();
+    names.dedup();
+    // Each reached only through env: held by env and by the copy get returns
+    let closures = names
+        .iter()
+        .filter(|name| {
+            matches!(
+                env.borrow().get(name),
+                Some(MalValue::BuiltinFunction(Function::UserDefined(def)))
+                    if Rc::ptr_eq(&def.env, env) && Rc::strong_count(&def) == 2
+            )
+        })
+        .count();
+    // Apart from those, only the caller's handle on env, and env's own handle
+    // on its bindings plus the one taken here
+    let bindings = env.borrow().get_bindings();
+    if Rc::strong_count(env) == 1 + closures && Rc::strong_count(&bindings) == 2 {
+        env.borrow().clear();
     }
 }
 
-pub fn equals(args: &[MalValue]) -> Result<MalValue> {
-    if args.len() != 2 {
-        return Err("= requires exactly two argument".to_string());
-    }
+// (as-> expr name form...) binds name to expr, then rebinds it to each form's
+// result in turn, returning the last
+pub fn as_thread(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (expr, name, forms) = match args {
+        [expr, name, forms @ ..] => (expr, bindable_symbol("as->", name)?, forms),
+        _ => return Err("as-> requires an expression and a binding name".into()),
+    };
 
-    Ok(MalValue::Bool(args[0] == args[1]))
+    let new_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+        &env.borrow().get_bindings(),
+    )))));
+    let mut value = eval(expr, Rc::clone(&env), ctx)?;
+    for form in forms {
+        new_env.borrow().set(name.clone(), value);
+        value = eval(form, Rc::clone(&new_env), ctx)?;
+    }
+    Ok(value)
 }
 
-pub fn comparison_operator(op: &str, args: &[MalValue]) -> Result<MalValue> {
-    if args.len() != 2 {
-        return Err(format!("{} requires exactly two arguments", op));
+// (some-> expr form...) threads like -> (the value becomes each form's first
+// argument) but stops and returns nil as soon as a step produces nil
+pub fn some_thread(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (expr, forms) = match args.split_first() {
+        Some(split) => split,
+        None => return Err("some-> requires an expression".into()),
+    };
+
+    let mut value = eval(expr, Rc::clone(&env), ctx)?;
+    for form in forms {
+        if value == MalValue::Nil {
+            break;
+        }
+        // The value is spliced in quoted so it isn't evaluated a second time
+        let quoted = MalValue::Round(vec![MalValue::Symbol("quote".to_string()), value]);
+        let call = match form {
+            MalValue::Round(items) if !items.is_empty() => {
+                let mut call = vec![items[0].clone(), quoted];
+                call.extend(items[1..].iter().cloned());
+                call
+            }
+            _ => vec![form.clone(), quoted],
+        };
+        value = eval(&MalValue::Round(call), Rc::clone(&env), ctx)?;
     }
+    Ok(value)
+}
 
-    let (a, b) = match (args.get(0), args.get(1)) {
-        (Some(MalValue::Number(a)), Some(MalValue::Number(b))) => (*a, *b),
-        _ => return Err("Arguments must be numbers".into()),
+// (try* expr (catch* e handler...)) evaluates expr, and on error binds e to the
+// thrown value (or the error message for interpreter errors) and runs the handler
+pub fn try_star(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let is_catch = |form: &[MalValue]| {
+        form.len() >= 2 && form[0] == MalValue::Symbol("catch*".to_string())
     };
-
-    let result = match op {
-        "<" => a < b,
-        "<=" => a <= b,
-        ">" => a > b,
-        ">=" => a >= b,
-        _ => return Err(format!("Unsupported operator: {}", op)),
+    let (expr, handler) = match args {
+        [expr] => (expr, None),
+        [expr, MalValue::Round(catch)] if is_catch(catch) => {
+            (expr, Some((bindable_symbol("catch*", &catch[1])?, &catch[2..])))
+        }
+        _ => {
+            return Err(
+                "try* requires an expression and an optional (catch* name handler...) form"
+                    .to_string().into(),
+            )
+        }
+    };
+
+    let error = match eval(expr, Rc::clone(&env), ctx) {
+        Ok(value) => return Ok(value),
+        Err(error) => error,
+    };
+    let Some((name, body)) = handler else {
+        return Err(error);
+    };
+
+    let catch_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+        &env.borrow().get_bindings(),
+    )))));
+    catch_env.borrow().set(name, error.into_value());
+    do_func(body, catch_env, ctx)
+}
+
+// (with-out-str body...) evaluates body and returns what it printed as a string
+pub fn with_out_str(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    capture(Stream::Out, || do_func(args, env, ctx)).map(MalValue::String)
+}
+
+// Like with-out-str, for warnings and anything else written to stderr
+pub fn with_err_str(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    capture(Stream::Err, || do_func(args, env, ctx)).map(MalValue::String)
+}
+
+// (with-open [name resource ...] body...) binds resources like let* and closes
+// them in reverse order once the body finishes, whether or not it failed
+pub fn with_open(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let bindings_list = match args.first() {
+        Some(MalValue::Square(v)) | Some(MalValue::Round(v)) => v,
+        _ => return Err("with-open first argument must be a vector of bindings".into()),
+    };
+    if bindings_list.len() % 2 != 0 {
+        return Err("Bindings must be pairs".into());
+    }
+
+    let new_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+        &env.borrow().get_bindings(),
+    )))));
+    let mut opened = Vec::new();
+
+    let result = (|| -> Result<MalValue> {
+        for pair in bindings_list.chunks(2) {
+            let key = match &pair[0] {
+                symbol @ MalValue::Symbol(_) => bindable_symbol("with-open", symbol)?,
+                _ => return Err("Bindings must start with a symbol".into()),
+            };
+            let value = eval(&pair[1], Rc::clone(&new_env), ctx)?;
+            match &value {
+                MalValue::Resource(r) => opened.push(Rc::clone(r)),
+                _ => {
+                    return Err(format!("with-open: {} is not bound to a resource", key).into())
+                }
+            }
+            new_env.borrow_mut().set(key, value);
+        }
+
+        let mut res = MalValue::Nil;
+        for expr in &args[1..] {
+            res = eval(expr, Rc::clone(&new_env), ctx)?;
+        }
+        Ok(res)
+    })();
+
+    // Close everything even on error; the body's error takes precedence over close failures
+    let mut closed = Ok(());
+    for resource in opened.iter().rev() {
+        let res = resource.close();
+        if closed.is_ok() {
+            closed = res;
+        }
+    }
+    let value = result?;
+    closed.map(|_| value)
+}
+
+pub fn list(args: &[MalValue]) -> Result<MalValue> {
+    Ok(MalValue::Round(args.to_vec()))
+}
+
+pub fn vector(args: &[MalValue]) -> Result<MalValue> {
+    Ok(MalValue::Square(args.to_vec()))
+}
+
+// (cons x coll) is always a list, even when coll is a vector
+pub fn cons(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value, coll] => {
+            let mut items = vec![value.clone()];
+            items.extend(seq_arg(coll, "cons")?);
+            Ok(MalValue::Round(items))
+        }
+        _ => Err("cons requires a value and a sequence".into()),
+    }
+}
+
+pub fn vec(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Square(_)] => Ok(args[0].clone()),
+        [coll] => Ok(MalValue::Square(seq_arg(coll, "vec")?.collect())),
+        _ => Err("vec requires exactly one argument".into()),
+    }
+}
+
+pub fn list_question(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("list? requires at least one argument".into());
+    }
+    match args[0] {
+        MalValue::Round(_) => Ok(MalValue::TRUE),
+        _ => Ok(MalValue::FALSE),
+    }
+}
+
+pub fn vector_question(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("vector? requires exactly one argument".into());
+    }
+    Ok(MalValue::bool(matches!(args[0], MalValue::Square(_))))
+}
+
+// One-argument type tests; the spec predicates in particular lean on these
+fn type_question(args: &[MalValue], name: &str, test: fn(&MalValue) -> bool) -> Result<MalValue> {
+    match args {
+        [value] => Ok(MalValue::bool(test(value))),
+        _ => Err(format!("{} requires exactly one argument", name).into()),
+    }
+}
+
+pub fn string_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "string?", |v| matches!(v, MalValue::String(_)))
+}
+
+pub fn int_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "int?", |v| matches!(v, MalValue::Number(_)))
+}
+
+pub fn float_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "float?", |v| matches!(v, MalValue::Float(_)))
+}
+
+pub fn number_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "number?", |v| matches!(v, MalValue::Number(_) | MalValue::Float(_)))
+}
+
+pub fn boolean_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "boolean?", |v| matches!(v, MalValue::Bool(_)))
+}
+
+pub fn keyword_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "keyword?", |v| matches!(v, MalValue::Atom(_)))
+}
+
+pub fn symbol_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "symbol?", |v| matches!(v, MalValue::Symbol(_)))
+}
+
+pub fn nil_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "nil?", |v| matches!(v, MalValue::Nil))
+}
+
+pub fn true_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "true?", |v| matches!(v, MalValue::Bool(true)))
+}
+
+pub fn false_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "false?", |v| matches!(v, MalValue::Bool(false)))
+}
+
+// The namespace and name of :ns/key or ns/sym; a lone / is a name
+fn qualified_parts(value: &MalValue, builtin: &str) -> Result<(Option<String>, String)> {
+    let qualified = match value {
+        MalValue::Atom(k) => &k[1..],
+        MalValue::Symbol(s) => s.as_str(),
+        MalValue::String(s) if builtin == "name" => return Ok((None, s.clone())),
+        _ => {
+            return Err(format!(
+                "{}: {} is not a keyword or symbol",
+                builtin,
+                pr_str(value, true)
+            )
+            .into())
+        }
+    };
+    Ok(match qualified.split_once('/') {
+        Some((ns, name)) if !ns.is_empty() && !name.is_empty() => {
+            (Some(ns.to_string()), name.to_string())
+        }
+        _ => (None, qualified.to_string()),
+    })
+}
+
+pub fn namespace(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(qualified_parts(value, "namespace")?
+            .0
+            .map_or(MalValue::Nil, MalValue::String)),
+        _ => Err("namespace requires exactly one argument".into()),
+    }
+}
+
+// (name :ns/key) is "key"; a string is its own name
+pub fn name(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(MalValue::String(qualified_parts(value, "name")?.1)),
+        _ => Err("name requires exactly one argument".into()),
+    }
+}
+
+// (in-ns 'app.events) makes ::key forms read after it resolve to :app.events/key
+pub fn in_ns(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Symbol(ns) | MalValue::String(ns)] if !ns.is_empty() && !ns.contains('/') => {
+            set_namespace(ns)?;
+            Ok(MalValue::Symbol(ns.clone()))
+        }
+        _ => Err("in-ns requires a namespace name".into()),
+    }
+}
+
+pub fn map_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "map?", |v| matches!(v, MalValue::Map(_)) || record_entries(v).is_some())
+}
+
+pub fn fn_question(args: &[MalValue]) -> Result<MalValue> {
+    type_question(args, "fn?", |v| matches!(v, MalValue::BuiltinFunction(_)))
+}
+
+// Borrows the elements of a list or vector (nil is the empty sequence). Sequence
+// builtins go through this and seq_len instead of matching collection variants.
+fn as_seq(value: &MalValue) -> Option<&[MalValue]> {
+    match value {
+        MalValue::Round(items) | MalValue::Square(items) => Some(items),
+        MalValue::Nil => Some(&[]),
+        _ => None,
+    }
+}
+
+// Number of elements iter_seq would yield, without building them
+fn seq_len(value: &MalValue) -> Option<usize> {
+    match value {
+        MalValue::String(s) => Some(s.chars().count()),
+        MalValue::Bytes(b) => Some(b.len()),
+        MalValue::Set(items) => Some(items.len()),
+        MalValue::Map(map) => Some(map.len()),
+        _ => record_entries(value)
+            .map(|fields| fields.len())
+            .or_else(|| as_seq(value).map(|items| items.len())),
+    }
+}
+
+pub fn empty_question(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("empty? requires exactly one argument".into());
+    }
+
+    seq_len(&args[0])
+        .map(|len| MalValue::Bool(len == 0))
+        .ok_or_else(|| format!("empty?: {} is not a collection", pr_str(&args[0], true)).into())
+}
+
+pub fn count(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("Count requires exactly one argument".into());
+    }
+
+    if let Some(count) = extension(COUNT_IMPL, &args[0])? {
+        return Ok(count);
+    }
+    seq_len(&args[0])
+        .map(|len| MalValue::Number(len as i64))
+        .ok_or_else(|| format!("count: {} is not a collection", pr_str(&args[0], true)).into())
+}
+
+pub fn nth(args: &[MalValue]) -> Result<MalValue> {
+    let (coll, index) = match args {
+        [coll, MalValue::Number(index)] => (coll, *index),
+        _ => return Err("nth requires a collection and an index".into()),
+    };
+
+    let out_of_range = || MalError::from(format!("nth: index {} out of range", index));
+    let index = usize::try_from(index).map_err(|_| out_of_range())?;
+
+    match (as_seq(coll), coll) {
+        (Some(items), _) => items.get(index).cloned().ok_or_else(out_of_range),
+        (None, MalValue::Bytes(b)) => b
+            .get(index)
+            .map(|byte| MalValue::Number(*byte as i64))
+            .ok_or_else(out_of_range),
+        (None, MalValue::String(s)) => s
+            .chars()
+            .nth(index)
+            .map(|c| MalValue::String(c.to_string()))
+            .ok_or_else(out_of_range),
+        _ => Err("nth requires a list, vector, string, or bytes".into()),
+    }
+}
+
+// (seq coll) is nil for an empty collection, otherwise a list of its elements;
+// maps yield [k v] entries and strings one-character strings
+pub fn seq(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("seq requires exactly one argument".into());
+    }
+    let items: Vec<MalValue> = seq_arg(&args[0], "seq")?.collect();
+    if items.is_empty() {
+        Ok(MalValue::Nil)
+    } else {
+        Ok(MalValue::Round(items))
+    }
+}
+
+pub fn first(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("first requires exactly one argument".into());
+    }
+    Ok(seq_arg(&args[0], "first")?.next().unwrap_or(MalValue::Nil))
+}
+
+// (rest coll) is always a list, empty when coll has at most one element
+pub fn rest(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("rest requires exactly one argument".into());
+    }
+    Ok(MalValue::Round(seq_arg(&args[0], "rest")?.skip(1).collect()))
+}
+
+// (conj coll x ...) adds where the collection grows cheaply: lists at the
+// front, vectors at the end, sets only if absent, and maps take [k v] entries
+pub fn conj(args: &[MalValue]) -> Result<MalValue> {
+    let (coll, items) = match args.split_first() {
+        Some((coll, items)) => (coll, items),
+        None => return Err("conj requires a collection".into()),
+    };
+
+    if let Some(fields) = record_entries(coll) {
+        return Ok(with_entries(coll, conj_entries(fields, items)?));
+    }
+    match coll {
+        MalValue::Round(list) => {
+            let mut result: Vec<MalValue> = items.iter().rev().cloned().collect();
+            result.extend(list.iter().cloned());
+            Ok(MalValue::Round(result))
+        }
+        MalValue::Nil => Ok(MalValue::Round(items.iter().rev().cloned().collect())),
+        MalValue::Square(vector) => {
+            let mut result = vector.clone();
+            result.extend(items.iter().cloned());
+            Ok(MalValue::Square(result))
+        }
+        MalValue::Set(set) => {
+            let mut result = set.clone();
+            for item in items {
+                MalKey::try_from(item)?;
+                if !result.contains(item) {
+                    result.push(item.clone());
+                }
+            }
+            Ok(MalValue::Set(result))
+        }
+        MalValue::Map(map) => Ok(MalValue::Map(conj_entries(map, items)?)),
+        _ => Err(format!("conj: {} is not a collection", pr_str(coll, true)).into()),
+    }
+}
+
+// map with each [key value] vector in items added
+fn conj_entries(map: &MalMap, items: &[MalValue]) -> Result<MalMap> {
+    let mut result = map.clone();
+    for item in items {
+        match item {
+            MalValue::Square(entry) if entry.len() == 2 => {
+                result.insert(MalKey::try_from(&entry[0])?, entry[1].clone());
+            }
+            _ => {
+                return Err(format!(
+                    "conj: map entries must be [key value] vectors, got {}",
+                    pr_str(item, true)
+                )
+                .into())
+            }
+        }
+    }
+    Ok(result)
+}
+
+// (hash-map k v ...) builds a map from alternating keys and values; a repeated
+// key keeps its last value
+pub fn hash_map(args: &[MalValue]) -> Result<MalValue> {
+    if !args.len().is_multiple_of(2) {
+        return Err("hash-map requires an even number of arguments".into());
+    }
+    Ok(MalValue::Map(map_from_entries(args)?))
+}
+
+// (get coll key [default]) looks up a map key, set member, or vector index.
+// Like keys, vals, and concat, it treats nil as an empty collection.
+pub fn get(args: &[MalValue]) -> Result<MalValue> {
+    let (coll, key, default) = match args {
+        [coll, key] => (coll, key, MalValue::Nil),
+        [coll, key, default] => (coll, key, default.clone()),
+        _ => return Err("get requires a collection, a key, and an optional default".into()),
+    };
+
+    if let Some(fields) = record_entries(coll) {
+        return Ok(map_get(fields, key).cloned().unwrap_or(default));
+    }
+    let found = match (coll, key) {
+        (MalValue::Map(map), _) => map_get(map, key).cloned(),
+        (MalValue::Set(items), _) => items.contains(key).then(|| key.clone()),
+        (MalValue::Square(items), MalValue::Number(index)) => usize::try_from(*index)
+            .ok()
+            .and_then(|index| items.get(index).cloned()),
+        (MalValue::Nil, _) | (MalValue::Square(_), _) => None,
+        _ => {
+            return Err(format!(
+                "get: {} is not a map, set, or vector",
+                pr_str(coll, true)
+            )
+            .into())
+        }
+    };
+    Ok(found.unwrap_or(default))
+}
+
+// (assoc m k v ...) returns m with each key set to its value; nil is an empty
+// map, and a record stays a record of the same type
+pub fn assoc(args: &[MalValue]) -> Result<MalValue> {
+    let (coll, pairs) = match args {
+        [coll, pairs @ ..] if !pairs.is_empty() && pairs.len() % 2 == 0 => (coll, pairs),
+        _ => return Err("assoc requires a map and key-value pairs".into()),
+    };
+    let mut map = map_entries(coll, "assoc")?.cloned().unwrap_or_default();
+    for pair in pairs.chunks(2) {
+        map.insert(MalKey::try_from(&pair[0])?, pair[1].clone());
+    }
+    Ok(with_entries(coll, map))
+}
+
+// (dissoc m k ...) returns m without the given keys; nil stays nil, and a
+// record loses its type along with its fields
+pub fn dissoc(args: &[MalValue]) -> Result<MalValue> {
+    let [coll, keys @ ..] = args else {
+        return Err("dissoc requires a map and keys".into());
+    };
+    let Some(map) = map_entries(coll, "dissoc")? else {
+        return Ok(MalValue::Nil);
+    };
+    let mut map = map.clone();
+    for key in keys {
+        map.remove(&MalKey::try_from(key)?);
+    }
+    Ok(MalValue::Map(map))
+}
+
+// (contains? coll key) is true when a map or record has key, a set has it as
+// a member, or a vector has it as an index; nil contains nothing
+pub fn contains_question(args: &[MalValue]) -> Result<MalValue> {
+    let [coll, key] = args else {
+        return Err("contains? requires a collection and a key".into());
+    };
+    if let Some(fields) = record_entries(coll) {
+        return Ok(MalValue::bool(map_get(fields, key).is_some()));
+    }
+    Ok(MalValue::bool(match (coll, key) {
+        (MalValue::Map(map), _) => map_get(map, key).is_some(),
+        (MalValue::Set(items), _) => items.contains(key),
+        (MalValue::Square(items), MalValue::Number(index)) => {
+            usize::try_from(*index).is_ok_and(|index| index < items.len())
+        }
+        (MalValue::Nil, _) | (MalValue::Square(_), _) => false,
+        _ => {
+            return Err(format!(
+                "contains?: {} is not a map, set, or vector",
+                pr_str(coll, true)
+            )
+            .into())
+        }
+    }))
+}
+
+fn map_entries<'a>(value: &'a MalValue, name: &str) -> Result<Option<&'a MalMap>> {
+    if let Some(fields) = record_entries(value) {
+        return Ok(Some(fields));
+    }
+    match value {
+        MalValue::Map(map) => Ok(Some(map)),
+        MalValue::Nil => Ok(None),
+        _ => Err(format!("{}: {} is not a map", name, pr_str(value, true)).into()),
+    }
+}
+
+pub fn keys(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("keys requires exactly one argument".into());
+    }
+    Ok(match map_entries(&args[0], "keys")? {
+        Some(map) => MalValue::Round(map.keys().cloned().map(MalValue::from).collect()),
+        None => MalValue::Nil,
+    })
+}
+
+pub fn vals(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("vals requires exactly one argument".into());
+    }
+    Ok(match map_entries(&args[0], "vals")? {
+        Some(map) => MalValue::Round(map.values().cloned().collect()),
+        None => MalValue::Nil,
+    })
+}
+
+pub fn concat(args: &[MalValue]) -> Result<MalValue> {
+    let mut result = Vec::new();
+    for arg in args {
+        result.extend(seq_arg(arg, "concat")?);
+    }
+    Ok(MalValue::Round(result))
+}
+
+// (reverse coll) is a list; strings reverse as one-character strings, which
+// (apply str ...) joins back together
+pub fn reverse(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("reverse requires exactly one argument".into());
+    }
+    let mut items: Vec<MalValue> = seq_arg(&args[0], "reverse")?.collect();
+    items.reverse();
+    Ok(MalValue::Round(items))
+}
+
+pub fn last(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("last requires exactly one argument".into());
+    }
+    if let Some(items) = as_seq(&args[0]) {
+        return Ok(items.last().cloned().unwrap_or(MalValue::Nil));
+    }
+    Ok(seq_arg(&args[0], "last")?.last().unwrap_or(MalValue::Nil))
+}
+
+pub fn butlast(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("butlast requires exactly one argument".into());
+    }
+    let mut items: Vec<MalValue> = seq_arg(&args[0], "butlast")?.collect();
+    items.pop();
+    Ok(MalValue::Round(items))
+}
+
+// Keeps the first occurrence of each element. Hashable elements are tracked in
+// a set; anything else (e.g. nested collections) falls back to a linear scan.
+pub fn distinct(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("distinct requires exactly one argument".into());
+    }
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    for item in seq_arg(&args[0], "distinct")? {
+        let fresh = match MalKey::try_from(&item) {
+            Ok(key) => seen.insert(key),
+            Err(_) => !result.contains(&item),
+        };
+        if fresh {
+            result.push(item);
+        }
+    }
+    Ok(MalValue::Round(result))
+}
+
+// The default order for sort: nil, then booleans, numbers, strings, keywords,
+// and symbols, each group in its natural order. Lists and vectors compare
+// element by element. Anything else can't be ordered and is an error.
+fn sort_rank(value: &MalValue) -> Option<u8> {
+    match value {
+        MalValue::Nil => Some(0),
+        MalValue::Bool(_) => Some(1),
+        MalValue::Number(_) | MalValue::Float(_) => Some(2),
+        MalValue::String(_) => Some(3),
+        MalValue::Atom(_) => Some(4),
+        MalValue::Symbol(_) => Some(5),
+        MalValue::Round(_) | MalValue::Square(_) => Some(6),
+        _ => None,
+    }
+}
+
+fn compare_values(a: &MalValue, b: &MalValue) -> Result<Ordering> {
+    let (Some(rank_a), Some(rank_b)) = (sort_rank(a), sort_rank(b)) else {
+        return Err(format!(
+            "Cannot compare {} with {}",
+            pr_str(a, true),
+            pr_str(b, true)
+        )
+        .into());
+    };
+    if rank_a != rank_b {
+        return Ok(rank_a.cmp(&rank_b));
+    }
+    Ok(match (a, b) {
+        (MalValue::Bool(x), MalValue::Bool(y)) => x.cmp(y),
+        (MalValue::Number(x), MalValue::Number(y)) => x.cmp(y),
+        (MalValue::String(x), MalValue::String(y))
+        | (MalValue::Atom(x), MalValue::Atom(y))
+        | (MalValue::Symbol(x), MalValue::Symbol(y)) => x.cmp(y),
+        (
+            MalValue::Round(xs) | MalValue::Square(xs),
+            MalValue::Round(ys) | MalValue::Square(ys),
+        ) => {
+            for (x, y) in xs.iter().zip(ys) {
+                let ordering = compare_values(x, y)?;
+                if ordering != Ordering::Equal {
+                    return Ok(ordering);
+                }
+            }
+            xs.len().cmp(&ys.len())
+        }
+        // Mixed integers and floats; NaN sorts after every other number
+        _ => {
+            let as_float = |v: &MalValue| match v {
+                MalValue::Number(n) => *n as f64,
+                MalValue::Float(f) => *f,
+                _ => f64::NAN,
+            };
+            as_float(a).total_cmp(&as_float(b))
+        }
+    })
+}
+
+// A user comparator may return a number (negative, zero, positive) or, like
+// Clojure's, a boolean meaning "a sorts before b"
+fn call_comparator(cmp: &MalValue, a: &MalValue, b: &MalValue) -> Result<Ordering> {
+    match call(cmp, &[a.clone(), b.clone()])? {
+        MalValue::Number(n) => Ok(n.cmp(&0)),
+        before if is_truthy(&before) => Ok(Ordering::Less),
+        _ if is_truthy(&call(cmp, &[b.clone(), a.clone()])?) => Ok(Ordering::Greater),
+        _ => Ok(Ordering::Equal),
+    }
+}
+
+// Stable sort of items by their keys; the first comparison error is returned
+fn sort_keyed(mut items: Vec<(MalValue, MalValue)>, cmp: Option<&MalValue>) -> Result<MalValue> {
+    let mut error = None;
+    items.sort_by(|(a, _), (b, _)| {
+        if error.is_some() {
+            return Ordering::Equal;
+        }
+        let ordering = match cmp {
+            Some(cmp) => call_comparator(cmp, a, b),
+            None => compare_values(a, b),
+        };
+        ordering.unwrap_or_else(|e| {
+            error = Some(e);
+            Ordering::Equal
+        })
+    });
+    match error {
+        Some(e) => Err(e),
+        None => Ok(MalValue::Round(items.into_iter().map(|(_, v)| v).collect())),
+    }
+}
+
+// (sort coll) or (sort comparator coll)
+pub fn sort(args: &[MalValue]) -> Result<MalValue> {
+    let (cmp, coll) = match args {
+        [coll] => (None, coll),
+        [cmp, coll] if is_callable(cmp) => (Some(cmp), coll),
+        _ => return Err("sort requires a collection and an optional comparator".into()),
+    };
+    let items = seq_arg(coll, "sort")?.map(|v| (v.clone(), v)).collect();
+    sort_keyed(items, cmp)
+}
+
+// (sort-by keyfn coll) or (sort-by keyfn comparator coll). keyfn may be a
+// keyword, so (sort-by :age people) works.
+pub fn sort_by(args: &[MalValue]) -> Result<MalValue> {
+    let (keyfn, cmp, coll) = match args {
+        [keyfn, coll] if is_callable(keyfn) => (keyfn, None, coll),
+        [keyfn, cmp, coll] if is_callable(keyfn) && is_callable(cmp) => (keyfn, Some(cmp), coll),
+        _ => {
+            return Err(
+                "sort-by requires a key function, an optional comparator, and a collection"
+                    .to_string().into(),
+            )
+        }
+    };
+    let items = seq_arg(coll, "sort-by")?
+        .map(|v| Ok((call(keyfn, std::slice::from_ref(&v))?, v)))
+        .collect::<Result<Vec<_>>>()?;
+    sort_keyed(items, cmp)
+}
+
+// (into to from) conjs every element of from onto to, so the result keeps to's
+// type: (into [] list) makes a vector, (into {} pairs) a map
+pub fn into(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [to, from] => {
+            let mut conj_args = vec![to.clone()];
+            conj_args.extend(seq_arg(from, "into")?);
+            conj(&conj_args)
+        }
+        _ => Err("into requires a target collection and a source sequence".into()),
+    }
+}
+
+// Structured errors: (ex-info msg data) is a #ex-info tagged map, and any value
+// can be thrown. Handlers read it back with ex-message and ex-data, which also
+// accept plain thrown maps so (throw {:type :io/error ...}) dispatches on :type.
+pub fn throw(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => {
+            let message = match ex_message(args)? {
+                MalValue::String(message) => message,
+                _ => pr_str(value, true),
+            };
+            Err(MalError::Thrown { value: value.clone(), message })
+        }
+        _ => Err("throw requires exactly one argument".into()),
+    }
+}
+
+pub fn ex_info(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::String(message), data @ (MalValue::Map(_) | MalValue::Nil)] => {
+            Ok(ex_info_value(message, data.clone()))
+        }
+        _ => Err("ex-info requires a message string and a data map".into()),
+    }
+}
+
+// The ex-info value (ex-info message data) builds, for errors raised from Rust
+pub fn ex_info_value(message: &str, data: MalValue) -> MalValue {
+    MalValue::Tagged(
+        "ex-info".to_string(),
+        Box::new(MalValue::Map(MalMap::from([
+            (MalKey::keyword("message"), MalValue::String(message.to_string())),
+            (MalKey::keyword("data"), data),
+        ]))),
+    )
+}
+
+fn ex_info_field(value: &MalValue, field: &str) -> Option<MalValue> {
+    match value {
+        MalValue::Tagged(tag, inner) if tag == "ex-info" => {
+            get(&[(**inner).clone(), MalValue::Atom(field.to_string())]).ok()
+        }
+        _ => None,
+    }
+}
+
+// The data map of an ex-info, a thrown map itself, or nil
+pub fn ex_data(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value @ MalValue::Map(_)] => Ok(value.clone()),
+        [value] => Ok(ex_info_field(value, ":data").unwrap_or(MalValue::Nil)),
+        _ => Err("ex-data requires exactly one argument".into()),
+    }
+}
+
+// The message of an ex-info, a thrown map's :message, an error string, or nil
+pub fn ex_message(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value @ MalValue::String(_)] => Ok(value.clone()),
+        [map @ MalValue::Map(_)] => {
+            get(&[map.clone(), MalValue::Atom(":message".to_string())])
+        }
+        [value] => Ok(ex_info_field(value, ":message").unwrap_or(MalValue::Nil)),
+        _ => Err("ex-message requires exactly one argument".into()),
+    }
+}
+
+pub fn atom(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(MalValue::Ref(Rc::new(RefCell::new(value.clone())))),
+        _ => Err("atom requires exactly one argument".into()),
+    }
+}
+
+pub fn atom_question(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(MalValue::bool(matches!(value, MalValue::Ref(_)))),
+        _ => Err("atom? requires exactly one argument".into()),
+    }
+}
+
+// @a reads as (deref a)
+pub fn deref(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Ref(cell)] => Ok(cell.borrow().clone()),
+        _ => Err("deref requires an atom".into()),
+    }
+}
+
+pub fn reset_bang(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Ref(cell), value] => {
+            *cell.borrow_mut() = value.clone();
+            Ok(value.clone())
+        }
+        _ => Err("reset! requires an atom and a value".into()),
+    }
+}
+
+// (swap! a f x ...) sets a to (f @a x ...). The borrow is released while f runs,
+// so f may itself read the atom.
+pub fn swap_bang(args: &[MalValue]) -> Result<MalValue> {
+    let (cell, func, extra) = match args {
+        [MalValue::Ref(cell), func, extra @ ..] if is_callable(func) => (cell, func, extra),
+        _ => return Err("swap! requires an atom and a function".into()),
+    };
+    let mut call_args = vec![cell.borrow().clone()];
+    call_args.extend(extra.iter().cloned());
+    let value = call(func, &call_args)?;
+    *cell.borrow_mut() = value.clone();
+    Ok(value)
+}
+
+// Rebuilds a collection with f applied to each element, keeping its type;
+// anything that isn't a collection is returned unchanged
+fn map_children(
+    value: &MalValue,
+    f: &mut impl FnMut(&MalValue) -> Result<MalValue>,
+) -> Result<MalValue> {
+    let mut each = |items: &[MalValue]| items.iter().map(&mut *f).collect::<Result<Vec<_>>>();
+    Ok(match value {
+        MalValue::Round(items) => MalValue::Round(each(items)?),
+        MalValue::Square(items) => MalValue::Square(each(items)?),
+        MalValue::Map(map) => MalValue::Map(
+            map.iter()
+                .map(|(key, value)| {
+                    let key = f(&MalValue::from(key.clone()))?;
+                    Ok((MalKey::try_from(&key)?, f(value)?))
+                })
+                .collect::<Result<MalMap>>()?,
+        ),
+        MalValue::Set(items) => MalValue::Set(each(items)?),
+        MalValue::Tagged(tag, inner) => MalValue::Tagged(tag.clone(), Box::new(f(inner)?)),
+        _ => value.clone(),
+    })
+}
+
+type AtomCell = Rc<RefCell<MalValue>>;
+
+// Copies every atom reachable from value. An atom reached twice is copied
+// once, so sharing (and cycles) among the copies mirrors the original.
+fn clone_value(value: &MalValue, copies: &mut Vec<(AtomCell, AtomCell)>) -> Result<MalValue> {
+    let MalValue::Ref(cell) = value else {
+        return map_children(value, &mut |child| clone_value(child, copies));
+    };
+    if let Some((_, copy)) = copies.iter().find(|(original, _)| Rc::ptr_eq(original, cell)) {
+        return Ok(MalValue::Ref(Rc::clone(copy)));
+    }
+    let copy = Rc::new(RefCell::new(MalValue::Nil));
+    copies.push((Rc::clone(cell), Rc::clone(&copy)));
+    let contents = cell.borrow().clone();
+    *copy.borrow_mut() = clone_value(&contents, copies)?;
+    Ok(MalValue::Ref(copy))
+}
+
+// (clone v) is a copy of v that shares no atoms with it, so swap! on one
+// never shows through the other
+pub fn clone(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => clone_value(value, &mut Vec::new()),
+        _ => Err("clone requires exactly one argument".into()),
+    }
+}
+
+// Replaces every atom reachable from value with its current contents
+fn freeze_value(value: &MalValue, visiting: &mut Vec<AtomCell>) -> Result<MalValue> {
+    let MalValue::Ref(cell) = value else {
+        return map_children(value, &mut |child| freeze_value(child, visiting));
+    };
+    if visiting.iter().any(|seen| Rc::ptr_eq(seen, cell)) {
+        return Err("freeze: an atom contains itself".into());
+    }
+    visiting.push(Rc::clone(cell));
+    let contents = cell.borrow().clone();
+    let frozen = freeze_value(&contents, visiting);
+    visiting.pop();
+    frozen
+}
+
+// (freeze a) is the plain value inside a, with any atoms nested in it
+// dereferenced too; later changes to the atoms don't affect it
+pub fn freeze(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => freeze_value(value, &mut Vec::new()),
+        _ => Err("freeze requires exactly one argument".into()),
+    }
+}
+
+// Tree walking. Unlike Clojure, maps are walked key by key and value by value
+// rather than as [k v] entries, since maps here are flat key/value sequences.
+
+// (walk inner outer form) applies inner to each element of form, then outer
+// to the rebuilt collection (or to form itself when it isn't a collection)
+pub fn walk(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [inner, outer, form] if is_callable(inner) && is_callable(outer) => {
+            let rebuilt = map_children(form, &mut |child| {
+                call(inner, std::slice::from_ref(child))
+            })?;
+            call(outer, &[rebuilt])
+        }
+        _ => Err("walk requires two functions and a form".into()),
+    }
+}
+
+fn prewalk_value(f: &MalValue, form: &MalValue) -> Result<MalValue> {
+    let replaced = call(f, std::slice::from_ref(form))?;
+    map_children(&replaced, &mut |child| prewalk_value(f, child))
+}
+
+fn postwalk_value(f: &MalValue, form: &MalValue) -> Result<MalValue> {
+    let rebuilt = map_children(form, &mut |child| postwalk_value(f, child))?;
+    call(f, &[rebuilt])
+}
+
+// (prewalk f form) replaces each form with (f form) before descending into it
+pub fn prewalk(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [f, form] if is_callable(f) => prewalk_value(f, form),
+        _ => Err("prewalk requires a function and a form".into()),
+    }
+}
+
+// (postwalk f form) descends first, so f sees each collection already rebuilt
+pub fn postwalk(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [f, form] if is_callable(f) => postwalk_value(f, form),
+        _ => Err("postwalk requires a function and a form".into()),
+    }
+}
+
+pub fn equals(args: &[MalValue]) -> Result<MalValue> {
+    if args.is_empty() {
+        return Err("= requires at least one argument".into());
+    }
+
+    Ok(MalValue::bool(args.windows(2).all(|pair| pair[0] == pair[1])))
+}
+
+// One difference found by diff: where it is, and the value on each side that
+// has one
+struct Difference {
+    path: Vec<MalValue>,
+    a: Option<MalValue>,
+    b: Option<MalValue>,
+}
+
+fn record_difference(
+    out: &mut Vec<Difference>,
+    path: &[MalValue],
+    a: Option<&MalValue>,
+    b: Option<&MalValue>,
+) {
+    out.push(Difference {
+        path: path.to_vec(),
+        a: a.cloned(),
+        b: b.cloned(),
+    });
+}
+
+// Walks a and b in parallel, recording each place they disagree. Maps compare
+// key by key, lists and vectors index by index, sets element by element;
+// anything else differs as a whole.
+fn collect_diff(
+    a: &MalValue,
+    b: &MalValue,
+    path: &mut Vec<MalValue>,
+    out: &mut Vec<Difference>,
+) {
+    if a == b {
+        return;
+    }
+    match (a, b) {
+        (MalValue::Map(xs), MalValue::Map(ys)) => {
+            for (key, x) in xs {
+                path.push(MalValue::from(key.clone()));
+                match ys.get(key) {
+                    Some(y) => collect_diff(x, y, path, out),
+                    None => record_difference(out, path, Some(x), None),
+                }
+                path.pop();
+            }
+            for (key, y) in ys.iter().filter(|(key, _)| !xs.contains_key(key)) {
+                path.push(MalValue::from(key.clone()));
+                record_difference(out, path, None, Some(y));
+                path.pop();
+            }
+        }
+        (
+            MalValue::Round(xs) | MalValue::Square(xs),
+            MalValue::Round(ys) | MalValue::Square(ys),
+        ) => {
+            for i in 0..xs.len().max(ys.len()) {
+                path.push(MalValue::Number(i as i64));
+                match (xs.get(i), ys.get(i)) {
+                    (Some(x), Some(y)) => collect_diff(x, y, path, out),
+                    (x, y) => record_difference(out, path, x, y),
+                }
+                path.pop();
+            }
+        }
+        (MalValue::Set(xs), MalValue::Set(ys)) => {
+            for x in xs.iter().filter(|x| !ys.contains(x)) {
+                record_difference(out, path, Some(x), None);
+            }
+            for y in ys.iter().filter(|y| !xs.contains(y)) {
+                record_difference(out, path, None, Some(y));
+            }
+        }
+        _ => record_difference(out, path, Some(a), Some(b)),
+    }
+}
+
+fn differences(a: &MalValue, b: &MalValue) -> Vec<Difference> {
+    let mut out = Vec::new();
+    collect_diff(a, b, &mut Vec::new(), &mut out);
+    out
+}
+
+// (diff a b) is a vector of {:path [...] :kind k :a x :b y} maps, empty when a
+// and b are equal. :kind is :changed when both sides have a value there,
+// :missing when only a does, and :extra when only b does; :a and :b are left
+// out on the side with no value.
+pub fn diff(args: &[MalValue]) -> Result<MalValue> {
+    let [a, b] = args else {
+        return Err("diff requires exactly two arguments".into());
+    };
+    let keyword = |name: &str| MalValue::Atom(format!(":{}", name));
+    Ok(MalValue::Square(
+        differences(a, b)
+            .into_iter()
+            .map(|d| {
+                let kind = match (&d.a, &d.b) {
+                    (Some(_), Some(_)) => "changed",
+                    (Some(_), None) => "missing",
+                    _ => "extra",
+                };
+                let mut entry = MalMap::from([
+                    (MalKey::keyword("path"), MalValue::Square(d.path)),
+                    (MalKey::keyword("kind"), keyword(kind)),
+                ]);
+                if let Some(a) = d.a {
+                    entry.insert(MalKey::keyword("a"), a);
+                }
+                if let Some(b) = d.b {
+                    entry.insert(MalKey::keyword("b"), b);
+                }
+                MalValue::Map(entry)
+            })
+            .collect(),
+    ))
+}
+
+// (assert= expected actual) returns nil, or fails listing each difference
+pub fn assert_equals(args: &[MalValue]) -> Result<MalValue> {
+    let (expected, actual, label) = match args {
+        [expected, actual] => (expected, actual, None),
+        [expected, actual, MalValue::String(label)] => (expected, actual, Some(label)),
+        _ => {
+            return Err(
+                "assert= requires an expected value, an actual value, and an optional message"
+                    .to_string().into(),
+            )
+        }
+    };
+    let found = differences(expected, actual);
+    if found.is_empty() {
+        return Ok(MalValue::Nil);
+    }
+    let show = |value: &Option<MalValue>| match value {
+        Some(value) => pr_str(value, true),
+        None => "nothing".to_string(),
     };
+    let details: Vec<String> = found
+        .iter()
+        .map(|d| {
+            format!(
+                "at {}: expected {}, got {}",
+                pr_str(&MalValue::Square(d.path.clone()), true),
+                show(&d.a),
+                show(&d.b)
+            )
+        })
+        .collect();
+    Err(format!(
+        "assert= failed{}: {}",
+        label.map(|l| format!(" ({})", l)).unwrap_or_default(),
+        details.join("; ")
+    )
+    .into())
+}
+
+// (< a b c) holds when every adjacent pair does; all arguments must be numbers
+pub fn comparison_operator(op: &str, args: &[MalValue]) -> Result<MalValue> {
+    if args.is_empty() {
+        return Err(format!("{} requires at least one argument", op).into());
+    }
+    if !args
+        .iter()
+        .all(|arg| matches!(arg, MalValue::Number(_) | MalValue::Float(_)))
+    {
+        return Err("Arguments must be numbers".into());
+    }
+
+    for pair in args.windows(2) {
+        let order = validate_and_extract(pair, op)?.compare();
+        let holds = match op {
+            "<" => order == Some(Ordering::Less),
+            "<=" => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+            ">" => order == Some(Ordering::Greater),
+            ">=" => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+            _ => return Err(format!("Unsupported operator: {}", op).into()),
+        };
+        if !holds {
+            return Ok(MalValue::FALSE);
+        }
+    }
 
-    Ok(MalValue::Bool(result))
+    Ok(MalValue::TRUE)
 }
 
-pub fn prn_fn(args: &[MalValue]) -> Result<MalValue> {
-    let strs = args.iter()
-        .map(|v| pr_str(v, true))
+// (no-print) returns a marker that the REPL, and -e, don't echo when it is
+// the line's last result
+const NO_PRINT: &str = "no-print";
+
+pub fn no_print(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("no-print takes no arguments".into());
+    }
+    Ok(host_value(NO_PRINT, ()))
+}
+
+pub fn is_no_print(value: &MalValue) -> bool {
+    matches!(value, MalValue::Host(object) if object.type_name() == NO_PRINT)
+}
+
+fn join_printed(args: &[MalValue], print_readably: bool, separator: &str) -> String {
+    args.iter()
+        .map(|v| pr_str(v, print_readably))
         .collect::<Vec<String>>()
-        .join(" ");
-    println!("{}", strs);
+        .join(separator)
+}
+
+pub fn prn_fn(args: &[MalValue]) -> Result<MalValue> {
+    write_out(&format!("{}\n", join_printed(args, print_readably(), " ")))?;
     Ok(MalValue::Nil)
 }
 
 pub fn pr_str_fn(args: &[MalValue]) -> Result<MalValue> {
-    let strs = args.iter()
-        .map(|v| pr_str(v, true))
-        .collect::<Vec<String>>()
-        .join(" ");
-    Ok(MalValue::String(strs))
+    Ok(MalValue::String(join_printed(args, print_readably(), " ")))
 }
 
 pub fn str_fn(args: &[MalValue]) -> Result<MalValue> {
-    let strs = args.iter()
-        .map(|v| pr_str(v, false))
-        .collect::<Vec<String>>()
-        .join("");
-
-    Ok(MalValue::String(strs))
+    Ok(MalValue::String(join_printed(args, false, "")))
 }
 
 pub fn println_fn(args: &[MalValue]) -> Result<MalValue> {
-    let strs = args.iter()
-        .map(|v| pr_str(v, false))
-        .collect::<Vec<String>>()
-        .join(" ");
-    println!("{}", strs);
+    write_out(&format!("{}\n", join_printed(args, false, " ")))?;
     Ok(MalValue::Nil)
 }
 
+// pr and print are prn and println without the newline. Output may sit in a
+// buffer until the next newline, so call flush to show partial lines.
+pub fn pr_fn(args: &[MalValue]) -> Result<MalValue> {
+    write_out(&join_printed(args, print_readably(), " "))?;
+    Ok(MalValue::Nil)
+}
 
-// Function to create the REPL environment with built-in functions
-pub fn create_repl_env() -> Rc<RefCell<Env>> {
-    let repl_env = Rc::new(RefCell::new(Env::new(None)));
+pub fn print_fn(args: &[MalValue]) -> Result<MalValue> {
+    write_out(&join_printed(args, false, " "))?;
+    Ok(MalValue::Nil)
+}
+
+pub fn newline(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("newline takes no arguments".into());
+    }
+    write_out("\n")?;
+    Ok(MalValue::Nil)
+}
 
-    // Wrapper functions for comparison operators
-    fn less_than(args: &[MalValue]) -> Result<MalValue> {
-        comparison_operator("<", args)
+pub fn flush(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("flush takes no arguments".into());
     }
+    flush_out()?;
+    Ok(MalValue::Nil)
+}
 
-    fn less_than_or_equal(args: &[MalValue]) -> Result<MalValue> {
-        comparison_operator("<=", args)
+// Number formatting builtins
+
+fn as_f64(value: &MalValue, name: &str) -> Result<f64> {
+    match value {
+        MalValue::Number(n) => Ok(*n as f64),
+        MalValue::Float(f) => Ok(*f),
+        _ => Err(format!("{}: {} is not a number", name, pr_str(value, true)).into()),
     }
+}
 
-    fn greater_than(args: &[MalValue]) -> Result<MalValue> {
-        comparison_operator(">", args)
+fn precision_arg(value: &MalValue, name: &str) -> Result<usize> {
+    match value {
+        MalValue::Number(n) if (0..=100).contains(n) => Ok(*n as usize),
+        _ => Err(format!("{}: precision must be an integer from 0 to 100", name).into()),
     }
+}
 
-    fn greater_than_or_equal(args: &[MalValue]) -> Result<MalValue> {
-        comparison_operator(">=", args)
+// (to-fixed 3.14159 2) is "3.14"
+pub fn to_fixed(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [x, digits] => {
+            let x = as_f64(x, "to-fixed")?;
+            let digits = precision_arg(digits, "to-fixed")?;
+            Ok(MalValue::String(format!("{:.*}", digits, x)))
+        }
+        _ => Err("to-fixed requires a number and a number of digits".into()),
     }
+}
 
-    // Array of built-in functions
-    let builtins: &[(&str, fn(&[MalValue]) -> Result<MalValue>)] = &[
-        ("+", add),
-        ("-", sub),
-        ("*", mult),
-        ("/", divide),
-        ("list", list),
-        ("list?", list_question),
-        ("empty?", empty_question),
-        ("count", count),
-        ("=", equals),
-        ("prn", prn_fn),
-        ("pr-str", pr_str_fn),
-        ("str", str_fn),
-        ("println", println_fn),
-        ("<", less_than),
-        ("<=", less_than_or_equal),
-        (">", greater_than),
-        (">=", greater_than_or_equal),
-        // Add more built-in functions as needed
-    ];
+// Inserts separator between groups of three digits in the integer part of a
+// formatted number, leaving any sign and fraction alone
+fn group_thousands(formatted: &str, separator: &str) -> String {
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", formatted),
+    };
+    let (int_part, fraction) = match unsigned.find('.') {
+        Some(dot) => unsigned.split_at(dot),
+        None => (unsigned, ""),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i).is_multiple_of(3) {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    format!("{}{}{}", sign, grouped, fraction)
+}
+
+// (format-number n {:thousands "," :precision 3}); both options are optional.
+// Without :precision, integers print as integers and floats as they would in str.
+pub fn format_number(args: &[MalValue]) -> Result<MalValue> {
+    let (n, options) = match args {
+        [n] => (n, None),
+        [n, options] => (n, map_entries(options, "format-number")?),
+        _ => return Err("format-number requires a number and an optional options map".into()),
+    };
+    let mut thousands = String::new();
+    let mut precision = None;
+    for (key, value) in options.into_iter().flatten() {
+        match (key, value) {
+            (MalKey::Keyword(k), MalValue::String(sep)) if k == ":thousands" => {
+                thousands = sep.clone()
+            }
+            (MalKey::Keyword(k), digits) if k == ":precision" => {
+                precision = Some(precision_arg(digits, "format-number")?)
+            }
+            (key, _) => {
+                let key = MalValue::from(key.clone());
+                return Err(format!(
+                    "format-number: unknown or invalid option {}",
+                    pr_str(&key, true)
+                )
+                .into());
+            }
+        }
+    }
+
+    let formatted = match (n, precision) {
+        (_, Some(digits)) => format!("{:.*}", digits, as_f64(n, "format-number")?),
+        (MalValue::Number(_) | MalValue::Float(_), None) => pr_str(n, false),
+        _ => return Err(format!("format-number: {} is not a number", pr_str(n, true)).into()),
+    };
+    // Non-finite floats print as ##Inf and friends, which have no digits to group
+    if formatted.starts_with("##") || formatted.contains('e') {
+        return Ok(MalValue::String(formatted));
+    }
+    Ok(MalValue::String(group_thousands(&formatted, &thousands)))
+}
+
+// (to-string n radix) writes an integer in any base from 2 to 36, using
+// lowercase letters past 9; (to-string x) is (str x)
+pub fn to_string(args: &[MalValue]) -> Result<MalValue> {
+    let (n, radix) = match args {
+        [x] => return Ok(MalValue::String(pr_str(x, false))),
+        [MalValue::Number(n), MalValue::Number(radix)] if (2..=36).contains(radix) => {
+            (*n, *radix as u32)
+        }
+        [MalValue::Number(_), _] => {
+            return Err("to-string: radix must be an integer from 2 to 36".into())
+        }
+        _ => return Err("to-string requires a value, or an integer and a radix".into()),
+    };
+    let mut magnitude = n.unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        let digit = (magnitude % radix as u64) as u32;
+        digits.push(std::char::from_digit(digit, radix).unwrap_or('?'));
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    Ok(MalValue::String(digits.into_iter().rev().collect()))
+}
+
+// (print-table rows) prints a vector of maps with a column per key, in the order
+// keys first appear; (print-table headers rows) takes the columns explicitly,
+// and rows may then be vectors (by position) as well as maps (by key). Cells
+// print as str would, right-aligned like Clojure's print-table.
+pub fn print_table(args: &[MalValue]) -> Result<MalValue> {
+    let (headers, rows) = match args {
+        [rows] => (None, rows),
+        [MalValue::Square(headers) | MalValue::Round(headers), rows] => (Some(headers), rows),
+        _ => return Err("print-table requires rows and optional headers".into()),
+    };
+    let rows: Vec<MalValue> = seq_arg(rows, "print-table")?.collect();
+    let headers = match headers {
+        Some(headers) => headers.clone(),
+        None => {
+            let mut headers: Vec<MalValue> = Vec::new();
+            for row in &rows {
+                let Some(entries) = map_entries(row, "print-table")? else {
+                    continue;
+                };
+                for key in entries.keys().cloned().map(MalValue::from) {
+                    if !headers.contains(&key) {
+                        headers.push(key);
+                    }
+                }
+            }
+            headers
+        }
+    };
+    if headers.is_empty() {
+        return Ok(MalValue::Nil);
+    }
+
+    let cell = |row: &MalValue, index: usize| -> Result<String> {
+        let value = match row {
+            MalValue::Square(items) | MalValue::Round(items) => {
+                items.get(index).cloned().unwrap_or(MalValue::Nil)
+            }
+            _ => get(&[row.clone(), headers[index].clone()])?,
+        };
+        Ok(match value {
+            MalValue::Nil => String::new(),
+            value => pr_str(&value, false),
+        })
+    };
+    let mut table = vec![headers.iter().map(|h| pr_str(h, false)).collect::<Vec<_>>()];
+    for row in &rows {
+        table.push((0..headers.len()).map(|i| cell(row, i)).collect::<Result<_>>()?);
+    }
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| table.iter().map(|row| row[i].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    let format_row = |row: &[String]| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(text, &width)| format!("{:>width$}", text, width = width))
+            .collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+    let mut out = format_row(&table[0]);
+    out.push_str(&format!("|-{}-|\n", rule.join("-+-")));
+    for row in &table[1..] {
+        out.push_str(&format_row(row));
+    }
+    write_out(&out)?;
+    Ok(MalValue::Nil)
+}
+
+// Byte array builtins
+pub fn bytes(args: &[MalValue]) -> Result<MalValue> {
+    args.iter()
+        .map(|arg| match arg {
+            MalValue::Number(n) => {
+                let out_of_range = || format!("bytes: {} is not in the range 0-255", n).into();
+                u8::try_from(*n).map_err(|_| out_of_range())
+            }
+            _ => Err("bytes requires number arguments".into()),
+        })
+        .collect::<Result<Vec<u8>>>()
+        .map(MalValue::Bytes)
+}
+
+pub fn bytes_question(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("bytes? requires exactly one argument".into());
+    }
+    Ok(MalValue::bool(matches!(args[0], MalValue::Bytes(_))))
+}
+
+pub fn string_to_bytes(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::String(s)] => Ok(MalValue::Bytes(s.as_bytes().to_vec())),
+        _ => Err("string->bytes requires exactly one string argument".into()),
+    }
+}
 
-    // Array of special forms
-    let special_forms: &[(&str, fn(&[MalValue], Rc<RefCell<Env>>) -> Result<MalValue>)] = &[
-        ("def!", def_bang),
-        ("let*", let_star),
-        ("do", do_func),
-        ("fn*", fn_star),
-        ("if", if_special_form),
-        // Add more special forms as needed
+pub fn bytes_to_string(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Bytes(b)] => String::from_utf8(b.clone())
+            .map(MalValue::String)
+            .map_err(|e| format!("bytes->string: invalid UTF-8: {}", e).into()),
+        _ => Err("bytes->string requires exactly one bytes argument".into()),
+    }
+}
+
+// (read-string s) returns the first form in s, unevaluated, or nil if s has none
+pub fn read_string(args: &[MalValue]) -> Result<MalValue> {
+    let [MalValue::String(source)] = args else {
+        return Err("read-string requires exactly one string argument".into());
+    };
+    let forms = parse_input(source).map_err(|e| format_pest_error(*e))?;
+    // Input with several forms reads as one Mal node holding them all
+    Ok(forms
+        .into_iter()
+        .flat_map(|form| match form {
+            MalValue::Mal(forms) => forms,
+            form => vec![form],
+        })
+        .find(|form| !matches!(form, MalValue::Comment(_) | MalValue::EOI))
+        .unwrap_or(MalValue::Nil))
+}
+
+// (eval form) evaluates form in the top-level environment, wherever it is called
+pub fn eval_builtin(args: &[MalValue]) -> Result<MalValue> {
+    let [form] = args else {
+        return Err("eval requires exactly one argument".into());
+    };
+    let env = global_env().ok_or("eval requires an active interpreter")?;
+    eval(form, env, &active_ctx())
+}
+
+// (load-file "f.mal") evaluates every form in a file at the top level, for
+// its definitions, and returns nil
+pub fn load_file(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::FsRead, "load-file")?;
+    let [MalValue::String(path)] = args else {
+        return Err("load-file requires exactly one path argument".into());
+    };
+    let source = Source::read(path).map_err(|e| format!("load-file: {}: {}", path, e))?;
+    let env = global_env().ok_or("load-file requires an active interpreter")?;
+    let ctx = active_ctx();
+    let file = coverage::start_file(path);
+    let mut forms = source.forms().map_err(|e| format_pest_error(*e))?.enumerate();
+    let loaded = forms.by_ref().try_for_each(|(index, form)| {
+        let form = form.map_err(|e| format_pest_error(*e))?;
+        coverage::form_read(file, index, &form);
+        if !matches!(form.value, MalValue::Comment(_)) {
+            coverage::form_evaluated(file, index);
+            eval(&form.value, Rc::clone(&env), &ctx)?;
+        }
+        Ok(())
+    });
+    coverage::forms_skipped(file, forms);
+    loaded.map(|()| MalValue::Nil)
+}
+
+pub fn slurp(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::FsRead, "slurp")?;
+    match args {
+        [MalValue::String(path)] => fs::read_to_string(path)
+            .map(MalValue::String)
+            .map_err(|e| format!("slurp: {}: {}", path, e).into()),
+        _ => Err("slurp requires exactly one path argument".into()),
+    }
+}
+
+pub fn slurp_bytes(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::FsRead, "slurp-bytes")?;
+    match args {
+        [MalValue::String(path)] => fs::read(path)
+            .map(MalValue::Bytes)
+            .map_err(|e| format!("slurp-bytes: {}: {}", path, e).into()),
+        _ => Err("slurp-bytes requires exactly one path argument".into()),
+    }
+}
+
+pub fn spit_bytes(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::FsWrite, "spit-bytes")?;
+    match args {
+        [MalValue::String(path), MalValue::Bytes(b)] => fs::write(path, b)
+            .map(|_| MalValue::Nil)
+            .map_err(|e| format!("spit-bytes: {}: {}", path, e).into()),
+        _ => Err("spit-bytes requires a path and a bytes value".into()),
+    }
+}
+
+// (getenv name) is the value of an environment variable, or nil when it is unset
+pub fn getenv(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::Env, "getenv")?;
+    match args {
+        [MalValue::String(name)] => {
+            Ok(std::env::var(name).map_or(MalValue::Nil, MalValue::String))
+        }
+        _ => Err("getenv requires exactly one variable name".into()),
+    }
+}
+
+// Time and date builtins. Timestamps are milliseconds since the Unix epoch (UTC).
+fn current_time_ms() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+pub fn time_ms(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("time-ms takes no arguments".into());
+    }
+    let ms = logical_time().unwrap_or_else(current_time_ms);
+    Ok(MalValue::Number(ms))
+}
+
+pub fn now(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("now takes no arguments".into());
+    }
+    forbid_in_deterministic("now")?;
+    Ok(MalValue::Number(current_time_ms()))
+}
+
+// (bench expr {:iters 1000 :warmup 100}) evaluates expr warmup times untimed,
+// then iters times, returning {:iters :min :mean :max} with times in milliseconds
+pub fn bench(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (expr, options) = match args {
+        [expr] => (expr, MalValue::Nil),
+        [expr, options] => (expr, eval(options, Rc::clone(&env), ctx)?),
+        _ => return Err("bench requires an expression and an optional options map".into()),
+    };
+    forbid_in_deterministic("bench")?;
+    let (mut iters, mut warmup) = (1000, 100);
+    for (key, value) in map_entries(&options, "bench")?.into_iter().flatten() {
+        match (key, value) {
+            (MalKey::Keyword(k), MalValue::Number(n)) if k == ":iters" && *n > 0 => iters = *n,
+            (MalKey::Keyword(k), MalValue::Number(n)) if k == ":warmup" && *n >= 0 => warmup = *n,
+            (key, _) => {
+                let key = MalValue::from(key.clone());
+                let message = format!("bench: unknown or invalid option {}", pr_str(&key, true));
+                return Err(message.into());
+            }
+        }
+    }
+
+    for _ in 0..warmup {
+        eval(expr, Rc::clone(&env), ctx)?;
+    }
+    let (mut min, mut max, mut total) = (f64::INFINITY, 0.0_f64, 0.0);
+    for _ in 0..iters {
+        let start = Instant::now();
+        eval(expr, Rc::clone(&env), ctx)?;
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        min = min.min(ms);
+        max = max.max(ms);
+        total += ms;
+    }
+    Ok(MalValue::Map(MalMap::from([
+        (MalKey::keyword("iters"), MalValue::Number(iters)),
+        (MalKey::keyword("min"), MalValue::Float(min)),
+        (MalKey::keyword("mean"), MalValue::Float(total / iters as f64)),
+        (MalKey::keyword("max"), MalValue::Float(max)),
+    ])))
+}
+
+pub fn sleep(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(ms)] if *ms >= 0 => {
+            if !logical_sleep(*ms)? {
+                thread::sleep(Duration::from_millis(*ms as u64));
+            }
+            Ok(MalValue::Nil)
+        }
+        [MalValue::Number(_)] => Err("sleep duration must not be negative".into()),
+        _ => Err("sleep requires exactly one number argument".into()),
+    }
+}
+
+// (set-timeout ms f) and (set-interval ms f) only queue f; nothing runs until
+// (run-scheduler), which fires timers in due order until none are left
+fn add_timer(args: &[MalValue], name: &str, repeating: bool) -> Result<MalValue> {
+    let minimum = if repeating { 1 } else { 0 };
+    match args {
+        [MalValue::Number(ms), f] if *ms >= minimum && is_callable(f) => {
+            schedule(*ms, repeating, f.clone()).map(MalValue::Number)
+        }
+        _ => Err(format!(
+            "{} requires a delay of at least {} ms and a function",
+            name, minimum
+        )
+        .into()),
+    }
+}
+
+pub fn set_timeout(args: &[MalValue]) -> Result<MalValue> {
+    add_timer(args, "set-timeout", false)
+}
+
+pub fn set_interval(args: &[MalValue]) -> Result<MalValue> {
+    add_timer(args, "set-interval", true)
+}
+
+pub fn clear_timer(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(id)] => Ok(MalValue::bool(cancel_timer(*id))),
+        _ => Err("clear-timer requires a timer id".into()),
+    }
+}
+
+// Waits are real sleeps, or logical-clock advances in deterministic mode. An
+// interval runs until cleared, so the loop also stops on Ctrl-C.
+pub fn run_scheduler(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("run-scheduler takes no arguments".into());
+    }
+    while let Some((delay, callback)) = next_timer()? {
+        if delay > 0 && !logical_sleep(delay)? {
+            thread::sleep(Duration::from_millis(delay as u64));
+        }
+        eval_step()?;
+        call(&callback, &[])?;
+    }
+    Ok(MalValue::Nil)
+}
+
+// Returns a pseudo-random integer in [0, n), seeded fixed in deterministic mode
+pub fn rand_int(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(n)] if *n > 0 => {
+            let n = *n as u64;
+            // 2^64 isn't a multiple of n, so draws below 2^64 mod n are redrawn
+            // to keep every result equally likely
+            let skip = n.wrapping_neg() % n;
+            loop {
+                let draw = next_random();
+                if draw >= skip {
+                    return Ok(MalValue::Number((draw % n) as i64));
+                }
+            }
+        }
+        _ => Err("rand-int requires exactly one positive number argument".into()),
+    }
+}
+
+// Rejects strftime patterns chrono can't render, since formatting them would panic
+fn validate_date_format(fmt: &str) -> Result<()> {
+    if StrftimeItems::new(fmt).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date format: {}", fmt).into());
+    }
+    Ok(())
+}
+
+pub fn format_date(args: &[MalValue]) -> Result<MalValue> {
+    let (ms, fmt) = match args {
+        [MalValue::Number(ms)] => (*ms, "%Y-%m-%dT%H:%M:%S%.3fZ"),
+        [MalValue::Number(ms), MalValue::String(fmt)] => (*ms, fmt.as_str()),
+        _ => return Err("format-date requires a timestamp and an optional format string".into()),
+    };
+    validate_date_format(fmt)?;
+
+    let date = DateTime::from_timestamp_millis(ms)
+        .ok_or_else(|| format!("Timestamp out of range: {}", ms))?;
+    Ok(MalValue::String(date.format(fmt).to_string()))
+}
+
+pub fn parse_date(args: &[MalValue]) -> Result<MalValue> {
+    let (input, fmt) = match args {
+        [MalValue::String(input), MalValue::String(fmt)] => (input, fmt),
+        _ => return Err("parse-date requires an input string and a format string".into()),
+    };
+    validate_date_format(fmt)?;
+
+    // Fall back to a date-only parse so formats like "%Y-%m-%d" work at midnight
+    let parsed = NaiveDateTime::parse_from_str(input, fmt).or_else(|e| {
+        NaiveDate::parse_from_str(input, fmt)
+            .map(|d| d.and_time(NaiveTime::MIN))
+            .map_err(|_| e)
+    });
+
+    match parsed {
+        Ok(date) => Ok(MalValue::Number(date.and_utc().timestamp_millis())),
+        Err(e) => Err(format!("Could not parse date '{}': {}", input, e).into()),
+    }
+}
+
+
+// Wrapper functions for comparison operators
+fn less_than(args: &[MalValue]) -> Result<MalValue> {
+    comparison_operator("<", args)
+}
+
+fn less_than_or_equal(args: &[MalValue]) -> Result<MalValue> {
+    comparison_operator("<=", args)
+}
+
+fn greater_than(args: &[MalValue]) -> Result<MalValue> {
+    comparison_operator(">", args)
+}
+
+fn greater_than_or_equal(args: &[MalValue]) -> Result<MalValue> {
+    comparison_operator(">=", args)
+}
+
+// Registry of built-in functions: name, implementation, and a one-line description
+const BUILTINS: &[(&str, BuiltinFn, &str)] = &[
+    ("+", add, "Sum of any number of numbers; (+) is 0"),
+    ("-", sub, "Subtracts the rest of its arguments from the first, or negates one"),
+    ("*", mult, "Product of any number of numbers; (*) is 1"),
+    (
+        "/",
+        divide,
+        "Divides the first number by the rest, or 1 by a single argument; integers \
+         truncate unless --float-division is set",
+    ),
+    ("quot", quot, "Divides two numbers, truncating toward zero"),
+    ("inc", inc, "Adds one to a number"),
+    ("dec", dec, "Subtracts one from a number"),
+    ("zero?", zero_question, "True if a number is zero"),
+    ("pos?", pos_question, "True if a number is greater than zero"),
+    ("neg?", neg_question, "True if a number is less than zero"),
+    ("even?", even_question, "True if an integer is even"),
+    ("odd?", odd_question, "True if an integer is odd"),
+    ("list", list, "Returns a list of its arguments"),
+    ("list?", list_question, "True if the argument is a list"),
+    ("cons", cons, "List of a value followed by the elements of a sequence"),
+    ("vector", vector, "Returns a vector of its arguments"),
+    ("vec", vec, "Vector of the elements of a sequence"),
+    ("vector?", vector_question, "True if the argument is a vector"),
+    ("string?", string_question, "True if the argument is a string"),
+    ("int?", int_question, "True if the argument is an integer"),
+    ("float?", float_question, "True if the argument is a float"),
+    ("number?", number_question, "True if the argument is an integer or a float"),
+    ("boolean?", boolean_question, "True if the argument is true or false"),
+    ("keyword?", keyword_question, "True if the argument is a keyword"),
+    ("symbol?", symbol_question, "True if the argument is a symbol"),
+    ("nil?", nil_question, "True if the argument is nil"),
+    ("true?", true_question, "True if the argument is true"),
+    ("false?", false_question, "True if the argument is false"),
+    ("namespace", namespace, "Namespace of a keyword or symbol like :ns/key, or nil"),
+    ("name", name, "Name of a keyword or symbol without its namespace, or a string"),
+    ("in-ns", in_ns, "Sets the namespace that ::key keywords resolve against"),
+    ("map?", map_question, "True if the argument is a map"),
+    ("fn?", fn_question, "True if the argument is a function"),
+    ("empty?", empty_question, "True if a collection has no elements"),
+    ("count", count, "Number of elements in a collection"),
+    ("nth", nth, "Element at an index of a list, vector, or bytes"),
+    ("seq", seq, "List of a collection's elements, or nil when it is empty"),
+    ("first", first, "First element of a collection, or nil"),
+    ("rest", rest, "List of all but the first element of a collection"),
+    (
+        "conj",
+        conj,
+        "Adds elements to a collection: lists at the front, vectors at the end",
+    ),
+    (
+        "into",
+        into,
+        "Adds every element of a sequence to a collection with conj",
+    ),
+    ("hash-map", hash_map, "Map of alternating keys and values"),
+    ("get", get, "Value for a key in a map, set, or vector, or a default"),
+    (
+        "contains?",
+        contains_question,
+        "True if a map has a key, a set a member, or a vector an index",
+    ),
+    ("assoc", assoc, "Map with keys set to values; records stay records"),
+    ("dissoc", dissoc, "Map without the given keys"),
+    ("keys", keys, "List of a map's keys"),
+    ("vals", vals, "List of a map's values"),
+    ("concat", concat, "List of the elements of all its arguments in order"),
+    ("reverse", reverse, "List of a collection's elements in reverse order"),
+    ("repeat", repeat, "List of n copies of a value"),
+    (
+        "repeatedly",
+        repeatedly,
+        "List of the results of calling a function n times",
+    ),
+    ("last", last, "Last element of a collection, or nil"),
+    ("butlast", butlast, "List of all but the last element of a collection"),
+    ("sort", sort, "List of a collection's elements in order, by default or by a comparator"),
+    ("sort-by", sort_by, "List of a collection's elements ordered by a key function"),
+    ("distinct", distinct, "List of a collection's elements with duplicates removed"),
+    (
+        "apply",
+        apply_fn,
+        "Calls a function with leading arguments and the elements of a sequence",
+    ),
+    (
+        "partial",
+        partial,
+        "Function that calls another with some leading arguments already supplied",
+    ),
+    (
+        "comp",
+        comp,
+        "Function that calls the last function given, then each earlier one on the result",
+    ),
+    ("atom", atom, "Mutable reference holding a value"),
+    ("atom?", atom_question, "True if the argument is an atom"),
+    ("deref", deref, "Current value of an atom; @a is shorthand"),
+    ("walk", walk, "Applies inner to each element of a form and outer to the result"),
+    ("prewalk", prewalk, "Replaces each nested form with (f form), outermost first"),
+    ("postwalk", postwalk, "Replaces each nested form with (f form), innermost first"),
+    ("tagged", tagged, "Tagged value with a tag name, as #tag value reads"),
+    ("tag", tag, "Tag name of a tagged value or host object, or nil"),
+    ("untag", untag, "Value inside a tagged value"),
+    (
+        "tagged?",
+        tagged_question,
+        "True for a tagged value or host object, optionally with a given tag",
+    ),
+    (
+        "extend",
+        extend,
+        "Implements a protocol's methods for a type, given a map of keyword to function",
+    ),
+    ("satisfies?", satisfies_question, "True if a value's type implements a protocol"),
+    ("derive", derive, "Makes a value a child of another in the multimethod hierarchy"),
+    (
+        "isa?",
+        isa_question,
+        "True if a value equals another or derives from it, element-wise for vectors",
+    ),
+    ("zip/zipper", zip_zipper, "Zipper location at the root of a nested collection"),
+    ("zip/node", zip_node, "Value at a zipper location"),
+    ("zip/down", zip_down, "Location of the first child, or nil"),
+    ("zip/up", zip_up, "Location of the parent, or nil"),
+    ("zip/right", zip_right, "Location of the next sibling, or nil"),
+    ("zip/left", zip_left, "Location of the previous sibling, or nil"),
+    ("zip/replace", zip_replace, "Location with its value replaced"),
+    ("zip/edit", zip_edit, "Location with its value replaced by (f value args...)"),
+    ("zip/root", zip_root, "Root value of a zipper with all edits applied"),
+    (
+        "defspec",
+        defspec,
+        "Registers a spec under a keyword: a predicate, a map of key specs, or a combinator",
+    ),
+    ("valid?", valid_question, "True if a value conforms to a spec or registered spec name"),
+    ("explain", explain, "Readable report of why a value fails a spec, or nil if it conforms"),
+    (
+        "explain-data",
+        explain_data,
+        "Problems with a value as {:path :expected :value} maps, or nil if it conforms",
+    ),
+    ("spec/and", spec_and, "Spec that requires every given spec"),
+    ("spec/or", spec_or, "Spec that requires at least one given spec"),
+    ("spec/coll-of", spec_coll_of, "Spec for a list or vector whose elements all conform"),
+    ("spec/nilable", spec_nilable, "Spec that also accepts nil, or a missing map key"),
+    ("clone", clone, "Copy of a value sharing no atoms with the original"),
+    ("freeze", freeze, "Value with every nested atom replaced by its contents"),
+    ("reset!", reset_bang, "Sets an atom's value"),
+    (
+        "swap!",
+        swap_bang,
+        "Sets an atom to the result of a function of its current value",
+    ),
+    ("throw", throw, "Raises any value as an error, to be caught by try*"),
+    ("ex-info", ex_info, "Error value carrying a message and a data map"),
+    ("ex-data", ex_data, "Data map of an ex-info or thrown map, or nil"),
+    ("ex-message", ex_message, "Message of an ex-info, thrown map, or error"),
+    ("map", map, "Applies a function to each element of a sequence"),
+    ("filter", filter, "Elements of a sequence for which a predicate is truthy"),
+    ("reduce", reduce, "Folds a sequence with a two-argument function"),
+    ("=", equals, "True if all arguments are equal; lists and vectors compare by contents"),
+    ("diff", diff, "Vector describing each place two values differ"),
+    ("assert=", assert_equals, "Fails with the differences unless two values are equal"),
+    ("prn", prn_fn, "Prints values readably, followed by a newline"),
+    ("pr-str", pr_str_fn, "Returns values printed readably, joined by spaces"),
+    ("str", str_fn, "Concatenates values printed non-readably"),
+    ("render", render, "Fills a {{name}} template from a map, with {{#section}}s"),
+    ("println", println_fn, "Prints values non-readably, followed by a newline"),
+    ("pr", pr_fn, "Prints values readably, without a newline"),
+    ("print", print_fn, "Prints values non-readably, without a newline"),
+    ("newline", newline, "Prints a newline"),
+    ("print-table", print_table, "Prints rows of maps or vectors as an aligned table"),
+    ("flush", flush, "Flushes buffered output"),
+    ("term/color", term_color, "Text wrapped in ANSI escapes for a style or vector of styles"),
+    ("term/clear", term_clear, "Clears the terminal and homes the cursor"),
+    ("term/move", term_move, "Moves the cursor to a 1-based row and column"),
+    ("tty?", tty_question, "True if stdout (or :stdin or :stderr) is a terminal"),
+    ("term-width", term_width, "Width of the terminal in columns"),
+    ("term-height", term_height, "Height of the terminal in rows"),
+    ("progress-bar", progress_bar, "Draws a progress bar on stderr for a total count"),
+    ("progress-tick", progress_tick, "Advances a progress bar by one or n and redraws it"),
+    ("no-print", no_print, "Suppresses the REPL echo for the current line"),
+    ("<", less_than, "True if the numbers are strictly increasing"),
+    ("<=", less_than_or_equal, "True if the numbers never decrease"),
+    (">", greater_than, "True if the numbers are strictly decreasing"),
+    (">=", greater_than_or_equal, "True if the numbers never increase"),
+    ("min", min, "Smallest of one or more numbers"),
+    ("max", max, "Largest of one or more numbers"),
+    (
+        "time-ms",
+        time_ms,
+        "Milliseconds since the Unix epoch (a logical clock in deterministic mode)",
+    ),
+    ("now", now, "Current wall-clock time in milliseconds since the Unix epoch"),
+    ("sleep", sleep, "Pauses for a number of milliseconds"),
+    ("set-timeout", set_timeout, "Queues a function to run once after a delay in ms"),
+    ("set-interval", set_interval, "Queues a function to run every n ms until cleared"),
+    ("clear-timer", clear_timer, "Cancels a timer by id; true if it was still queued"),
+    ("run-scheduler", run_scheduler, "Runs queued timers in order until none remain"),
+    ("rand-int", rand_int, "Random integer from 0 up to, not including, n"),
+    ("to-fixed", to_fixed, "String of a number with a fixed number of decimal places"),
+    (
+        "format-number",
+        format_number,
+        "String of a number with optional :thousands separator and :precision",
+    ),
+    ("to-string", to_string, "String of a value, or of an integer in a radix from 2 to 36"),
+    (
+        "format-date",
+        format_date,
+        "Formats a millisecond timestamp with an optional strftime pattern",
+    ),
+    (
+        "parse-date",
+        parse_date,
+        "Parses a date string with a strftime pattern into a millisecond timestamp",
+    ),
+    ("bytes", bytes, "Returns a byte array of numbers 0-255"),
+    ("bytes?", bytes_question, "True if the argument is a byte array"),
+    ("string->bytes", string_to_bytes, "UTF-8 encodes a string into a byte array"),
+    ("bytes->string", bytes_to_string, "Decodes a UTF-8 byte array into a string"),
+    ("read-string", read_string, "First form in a string, unevaluated"),
+    ("eval", eval_builtin, "Evaluates a form in the top-level environment"),
+    (
+        "apropos",
+        apropos,
+        "Top-level symbols whose name or documentation matches a regular expression",
+    ),
+    (
+        "completions",
+        completions,
+        "{:name :kind :doc} for each top-level name, or each starting with a prefix",
+    ),
+    ("slurp", slurp, "Reads a whole file into a string"),
+    ("load-file", load_file, "Evaluates every form in a file at the top level"),
+    ("slurp-bytes", slurp_bytes, "Reads a whole file into a byte array"),
+    ("spit-bytes", spit_bytes, "Writes a byte array to a file"),
+    ("getenv", getenv, "Value of an environment variable, or nil when it is unset"),
+    ("edn/read", edn_read, "Parses an EDN string into a value"),
+    ("edn/write", edn_write, "Serializes a value as an EDN string"),
+    (
+        "file/open",
+        file_open,
+        "Opens a file resource for reading, or with mode \"w\" or \"a\" for writing",
+    ),
+    (
+        "file/read-line",
+        file_read_line,
+        "Reads the next line from a file resource, or nil at the end",
+    ),
+    ("file/read-all", file_read_all, "Reads the rest of a file resource into a string"),
+    ("file/write", file_write, "Writes a string or bytes to a file resource"),
+    ("kv/open", kv_open, "Opens a key-value store saved as an EDN map in a file"),
+    ("kv/get", kv_get, "Value stored under a key, or a default"),
+    ("kv/put", kv_put, "Stores a value under a key and saves the store"),
+    ("kv/delete", kv_delete, "Removes a key and saves the store"),
+    ("kv/keys", kv_keys, "List of the keys in a store"),
+    (
+        "process/spawn",
+        process_spawn,
+        "Starts a command, optionally with :on-stdout and :on-stderr line callbacks",
+    ),
+    (
+        "process/wait",
+        process_wait,
+        "Runs a process's callbacks until it exits, returning its exit code",
+    ),
+    ("close", close, "Closes a resource"),
+    ("open?", open_question, "True if a resource has not been closed"),
+    // Add more built-in functions as needed
+];
+
+// Registry of special forms
+const SPECIAL_FORMS: &[(&str, SpecialFormFn, &str)] = &[
+    ("def!", def_bang, "Binds a symbol to a value in the current environment"),
+    ("defmacro!", defmacro_bang, "Binds a symbol to a macro made from a fn* function"),
+    (
+        "macroexpand-1",
+        macroexpand_1,
+        "Returns the form a macro call expands to, without evaluating it",
+    ),
+    (
+        "macroexpand",
+        macroexpand,
+        "Expands a macro call repeatedly until the form is no longer a macro call",
+    ),
+    ("let*", let_star, "Evaluates a body with local bindings"),
+    (
+        "binding",
+        binding,
+        "Evaluates a body with top-level vars temporarily set to new values",
+    ),
+    (
+        "letrec*",
+        letrec_star,
+        "Like let*, but every name is in scope for all of the values",
+    ),
+    (
+        "as->",
+        as_thread,
+        "Threads a value through forms, binding it to a name at each step",
+    ),
+    (
+        "some->",
+        some_thread,
+        "Threads a value as the first argument of each form, stopping at nil",
+    ),
+    (
+        "try*",
+        try_star,
+        "Evaluates an expression, handling errors with a catch* clause",
+    ),
+    (
+        "with-out-str",
+        with_out_str,
+        "Evaluates a body and returns everything it printed as a string",
+    ),
+    (
+        "with-err-str",
+        with_err_str,
+        "Evaluates a body and returns everything it wrote to stderr as a string",
+    ),
+    (
+        "bench",
+        bench,
+        "Times repeated evaluations of an expression, returning {:iters :min :mean :max} in ms",
+    ),
+    ("quote", quote, "Returns its argument unevaluated"),
+    (
+        "quasiquote",
+        quasiquote,
+        "Returns its argument unevaluated except for the forms inside unquote",
+    ),
+    (
+        "quasiquoteexpand",
+        quasiquoteexpand,
+        "Returns the code a quasiquote form evaluates, without evaluating it",
+    ),
+    ("do", do_func, "Evaluates forms in order, returning the last"),
+    ("fn*", fn_star, "Creates a function from a parameter list and body"),
+    ("if", if_special_form, "Evaluates the then or else branch depending on a condition"),
+    (
+        "defrecord",
+        defrecord,
+        "Defines a record type with constructors, a predicate, and field accessors",
+    ),
+    (
+        "defmulti",
+        defmulti,
+        "Defines a function that calls the method for the value of a dispatch function",
+    ),
+    (
+        "defmethod",
+        defmethod,
+        "Adds a multimethod's method for a dispatch value, with parameters and body as for fn*",
+    ),
+    (
+        "defprotocol",
+        defprotocol,
+        "Defines a protocol and a function for each of its methods",
+    ),
+    (
+        "match",
+        match_form,
+        "Evaluates the result of the first clause whose pattern (and :when guard) matches",
+    ),
+    ("with-open", with_open, "Binds resources and closes them when the body exits"),
+    // Add more special forms as needed
+];
+
+// Archive, serialization, and network builtins, left out of builds without
+// the stdlib feature for embedders who only want the language core
+#[cfg(feature = "stdlib")]
+const STDLIB_BUILTINS: &[(&str, BuiltinFn, &str)] = &[
+    ("zip/entries", zip_entries, "Vector of {:name :size :dir?} maps for a zip archive"),
+    ("zip/slurp-entry", zip_slurp_entry, "Contents of a zip archive entry as bytes"),
+    ("tar/entries", tar_entries, "Vector of {:name :size :dir?} maps for a tar archive"),
+    ("tar/slurp-entry", tar_slurp_entry, "Contents of a tar archive entry as bytes"),
+    ("msgpack/encode", msgpack_encode, "Serializes a value as MessagePack bytes"),
+    ("msgpack/decode", msgpack_decode, "Parses MessagePack bytes into a value"),
+    (
+        "http/serve",
+        http_serve,
+        "Serves HTTP on a local port, calling a handler with each request map",
+    ),
+];
+
+// Every registered builtin, including those from optional cargo features
+fn all_builtins() -> impl Iterator<Item = &'static (&'static str, BuiltinFn, &'static str)> {
+    let optional: &[&[(&str, BuiltinFn, &str)]] = &[
+        #[cfg(feature = "stdlib")]
+        STDLIB_BUILTINS,
+        #[cfg(feature = "sqlite")]
+        crate::sql::SQL_BUILTINS,
     ];
+    BUILTINS.iter().chain(optional.iter().flat_map(|builtins| builtins.iter()))
+}
 
-    // Register built-in functions
-    for &(name, func) in builtins {
-        repl_env.borrow_mut().set(
-            name.to_string(),
-            MalValue::BuiltinFunction(Function::Builtin(func)),
-        );
+// Registry name of a builtin function value, for messages that name a predicate
+pub fn builtin_name(func: BuiltinFn) -> Option<&'static str> {
+    all_builtins()
+        .find(|&&(_, f, _)| std::ptr::fn_addr_eq(f, func))
+        .map(|&(name, _, _)| name)
+}
+
+// The builtin registered under name
+pub fn builtin_named(name: &str) -> Option<BuiltinFn> {
+    all_builtins()
+        .find(|&&(n, _, _)| n == name)
+        .map(|&(_, func, _)| func)
+}
+
+// Looks up the registry description of a builtin or special form
+pub fn builtin_doc(name: &str) -> Option<&'static str> {
+    all_builtins()
+        .map(|&(n, _, doc)| (n, doc))
+        .chain(SPECIAL_FORMS.iter().map(|&(n, _, doc)| (n, doc)))
+        .find(|&(n, _)| n == name)
+        .map(|(_, doc)| doc)
+}
+
+// Special forms are syntax rather than values: eval dispatches on the head
+// symbol before any environment lookup, so they can't be shadowed or passed around
+pub fn special_form(name: &str) -> Option<SpecialFormFn> {
+    SPECIAL_FORMS
+        .iter()
+        .find(|&&(n, _, _)| n == name)
+        .map(|&(_, func, _)| func)
+}
+
+pub fn is_special_form(name: &str) -> bool {
+    special_form(name).is_some()
+}
+
+// Special form names are reserved: a local binding could never be called, since
+// eval always treats the name in head position as the form
+pub fn bindable_symbol(form: &str, value: &MalValue) -> Result<String> {
+    match value {
+        MalValue::Symbol(s) if is_special_form(s) => Err(format!(
+            "{}: cannot bind special form name {}",
+            form, s
+        )
+        .into()),
+        MalValue::Symbol(s) => Ok(s.clone()),
+        _ => Err(format!("{}: binding names must be symbols", form).into()),
+    }
+}
+
+// A name defined at the top level, or a special form, as apropos and
+// completions describe it. Only builtins and special forms have a doc.
+struct TopLevelName {
+    name: String,
+    kind: &'static str,
+    doc: Option<&'static str>,
+}
+
+// Every top-level name and special form, sorted by name
+fn top_level_names() -> Vec<TopLevelName> {
+    let mut names: Vec<TopLevelName> = SPECIAL_FORMS
+        .iter()
+        .map(|&(name, _, doc)| TopLevelName {
+            name: name.to_string(),
+            kind: "special-form",
+            doc: Some(doc),
+        })
+        .collect();
+    if let Some(env) = global_env() {
+        let env = env.borrow();
+        for name in env.names() {
+            let (kind, doc) = match env.get(&name) {
+                Some(MalValue::BuiltinFunction(Function::Builtin(_))) => {
+                    ("builtin", builtin_doc(&name))
+                }
+                Some(MalValue::BuiltinFunction(Function::UserDefined(def))) if def.is_macro => {
+                    ("macro", None)
+                }
+                Some(MalValue::BuiltinFunction(_)) => ("function", None),
+                _ => ("value", None),
+            };
+            names.push(TopLevelName { name, kind, doc });
+        }
     }
+    names.sort_by(|a, b| a.name.cmp(&b.name));
+    names.dedup_by(|a, b| a.name == b.name);
+    names
+}
+
+// (apropos "str") lists the top-level symbols whose name or registry
+// description matches str, a case-insensitive regular expression, so a plain
+// word matches anywhere in either. Sorted by name.
+pub fn apropos(args: &[MalValue]) -> Result<MalValue> {
+    let pattern = match args {
+        [MalValue::String(pattern)] => RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| format!("apropos: {}", e))?,
+        _ => return Err("apropos requires a string".into()),
+    };
+    Ok(MalValue::Round(
+        top_level_names()
+            .into_iter()
+            .filter(|entry| {
+                pattern.is_match(&entry.name) || entry.doc.is_some_and(|doc| pattern.is_match(doc))
+            })
+            .map(|entry| MalValue::Symbol(entry.name))
+            .collect(),
+    ))
+}
+
+// (completions) describes every top-level name as {:name :kind :doc}, where
+// kind is :special-form, :builtin, :macro, :function, or :value, and doc is
+// nil unless it is a special form or builtin; (completions "pre") only those
+// starting with pre. For REPL tooling and editors.
+pub fn completions(args: &[MalValue]) -> Result<MalValue> {
+    let prefix = match args {
+        [] => "",
+        [MalValue::String(prefix)] => prefix,
+        _ => return Err("completions takes an optional prefix string".into()),
+    };
+    Ok(MalValue::Square(
+        top_level_names()
+            .into_iter()
+            .filter(|entry| entry.name.starts_with(prefix))
+            .map(|entry| {
+                let doc = entry.doc.map_or(MalValue::Nil, |doc| MalValue::String(doc.into()));
+                MalValue::Map(MalMap::from([
+                    (MalKey::keyword("name"), MalValue::String(entry.name)),
+                    (MalKey::keyword("kind"), MalValue::Atom(format!(":{}", entry.kind))),
+                    (MalKey::keyword("doc"), doc),
+                ]))
+            })
+            .collect(),
+    ))
+}
 
-    // Register special forms
-    for &(name, func) in special_forms {
+// Function to create the REPL environment with built-in functions
+pub fn create_repl_env() -> Rc<RefCell<Env>> {
+    let repl_env = Rc::new(RefCell::new(Env::new(None)));
+
+    // Register built-in functions
+    for &(name, func, _) in all_builtins() {
         repl_env.borrow_mut().set(
             name.to_string(),
-            MalValue::BuiltinFunction(Function::SpecialForm(func)),
+            MalValue::BuiltinFunction(Function::Builtin(func)),
         );
     }
+    // Optional builtin groups this build has, so scripts can check before use
+    let features = [
+        ("stdlib", cfg!(feature = "stdlib")),
+        ("sqlite", cfg!(feature = "sqlite")),
+    ];
+    let features = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| MalValue::Atom(format!(":{}", name)))
+        .collect();
+    repl_env
+        .borrow_mut()
+        .set("*features*".to_string(), MalValue::Square(features));
 
     repl_env
 }
@@ -0,0 +1,161 @@
+use crate::interpreter::with_coverage;
+use crate::reader::{ReaderError, Spanned};
+use crate::MalValue;
+
+// What --coverage records for the script and each file it loads: which
+// top-level forms were evaluated, and which fn* bodies ran at least once.
+// Bodies are recognised by their forms, so identical functions in one run
+// are hit together.
+#[derive(Default)]
+pub struct Coverage {
+    files: Vec<FileCoverage>,
+}
+
+struct FileCoverage {
+    path: String,
+    // (index among the file's forms, line, hit)
+    top_level: Vec<(usize, usize, bool)>,
+    bodies: Vec<Body>,
+}
+
+struct Body {
+    line: usize,
+    forms: Vec<MalValue>,
+    hit: bool,
+}
+
+// The body of (fn* [params] body...) or (fn* name [params] body...), as
+// fn* itself would take it
+fn fn_body(form: &Spanned) -> Option<Vec<MalValue>> {
+    let MalValue::Round(items) = &form.value else {
+        return None;
+    };
+    let args = match items.as_slice() {
+        [MalValue::Symbol(head), args @ ..] if head == "fn*" => args,
+        _ => return None,
+    };
+    let args = match args.first() {
+        Some(MalValue::Symbol(_)) => &args[1..],
+        _ => args,
+    };
+    args.get(1..).map(<[MalValue]>::to_vec)
+}
+
+fn collect_bodies(form: &Spanned, bodies: &mut Vec<Body>) {
+    if let Some(forms) = fn_body(form) {
+        bodies.push(Body {
+            line: form.span.line,
+            forms,
+            hit: false,
+        });
+    }
+    for child in &form.children {
+        collect_bodies(child, bodies);
+    }
+}
+
+impl Coverage {
+    fn add_file(&mut self, path: &str) -> usize {
+        self.files.push(FileCoverage {
+            path: path.to_string(),
+            top_level: Vec::new(),
+            bodies: Vec::new(),
+        });
+        self.files.len() - 1
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::from("Coverage:\n");
+        for file in &self.files {
+            let forms_hit = file.top_level.iter().filter(|(_, _, hit)| *hit).count();
+            let bodies_hit = file.bodies.iter().filter(|body| body.hit).count();
+            report.push_str(&format!(
+                "  {}: {}/{} top-level forms, {}/{} function bodies\n",
+                file.path,
+                forms_hit,
+                file.top_level.len(),
+                bodies_hit,
+                file.bodies.len()
+            ));
+            let mut missed: Vec<(usize, &str)> = file
+                .top_level
+                .iter()
+                .filter(|(_, _, hit)| !hit)
+                .map(|(_, line, _)| (*line, "top-level form"))
+                .chain(
+                    file.bodies
+                        .iter()
+                        .filter(|body| !body.hit)
+                        .map(|body| (body.line, "function body")),
+                )
+                .collect();
+            missed.sort();
+            for (line, what) in missed {
+                report.push_str(&format!("    line {}: {} not evaluated\n", line, what));
+            }
+        }
+        report
+    }
+}
+
+// Starts tracking a file about to be evaluated; None when coverage is off
+pub fn start_file(path: &str) -> Option<usize> {
+    with_coverage(|coverage| coverage.add_file(path))
+}
+
+// Tracks the form at index in a file's forms, and the fn* bodies in it. Files
+// are read a form at a time, so each form is added as it is read.
+pub fn form_read(file: Option<usize>, index: usize, form: &Spanned) {
+    let Some(file) = file else {
+        return;
+    };
+    if matches!(form.value, MalValue::Comment(_)) {
+        return;
+    }
+    with_coverage(|coverage| {
+        let file = &mut coverage.files[file];
+        file.top_level.push((index, form.span.line, false));
+        collect_bodies(form, &mut file.bodies);
+    });
+}
+
+// Adds the forms left over when a file stops at a failure; they count as missed
+pub fn forms_skipped(
+    file: Option<usize>,
+    forms: impl Iterator<Item = (usize, Result<Spanned, ReaderError>)>,
+) {
+    if file.is_none() {
+        return;
+    }
+    for (index, form) in forms {
+        if let Ok(form) = form {
+            form_read(file, index, &form);
+        }
+    }
+}
+
+// Records that the form at index in a file's forms is being evaluated
+pub fn form_evaluated(file: Option<usize>, index: usize) {
+    let Some(file) = file else {
+        return;
+    };
+    with_coverage(|coverage| {
+        let forms = &mut coverage.files[file].top_level;
+        if let Some(form) = forms.iter_mut().find(|(i, _, _)| *i == index) {
+            form.2 = true;
+        }
+    });
+}
+
+// Records that a function with this body was called
+pub fn body_entered(body: &[MalValue]) {
+    with_coverage(|coverage| {
+        for file in coverage.files.iter_mut() {
+            for tracked in file.bodies.iter_mut().filter(|tracked| !tracked.hit) {
+                if tracked.forms == body {
+                    tracked.hit = true;
+                }
+            }
+        }
+    });
+}
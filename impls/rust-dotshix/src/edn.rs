@@ -0,0 +1,211 @@
+use crate::error::MalError;
+use crate::printer::pr_str;
+use crate::reader::{format_pest_error, map_from_entries, MalKey};
+use crate::MalValue;
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::Parser;
+use std::collections::HashSet;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+#[derive(Parser)]
+#[grammar = "edn.pest"]
+struct EdnParser;
+
+// Parses a single EDN value. Empty input (or input made only of discards) reads as nil.
+pub fn read_edn(input: &str) -> Result<MalValue> {
+    let edn = EdnParser::parse(Rule::edn, input)
+        .map_err(format_pest_error)?
+        .next()
+        .unwrap();
+
+    let mut values = edn
+        .into_inner()
+        .filter(|pair| !matches!(pair.as_rule(), Rule::discard | Rule::EOI))
+        .map(build_value)
+        .collect::<Result<Vec<MalValue>>>()?;
+
+    match values.len() {
+        0 => Ok(MalValue::Nil),
+        1 => Ok(values.remove(0)),
+        _ => Err("edn/read expects a single value".into()),
+    }
+}
+
+fn build_elements(pair: Pair<Rule>) -> Result<Vec<MalValue>> {
+    pair.into_inner()
+        .filter(|p| p.as_rule() != Rule::discard)
+        .map(build_value)
+        .collect()
+}
+
+fn build_value(pair: Pair<Rule>) -> Result<MalValue> {
+    match pair.as_rule() {
+        Rule::nil => Ok(MalValue::Nil),
+        Rule::boolean => Ok(MalValue::Bool(pair.as_str() == "true")),
+        Rule::integer => {
+            let digits = pair.as_str().trim_end_matches('N');
+            digits
+                .parse::<i64>()
+                .map(MalValue::Number)
+                .map_err(|_| format!("EDN integer out of range: {}", pair.as_str()).into())
+        }
+        // A trailing M marks an exact decimal, which reads as the nearest float
+        Rule::float => pair
+            .as_str()
+            .trim_end_matches('M')
+            .parse::<f64>()
+            .map(MalValue::Float)
+            .map_err(|_| format!("Invalid EDN float: {}", pair.as_str()).into()),
+        Rule::symbolic_float => Ok(MalValue::Float(match pair.as_str() {
+            "##Inf" => f64::INFINITY,
+            "##-Inf" => f64::NEG_INFINITY,
+            _ => f64::NAN,
+        })),
+        Rule::string => {
+            let raw = pair.as_str();
+            unescape_edn_string(&raw[1..raw.len() - 1]).map(MalValue::String)
+        }
+        // Characters have no dedicated type, so they read as one-character strings
+        Rule::character => {
+            let name = &pair.as_str()[1..];
+            let c = match name {
+                "newline" => '\n',
+                "return" => '\r',
+                "space" => ' ',
+                "tab" => '\t',
+                _ if name.len() == 5 && name.starts_with('u') => {
+                    u32::from_str_radix(&name[1..], 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| format!("Invalid EDN character: \\{}", name))?
+                }
+                _ => {
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => c,
+                        _ => return Err(format!("Invalid EDN character: \\{}", name).into()),
+                    }
+                }
+            };
+            Ok(MalValue::String(c.to_string()))
+        }
+        Rule::keyword => Ok(MalValue::Atom(pair.as_str().to_string())),
+        Rule::symbol => Ok(MalValue::Symbol(pair.as_str().to_string())),
+        Rule::list => build_elements(pair).map(MalValue::Round),
+        Rule::vector => build_elements(pair).map(MalValue::Square),
+        Rule::map => {
+            let entries = build_elements(pair)?;
+            if entries.len() % 2 != 0 {
+                return Err("EDN map literal must contain an even number of forms".into());
+            }
+            Ok(MalValue::Map(map_from_entries(&entries)?))
+        }
+        Rule::set => {
+            let mut items: Vec<MalValue> = Vec::new();
+            let mut seen = HashSet::new();
+            for item in build_elements(pair)? {
+                if !seen.insert(MalKey::try_from(&item)?) {
+                    return Err(format!(
+                        "Duplicate EDN set element: {}",
+                        pr_str(&item, true)
+                    )
+                    .into());
+                }
+                items.push(item);
+            }
+            Ok(MalValue::Set(items))
+        }
+        Rule::tagged => {
+            let mut inner = pair.into_inner();
+            let tag = inner.next().unwrap().as_str().to_string();
+            let value = build_value(inner.next().unwrap())?;
+            Ok(MalValue::Tagged(tag, Box::new(value)))
+        }
+        rule => Err(format!("Unexpected EDN element: {:?}", rule).into()),
+    }
+}
+
+fn unescape_edn_string(s: &str) -> Result<String> {
+    let mut result = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let c = u32::from_str_radix(&hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("Invalid unicode escape: \\u{}", hex))?;
+                result.push(c);
+            }
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    Ok(result)
+}
+
+// Writes a value as EDN, refusing values that have no EDN representation
+pub fn write_edn(value: &MalValue) -> Result<String> {
+    let join = |items: &[MalValue]| -> Result<String> {
+        Ok(items
+            .iter()
+            .map(write_edn)
+            .collect::<Result<Vec<String>>>()?
+            .join(" "))
+    };
+
+    match value {
+        MalValue::Nil
+        | MalValue::Bool(_)
+        | MalValue::Number(_)
+        | MalValue::Float(_)
+        | MalValue::String(_)
+        | MalValue::Symbol(_)
+        | MalValue::Atom(_) => Ok(pr_str(value, true)),
+        MalValue::Round(items) => Ok(format!("({})", join(items)?)),
+        MalValue::Square(items) => Ok(format!("[{}]", join(items)?)),
+        MalValue::Map(map) => {
+            let entries: Vec<MalValue> = map
+                .iter()
+                .flat_map(|(key, value)| [key.clone().into(), value.clone()])
+                .collect();
+            Ok(format!("{{{}}}", join(&entries)?))
+        }
+        MalValue::Set(items) => Ok(format!("#{{{}}}", join(items)?)),
+        MalValue::Tagged(tag, inner) => Ok(format!("#{} {}", tag, write_edn(inner)?)),
+        other => Err(format!(
+            "Cannot write value as EDN: {}",
+            pr_str(other, true)
+        )
+        .into()),
+    }
+}
+
+pub fn edn_read(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::String(input)] => read_edn(input),
+        _ => Err("edn/read requires exactly one string argument".into()),
+    }
+}
+
+pub fn edn_write(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => write_edn(value).map(MalValue::String),
+        _ => Err("edn/write requires exactly one argument".into()),
+    }
+}
@@ -1,4 +1,6 @@
 use crate::MalValue;
+use crate::error::MalError;
+use crate::interpreter::EvalCtx;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt;
@@ -6,23 +8,47 @@ use std::rc::Rc;
 use std::result::Result as StdResult;
 
 // Type Definitions
-type Result<T> = StdResult<T, String>;
+type Result<T> = StdResult<T, MalError>;
 type BindingsHandle = Rc<RefCell<Bindings>>;
+pub type BuiltinFn = fn(&[MalValue]) -> Result<MalValue>;
+pub type SpecialFormFn = fn(&[MalValue], Rc<RefCell<Env>>, &EvalCtx) -> Result<MalValue>;
 
 // Function Enum for  different function types
 pub enum Function {
-    Builtin(fn(&[MalValue]) -> Result<MalValue>),
-    SpecialForm(fn(&[MalValue], Rc<RefCell<Env>>) -> Result<MalValue>),
+    Builtin(BuiltinFn),
     // WithEnv(
     //     fn(&[MalValue], Rc<RefCell<Env>>) -> Result<MalValue>,
     //     Rc<RefCell<Env>>,
     // ),
-    UserDefined {
-        params: Vec<String>,
-        rest_param: Option<String>,
-        body: Vec<MalValue>,
-        env: Rc<RefCell<Env>>,
-    },
+    UserDefined(Rc<FnDef>),
+    Partial(Rc<Partial>),
+    // (comp f g h): calls h, then g, then f, each on the previous result. The
+    // functions are called directly, so a composition of builtins never
+    // evaluates mal code. None of them is itself a composition.
+    Composed(Rc<Vec<MalValue>>),
+}
+
+// The immutable parts of a fn* closure, shared so cloning or calling a function
+// value only bumps a reference count
+pub struct FnDef {
+    pub params: Vec<String>,
+    pub rest_param: Option<String>,
+    pub body: Vec<MalValue>,
+    pub env: Rc<RefCell<Env>>,
+    // A named fn*'s name, bound to the function itself in each call rather than
+    // in env, which would then hold the function that holds it
+    pub name: Option<String>,
+    // Set by defmacro!: eval calls the function with its arguments unevaluated
+    // and evaluates the form it returns in place of the call
+    pub is_macro: bool,
+}
+
+// A function with leading arguments already supplied by partial. Calling it
+// calls func directly with args followed by the new arguments, so no wrapper
+// closure is evaluated; func is never itself a partial.
+pub struct Partial {
+    pub func: MalValue,
+    pub args: Vec<MalValue>,
 }
 
 // Implementations for Debug and Clone for Function
@@ -31,8 +57,9 @@ impl fmt::Debug for Function {
         match self {
             Function::Builtin(_) => write!(f, "Builtin Function"),
             // Function::WithEnv(_, _) => write!(f, "WithEnv Function"),
-            Function::UserDefined { .. } => write!(f, "UserDefined Function"),
-            Function::SpecialForm(_) => write!(f, "SpecialForm"),
+            Function::UserDefined(_) => write!(f, "UserDefined Function"),
+            Function::Partial(_) => write!(f, "Partial Function"),
+            Function::Composed(_) => write!(f, "Composed Function"),
         }
     }
 }
@@ -42,13 +69,9 @@ impl Clone for Function {
         match self {
             Function::Builtin(func) => Function::Builtin(*func),
             // Function::WithEnv(func, env) => Function::WithEnv(*func, Rc::clone(env)),
-            Function::SpecialForm(func) => Function::SpecialForm(*func),
-            Function::UserDefined { params, rest_param, body, env } => Function::UserDefined {
-                params: params.clone(),
-                rest_param: rest_param.clone(),
-                body: body.clone(),
-                env: env.clone(),
-            },
+            Function::UserDefined(def) => Function::UserDefined(Rc::clone(def)),
+            Function::Partial(partial) => Function::Partial(Rc::clone(partial)),
+            Function::Composed(funcs) => Function::Composed(Rc::clone(funcs)),
         }
     }
 }
@@ -56,22 +79,19 @@ impl Clone for Function {
 impl PartialEq for Function {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Function::Builtin(f1), Function::Builtin(f2)) => f1 == f2,
-            (Function::SpecialForm(f1), Function::SpecialForm(f2)) => f1 == f2,
-            (
-                Function::UserDefined {
-                    params: p1,
-                    rest_param: rp1,
-                    body: b1,
-                    ..
-                },
-                Function::UserDefined {
-                    params: p2,
-                    rest_param: rp2,
-                    body: b2,
-                    ..
-                },
-            ) => p1 == p2 && b1 == b2 && rp1 == rp2, // Ignore the environment, compare only params and body
+            (Function::Builtin(f1), Function::Builtin(f2)) => std::ptr::fn_addr_eq(*f1, *f2),
+            (Function::UserDefined(d1), Function::UserDefined(d2)) => {
+                // Ignore the environment, compare only params and body
+                Rc::ptr_eq(d1, d2)
+                    || (d1.params == d2.params
+                        && d1.body == d2.body
+                        && d1.rest_param == d2.rest_param
+                        && d1.is_macro == d2.is_macro)
+            }
+            (Function::Partial(p1), Function::Partial(p2)) => {
+                Rc::ptr_eq(p1, p2) || (p1.func == p2.func && p1.args == p2.args)
+            }
+            (Function::Composed(c1), Function::Composed(c2)) => c1 == c2,
             _ => false,
         }
     }
@@ -95,6 +115,19 @@ impl Bindings {
         self.current_level.insert(key, value);
     }
 
+    // Every name bound at this level or any enclosing one; shadowed names repeat
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.current_level.keys().cloned().collect();
+        if let Some(ref parent) = self.parent {
+            names.extend(parent.borrow().names());
+        }
+        names
+    }
+
+    pub fn is_global(&self) -> bool {
+        self.parent.is_none()
+    }
+
     pub fn get(&self, key: &String) -> Option<MalValue> {
         match self.current_level.get(key) {
             Some(value) => Some(value.clone()),
@@ -130,6 +163,22 @@ impl Env {
         self.bindings.borrow().get(key)
     }
 
+    // True for the top-level environment, which has no enclosing scope
+    pub fn is_global(&self) -> bool {
+        self.bindings.borrow().is_global()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.bindings.borrow().names()
+    }
+
+    // Unbinds everything bound at this level. The values are dropped after the
+    // bindings are released, in case dropping one reaches this environment.
+    pub fn clear(&self) {
+        let values = std::mem::take(&mut self.bindings.borrow_mut().current_level);
+        drop(values);
+    }
+
     pub fn get_bindings(&self) -> Rc<RefCell<Bindings>> {
         Rc::clone(&self.bindings)
     }
@@ -0,0 +1,45 @@
+use crate::MalValue;
+use std::fmt;
+
+// What evaluation fails with: an interpreter error, or a value raised by
+// throw, which catch* hands back exactly as it was thrown. A thrown value's
+// message is what it reports when nothing catches it.
+#[derive(Debug, Clone)]
+pub enum MalError {
+    Message(String),
+    Thrown { value: MalValue, message: String },
+}
+
+impl MalError {
+    // What catch* binds: the thrown value, or the message as a string
+    pub fn into_value(self) -> MalValue {
+        match self {
+            MalError::Message(message) => MalValue::String(message),
+            MalError::Thrown { value, .. } => value,
+        }
+    }
+
+    pub fn is_thrown(&self) -> bool {
+        matches!(self, MalError::Thrown { .. })
+    }
+}
+
+impl fmt::Display for MalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MalError::Message(message) | MalError::Thrown { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for MalError {
+    fn from(message: String) -> Self {
+        MalError::Message(message)
+    }
+}
+
+impl From<&str> for MalError {
+    fn from(message: &str) -> Self {
+        MalError::Message(message.to_string())
+    }
+}
@@ -0,0 +1,71 @@
+use crate::reader::{tokenize, TokenKind};
+use crate::term::color_enabled;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::Helper;
+use std::borrow::Cow;
+
+// SGR code for each kind of token, or None to leave it uncolored
+fn style(kind: TokenKind) -> Option<u8> {
+    match kind {
+        TokenKind::Open | TokenKind::Close | TokenKind::Symbol => None,
+        TokenKind::ReaderMacro => Some(34),
+        TokenKind::String => Some(32),
+        TokenKind::Number => Some(36),
+        TokenKind::Keyword => Some(35),
+        TokenKind::Boolean | TokenKind::Nil => Some(33),
+        TokenKind::Comment => Some(90),
+        TokenKind::Invalid => Some(31),
+    }
+}
+
+// Wraps each token of line in the ANSI escapes for its kind; whitespace,
+// commas, and uncolored tokens are copied through as they are
+pub fn highlight(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut copied = 0;
+    for token in tokenize(line) {
+        let Some(code) = style(token.kind) else {
+            continue;
+        };
+        let (start, end) = (token.span.start, token.span.end);
+        out.push_str(&line[copied..start]);
+        out.push_str(&format!("\x1b[{}m{}\x1b[0m", code, &line[start..end]));
+        copied = end;
+    }
+    out.push_str(&line[copied..]);
+    out
+}
+
+// The REPL's line editor helper: highlighting only, with rustyline's defaults
+// for completion, hints, and validation
+pub struct ReplHelper;
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if color_enabled() {
+            Cow::Owned(highlight(line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    // Every keystroke can change how the rest of the line tokenizes
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
@@ -0,0 +1,94 @@
+use crate::error::MalError;
+use crate::printer::pr_str;
+use crate::MalValue;
+use std::any::Any;
+use std::fmt;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// An opaque Rust value handed to mal code, like a compiled regex or a database
+// handle. Mal code sees only its type name, through printing, tag, and tagged?;
+// the value itself is reachable from Rust by downcasting.
+pub struct HostObject {
+    type_name: String,
+    value: Box<dyn Any>,
+}
+
+impl HostObject {
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+}
+
+impl fmt::Debug for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostObject({})", self.type_name)
+    }
+}
+
+// Wraps value for mal code; builtins and embedders introduce their own types
+// through this rather than adding MalValue variants
+pub fn host_value<T: Any>(type_name: &str, value: T) -> MalValue {
+    MalValue::Host(Rc::new(HostObject {
+        type_name: type_name.to_string(),
+        value: Box::new(value),
+    }))
+}
+
+// The type name of a tagged literal or host object
+fn tag_of(value: &MalValue) -> Option<&str> {
+    match value {
+        MalValue::Tagged(tag, _) => Some(tag),
+        MalValue::Host(object) => Some(object.type_name()),
+        _ => None,
+    }
+}
+
+fn tag_name(value: &MalValue, name: &str) -> Result<String> {
+    match value {
+        MalValue::String(s) | MalValue::Symbol(s) if !s.is_empty() => Ok(s.clone()),
+        _ => Err(format!("{}: {} is not a tag name", name, pr_str(value, true)).into()),
+    }
+}
+
+// (tagged "inst" "2024-01-01") is the value #inst "2024-01-01" reads as
+pub fn tagged(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [tag, value] => Ok(MalValue::Tagged(tag_name(tag, "tagged")?, Box::new(value.clone()))),
+        _ => Err("tagged requires a tag and a value".into()),
+    }
+}
+
+pub fn tag(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(tag_of(value).map_or(MalValue::Nil, |tag| MalValue::String(tag.to_string()))),
+        _ => Err("tag requires exactly one argument".into()),
+    }
+}
+
+pub fn untag(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Tagged(_, value)] => Ok((**value).clone()),
+        [value] => Err(format!("untag: {} is not a tagged value", pr_str(value, true)).into()),
+        _ => Err("untag requires exactly one argument".into()),
+    }
+}
+
+// (tagged? x) is true for any tagged value or host object; (tagged? x "regex")
+// also requires that tag
+pub fn tagged_question(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => Ok(MalValue::Bool(tag_of(value).is_some())),
+        [value, wanted] => {
+            let wanted = tag_name(wanted, "tagged?")?;
+            Ok(MalValue::Bool(tag_of(value) == Some(wanted.as_str())))
+        }
+        _ => Err("tagged? requires a value and an optional tag".into()),
+    }
+}
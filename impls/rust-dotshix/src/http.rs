@@ -0,0 +1,259 @@
+use crate::core::{call, is_callable};
+use crate::error::MalError;
+use crate::interpreter::{check_interrupt, require, Permission};
+use crate::output::write_err;
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::MalValue;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Requests larger than these are refused rather than buffered
+const MAX_BODY: usize = 16 * 1024 * 1024;
+const MAX_LINE: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+// Reads the request line or a header into line, refusing one longer than MAX_LINE
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<()> {
+    line.clear();
+    let read = reader
+        .take(MAX_LINE)
+        .read_line(line)
+        .map_err(|e| e.to_string())?;
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err("request line or header too long".into());
+    }
+    Ok(())
+}
+
+// Reads one HTTP/1.1 request into {:method :path :query :headers :body}.
+// Header names are lowercased; the body is a string.
+fn read_request(stream: &TcpStream) -> Result<MalValue> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".into());
+    };
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, ""),
+    };
+    let mut request = MalMap::from([
+        (MalKey::keyword("method"), MalValue::String(method.to_string())),
+        (MalKey::keyword("path"), MalValue::String(path.to_string())),
+        (MalKey::keyword("query"), MalValue::String(query.to_string())),
+    ]);
+
+    let mut headers = MalMap::new();
+    let mut content_length = 0;
+    for count in 0.. {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err("too many headers".into());
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(format!("malformed header: {}", header).into());
+        };
+        let (name, value) = (name.trim().to_lowercase(), value.trim());
+        if name == "content-length" {
+            content_length = value
+                .parse()
+                .map_err(|_| format!("bad content-length: {}", value))?;
+        }
+        headers.insert(MalKey::String(name), MalValue::String(value.to_string()));
+    }
+    if content_length > MAX_BODY {
+        return Err("request body too large".into());
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    request.extend([
+        (MalKey::keyword("headers"), MalValue::Map(headers)),
+        (
+            MalKey::keyword("body"),
+            MalValue::String(String::from_utf8_lossy(&body).into_owned()),
+        ),
+    ]);
+    Ok(MalValue::Map(request))
+}
+
+fn reason(status: i64) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+// A handler may return a response map {:status :headers :body}, where every
+// key is optional, or just a string body to send with status 200. The status
+// must be from 100 to 599, and header names and values can't contain line
+// breaks, which would end the header early.
+fn encode_response(response: &MalValue) -> Result<Vec<u8>> {
+    let mut status = 200;
+    let mut headers = Vec::new();
+    let mut body = Vec::new();
+    match response {
+        MalValue::String(s) => body = s.clone().into_bytes(),
+        MalValue::Nil => status = 204,
+        MalValue::Map(entries) => {
+            for (key, value) in entries {
+                match (key, value) {
+                    (MalKey::Keyword(k), MalValue::Number(n)) if k == ":status" => {
+                        if !(100..=599).contains(n) {
+                            return Err(format!("invalid response status {}", n).into());
+                        }
+                        status = *n
+                    }
+                    (MalKey::Keyword(k), MalValue::String(s)) if k == ":body" => {
+                        body = s.clone().into_bytes()
+                    }
+                    (MalKey::Keyword(k), MalValue::Bytes(b)) if k == ":body" => body = b.clone(),
+                    (MalKey::Keyword(k), MalValue::Map(h)) if k == ":headers" => {
+                        for (name, value) in h {
+                            let name = pr_str(&MalValue::from(name.clone()), false);
+                            let value = pr_str(value, false);
+                            if name.contains(['\r', '\n']) || value.contains(['\r', '\n']) {
+                                return Err(format!("invalid response header {}", name).into());
+                            }
+                            headers.push((name, value));
+                        }
+                    }
+                    (key, _) => {
+                        let key = MalValue::from(key.clone());
+                        return Err(format!("invalid response entry {}", pr_str(&key, true)).into());
+                    }
+                }
+            }
+        }
+        other => return Err(format!("invalid response {}", pr_str(other, true)).into()),
+    }
+
+    let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason(status));
+    for (name, value) in &headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+    let mut out = head.into_bytes();
+    out.extend(body);
+    Ok(out)
+}
+
+fn status_response(status: i64) -> MalValue {
+    MalValue::Map(MalMap::from([(MalKey::keyword("status"), MalValue::Number(status))]))
+}
+
+// A response that can't be sent as given becomes a 500, and the error is reported
+fn write_response(mut stream: &TcpStream, response: &MalValue) -> Result<()> {
+    let (out, invalid) = match encode_response(response) {
+        Ok(out) => (out, None),
+        Err(e) => (encode_response(&status_response(500))?, Some(e)),
+    };
+    stream
+        .write_all(&out)
+        .and_then(|_| stream.flush())
+        .map_err(|e| e.to_string())?;
+    invalid.map_or(Ok(()), Err)
+}
+
+fn handle(stream: TcpStream, handler: &MalValue) -> Result<()> {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .map_err(|e| e.to_string())?;
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(e) => {
+            let response = MalValue::Map(MalMap::from([
+                (MalKey::keyword("status"), MalValue::Number(400)),
+                (MalKey::keyword("body"), MalValue::String(e.to_string())),
+            ]));
+            write_response(&stream, &response)?;
+            return Err(e);
+        }
+    };
+    // A failing handler gets a 500 and is reported, but the server keeps going
+    match call(handler, &[request]) {
+        Ok(response) => write_response(&stream, &response),
+        Err(e) => {
+            write_response(&stream, &status_response(500))?;
+            Err(e)
+        }
+    }
+}
+
+// (http/serve port handler) serves requests one at a time until interrupted;
+// (http/serve port handler {:max-requests n}) returns nil after n requests
+pub fn http_serve(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::Net, "http/serve")?;
+    let (port, handler, options) = match args {
+        [MalValue::Number(port), handler] => (port, handler, None),
+        [MalValue::Number(port), handler, MalValue::Map(options)] => (port, handler, Some(options)),
+        _ => {
+            return Err(
+                "http/serve requires a port, a handler function, and an optional options map"
+                    .to_string().into(),
+            )
+        }
+    };
+    if !is_callable(handler) {
+        return Err("http/serve: handler must be a function".into());
+    }
+    let port = u16::try_from(*port).map_err(|_| format!("http/serve: invalid port {}", port))?;
+    let mut remaining = None;
+    for option in options.into_iter().flatten() {
+        match option {
+            (MalKey::Keyword(k), MalValue::Number(n)) if k == ":max-requests" && *n > 0 => {
+                remaining = Some(*n)
+            }
+            _ => return Err("http/serve: :max-requests must be a positive number".into()),
+        }
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("http/serve: port {}: {}", port, e))?;
+    // Polled rather than blocking, so Ctrl-C can stop the server between requests
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("http/serve: {}", e))?;
+
+    while remaining != Some(0) {
+        check_interrupt()?;
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            Err(e) => return Err(format!("http/serve: {}", e).into()),
+        };
+        remaining = remaining.map(|n| n - 1);
+        let handled = stream
+            .set_nonblocking(false)
+            .map_err(|e| e.to_string().into())
+            .and_then(|_| handle(stream, handler));
+        if let Err(e) = handled {
+            write_err(&format!("http/serve: {}\n", e))?;
+        }
+    }
+    Ok(MalValue::Nil)
+}
@@ -0,0 +1,835 @@
+use crate::core::{create_repl_env, ex_info_value, quiet_caught_panics};
+use crate::coverage::Coverage;
+use crate::env::FnDef;
+use crate::error::MalError;
+use crate::printer::pr_str;
+use crate::protocol::define_core_protocols;
+use crate::reader::{parse_input, MalKey, MalMap, ReaderLimits, MAX_NESTING};
+use crate::{Env, MalValue};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Hook signatures: called with the function name and evaluated arguments,
+// the error message, and the defined symbol and its value respectively.
+type CallHook = Box<dyn Fn(&str, &[MalValue])>;
+type ErrorHook = Box<dyn Fn(&str)>;
+type DefHook = Box<dyn Fn(&str, &MalValue)>;
+
+#[derive(Default)]
+struct Hooks {
+    on_call: Vec<CallHook>,
+    on_error: Vec<ErrorHook>,
+    on_def: Vec<DefHook>,
+}
+
+// Capabilities a privileged builtin must be granted before it runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    FsRead,
+    FsWrite,
+    Net,
+    Shell,
+    Env,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 5] = [
+        Permission::FsRead,
+        Permission::FsWrite,
+        Permission::Net,
+        Permission::Shell,
+        Permission::Env,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::FsRead => "fs-read",
+            Permission::FsWrite => "fs-write",
+            Permission::Net => "net",
+            Permission::Shell => "shell",
+            Permission::Env => "env",
+        }
+    }
+}
+
+// What the eval loop itself consults on every step: a Ctrl-C flag (shared with
+// the signal handler, hence atomic), the steps left of each top-level
+// evaluation's budget, the stack of user-visible calls in progress, which call
+// hooks can read for tracing, and the --trace-eval sink. eval and the special
+// forms are handed it; builtins that call back into eval use active_ctx.
+pub struct EvalCtx {
+    interrupted: Arc<AtomicBool>,
+    budget: Cell<Option<u64>>,
+    fuel: Cell<Option<u64>>,
+    call_stack: RefCell<Vec<String>>,
+    tracer: RefCell<Option<Rc<Tracer>>>,
+}
+
+impl Default for EvalCtx {
+    fn default() -> Self {
+        EvalCtx {
+            interrupted: Arc::new(AtomicBool::new(false)),
+            budget: Cell::new(None),
+            fuel: Cell::new(None),
+            call_stack: RefCell::default(),
+            tracer: RefCell::new(None),
+        }
+    }
+}
+
+impl EvalCtx {
+    // Starts a top-level evaluation with a full budget and an empty call stack
+    pub(crate) fn refuel(&self) {
+        self.fuel.set(self.budget.get());
+        self.call_stack.borrow_mut().clear();
+    }
+
+    // Called by eval before every step: fails once the evaluation has been
+    // interrupted or has used up its fuel
+    pub fn step(&self) -> Result<()> {
+        self.check_interrupt()?;
+        match self.fuel.get() {
+            Some(0) => Err("Evaluation budget exhausted".into()),
+            Some(fuel) => {
+                self.fuel.set(Some(fuel - 1));
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Fails once the evaluation has been interrupted, without using any fuel
+    pub fn check_interrupt(&self) -> Result<()> {
+        if self.interrupted.swap(false, Ordering::Relaxed) {
+            return Err("Interrupted".into());
+        }
+        Ok(())
+    }
+
+    // Records a call on the call stack until the frame is dropped
+    pub fn enter_call(&self, name: &str) -> Frame<'_> {
+        self.call_stack.borrow_mut().push(name.to_string());
+        Frame { ctx: self }
+    }
+
+    pub fn tracer(&self) -> Option<Rc<Tracer>> {
+        self.tracer.borrow().clone()
+    }
+}
+
+pub struct Frame<'a> {
+    ctx: &'a EvalCtx,
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        self.ctx.call_stack.borrow_mut().pop();
+    }
+}
+
+// What / does with two integers: truncate like quot (the default, matching
+// other mal implementations), or produce a float when the division isn't exact
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivisionMode {
+    Truncate,
+    Float,
+}
+
+// Where --trace-eval writes its tree, and how much of it: forms nested deeper
+// than max_depth are evaluated silently, and printed forms longer than
+// max_len characters are cut short
+pub struct Tracer {
+    sink: RefCell<Box<dyn Write>>,
+    max_depth: usize,
+    max_len: usize,
+    depth: Cell<usize>,
+}
+
+impl Tracer {
+    pub fn new(sink: Box<dyn Write>, max_depth: usize, max_len: usize) -> Self {
+        Tracer {
+            sink: RefCell::new(sink),
+            max_depth,
+            max_len,
+            depth: Cell::new(0),
+        }
+    }
+
+    fn line(&self, depth: usize, text: &str) {
+        if depth >= self.max_depth {
+            return;
+        }
+        let text = match text.char_indices().nth(self.max_len) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.to_string(),
+        };
+        // A trace that can't be written shouldn't fail the evaluation it describes
+        let _ = writeln!(self.sink.borrow_mut(), "{}{}", "  ".repeat(depth), text);
+    }
+}
+
+// Callbacks queued by set-timeout and set-interval. Time here is the
+// scheduler's own: it starts at 0 and jumps to each timer's due time as
+// run-scheduler fires it, so delays are relative to the firing callback.
+#[derive(Default)]
+struct Scheduler {
+    timers: Vec<Timer>,
+    next_id: i64,
+    now: i64,
+}
+
+struct Timer {
+    id: i64,
+    due: i64,
+    interval: Option<i64>,
+    callback: MalValue,
+}
+
+// A multimethod's dispatch function and its methods by dispatch value
+pub type Multimethod = (MalValue, Vec<(MalValue, MalValue)>);
+
+// Per-interpreter state shared with eval and the builtins while the interpreter is active
+pub struct State {
+    ctx: Rc<EvalCtx>,
+    // The top-level environment, where eval and load-file evaluate
+    global_env: RefCell<Option<Rc<RefCell<Env>>>>,
+    hooks: RefCell<Hooks>,
+    permissions: RefCell<HashSet<Permission>>,
+    deterministic: Cell<bool>,
+    logical_clock: Cell<i64>,
+    // Seeded on first use, so runs that never ask for a random number skip it
+    rng: OnceCell<Cell<u64>>,
+    strict_redef: Cell<bool>,
+    division_mode: Cell<DivisionMode>,
+    coverage: RefCell<Option<Coverage>>,
+    reader_limits: Cell<ReaderLimits>,
+    scheduler: RefCell<Scheduler>,
+    specs: RefCell<HashMap<String, MalValue>>,
+    records: RefCell<HashMap<String, Vec<String>>>,
+    macro_cache: RefCell<HashMap<String, CachedExpansion>>,
+    // The namespace ::key keywords resolve against, set by in-ns
+    namespace: RefCell<String>,
+    // Protocol method implementations, keyed by method and then type name
+    type_methods: RefCell<HashMap<String, HashMap<String, MalValue>>>,
+    multimethods: RefCell<HashMap<String, Multimethod>>,
+    // (child, parent) pairs added by derive
+    hierarchy: RefCell<Vec<(MalValue, MalValue)>>,
+}
+
+// A macro call's expansion, valid only while the same macro definition is in effect
+struct CachedExpansion {
+    name: String,
+    form: MalValue,
+    definition: Rc<FnDef>,
+    expansion: MalValue,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            ctx: Rc::default(),
+            global_env: RefCell::new(None),
+            hooks: RefCell::default(),
+            permissions: RefCell::new(Permission::ALL.into_iter().collect()),
+            deterministic: Cell::new(false),
+            logical_clock: Cell::new(0),
+            rng: OnceCell::new(),
+            strict_redef: Cell::new(false),
+            division_mode: Cell::new(DivisionMode::Truncate),
+            coverage: RefCell::new(None),
+            reader_limits: Cell::new(ReaderLimits::default()),
+            scheduler: RefCell::default(),
+            specs: RefCell::default(),
+            records: RefCell::default(),
+            macro_cache: RefCell::default(),
+            namespace: RefCell::new(DEFAULT_NAMESPACE.to_string()),
+            type_methods: RefCell::default(),
+            multimethods: RefCell::default(),
+            hierarchy: RefCell::default(),
+        }
+    }
+}
+
+impl State {
+    fn rng(&self) -> &Cell<u64> {
+        self.rng.get_or_init(|| Cell::new(wall_clock_seed()))
+    }
+}
+
+// Fixed seed used for the RNG in deterministic mode
+const DETERMINISTIC_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+fn wall_clock_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(DETERMINISTIC_SEED);
+    // xorshift state must never be zero
+    nanos | 1
+}
+
+// xorshift64* step
+fn next_rng(state: u64) -> (u64, u64) {
+    let mut x = state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    (x, x.wrapping_mul(0x2545_f491_4f6c_dd1d))
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Rc<State>>> = const { RefCell::new(None) };
+}
+
+fn active_state() -> Option<Rc<State>> {
+    ACTIVE.with(|active| active.borrow().clone())
+}
+
+// Restores the previously active interpreter when dropped
+pub struct ActiveGuard {
+    previous: Option<Rc<State>>,
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| *active.borrow_mut() = self.previous.take());
+    }
+}
+
+pub struct Interpreter {
+    env: Rc<RefCell<Env>>,
+    state: Rc<State>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        quiet_caught_panics();
+        let env = create_repl_env();
+        for name in ["*1", "*2", "*3", "*e"] {
+            env.borrow().set(name.to_string(), MalValue::Nil);
+        }
+        env.borrow().set(PRINT_READABLY.to_string(), MalValue::Bool(true));
+        define_core_protocols(&env);
+
+        let state = State::default();
+        // NOTE PROBABLY DELETE THIS LATER
+        // part of test cases
+        if let Ok(forms) = parse_input("(def! not (fn* (a) (if a false true)))") {
+            for form in forms {
+                let _ = crate::eval(&form, Rc::clone(&env), &state.ctx);
+            }
+        }
+        // (cond test expr ...) expands into nested ifs
+        if let Ok(forms) = parse_input(concat!(
+            "(defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) ",
+            "(if (> (count xs) 1) (nth xs 1) (throw \"odd number of forms to cond\")) ",
+            "(cons 'cond (rest (rest xs)))))))",
+        )) {
+            for form in forms {
+                let _ = crate::eval(&form, Rc::clone(&env), &state.ctx);
+            }
+        }
+
+        *state.global_env.borrow_mut() = Some(Rc::clone(&env));
+        Interpreter {
+            env,
+            state: Rc::new(state),
+        }
+    }
+
+    pub fn env(&self) -> Rc<RefCell<Env>> {
+        Rc::clone(&self.env)
+    }
+
+    // Makes this interpreter's state visible to eval and builtins until the guard drops
+    pub fn activate(&self) -> ActiveGuard {
+        let previous = ACTIVE.with(|active| active.borrow_mut().replace(Rc::clone(&self.state)));
+        ActiveGuard { previous }
+    }
+
+    // Seeds the RNG, swaps the wall clock for a logical one, and denies network,
+    // shell and environment access (a child process can read the clock or the
+    // network) so the same script produces the same output on every run.
+    pub fn set_deterministic(&self) {
+        self.state.deterministic.set(true);
+        self.state.logical_clock.set(0);
+        self.state.rng().set(DETERMINISTIC_SEED);
+        let mut permissions = self.state.permissions.borrow_mut();
+        permissions.remove(&Permission::Net);
+        permissions.remove(&Permission::Shell);
+        permissions.remove(&Permission::Env);
+    }
+
+    // Turns the warning for redefining a builtin or special form into an error
+    pub fn set_strict_redef(&self) {
+        self.state.strict_redef.set(true);
+    }
+
+    pub fn set_division_mode(&self, mode: DivisionMode) {
+        self.state.division_mode.set(mode);
+    }
+
+    // Logs every eval step from now on as an indented form → result tree
+    pub fn set_tracer(&self, tracer: Tracer) {
+        *self.state.ctx.tracer.borrow_mut() = Some(Rc::new(tracer));
+    }
+
+    // Applies to everything this interpreter reads: the REPL, scripts, load-file,
+    // and read-string. Nesting is capped at MAX_NESTING whatever limits asks for.
+    pub fn set_reader_limits(&self, limits: ReaderLimits) {
+        let max_nesting = limits.max_nesting.min(MAX_NESTING);
+        self.state.reader_limits.set(ReaderLimits { max_nesting, ..limits });
+    }
+
+    // Records which top-level forms and function bodies get evaluated from now on
+    pub fn enable_coverage(&self) {
+        *self.state.coverage.borrow_mut() = Some(Coverage::default());
+    }
+
+    // Hits and misses per file so far, or None when coverage is off
+    pub fn coverage_report(&self) -> Option<String> {
+        self.state.coverage.borrow().as_ref().map(Coverage::report)
+    }
+
+    // Setting the returned flag from any thread (e.g. a Ctrl-C handler) stops
+    // the evaluation in progress with an error
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.state.ctx.interrupted)
+    }
+
+    // Limits each top-level evaluation to a number of eval steps; None removes the limit
+    pub fn set_fuel(&self, fuel: Option<u64>) {
+        self.state.ctx.budget.set(fuel);
+        self.state.ctx.fuel.set(fuel);
+    }
+
+    pub fn rep(&self, input: &str) -> String {
+        let _active = self.activate();
+        self.state.ctx.interrupted.store(false, Ordering::Relaxed);
+        self.state.ctx.refuel();
+        crate::rep(input.to_string(), self.env(), &self.state.ctx)
+    }
+
+    pub(crate) fn ctx(&self) -> &EvalCtx {
+        &self.state.ctx
+    }
+}
+
+// Hook registration for embedders; the REPL binary itself registers none
+impl Interpreter {
+    pub fn on_call(&self, hook: impl Fn(&str, &[MalValue]) + 'static) {
+        self.state.hooks.borrow_mut().on_call.push(Box::new(hook));
+    }
+
+    pub fn on_error(&self, hook: impl Fn(&str) + 'static) {
+        self.state.hooks.borrow_mut().on_error.push(Box::new(hook));
+    }
+
+    pub fn on_def(&self, hook: impl Fn(&str, &MalValue) + 'static) {
+        self.state.hooks.borrow_mut().on_def.push(Box::new(hook));
+    }
+
+    pub fn grant(&self, permission: Permission) {
+        self.state.permissions.borrow_mut().insert(permission);
+    }
+
+    pub fn revoke(&self, permission: Permission) {
+        self.state.permissions.borrow_mut().remove(&permission);
+    }
+
+    // Revokes every permission, leaving only pure computation available
+    pub fn revoke_all(&self) {
+        self.state.permissions.borrow_mut().clear();
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.state.permissions.borrow().contains(&permission)
+    }
+}
+
+// Checked at the start of each privileged builtin. Code running outside any
+// interpreter (e.g. a bare env from create_repl_env) is unrestricted. A denial
+// throws an ex-info whose data names the builtin and the missing permission,
+// {:type :permission-denied :permission :net :builtin "http/serve"}.
+pub fn require(permission: Permission, builtin: &str) -> Result<()> {
+    match active_state() {
+        Some(state) if !state.permissions.borrow().contains(&permission) => {
+            let message = format!(
+                "Permission denied: {} requires the {} permission",
+                builtin,
+                permission.name()
+            );
+            let data = MalMap::from([
+                (MalKey::keyword("type"), MalKey::keyword("permission-denied").into()),
+                (MalKey::keyword("permission"), MalKey::keyword(permission.name()).into()),
+                (MalKey::keyword("builtin"), MalValue::String(builtin.to_string())),
+            ]);
+            let value = ex_info_value(&message, MalValue::Map(data));
+            Err(MalError::Thrown { value, message })
+        }
+        _ => Ok(()),
+    }
+}
+
+// The active interpreter's context, for builtins that call back into eval.
+// Outside any interpreter it is a fresh one, without limits.
+pub fn active_ctx() -> Rc<EvalCtx> {
+    active_state().map_or_else(Rc::default, |state| Rc::clone(&state.ctx))
+}
+
+// A step of a builtin's own loop, counted like an eval step
+pub fn eval_step() -> Result<()> {
+    active_state().map_or(Ok(()), |state| state.ctx.step())
+}
+
+// For a builtin waiting on something outside the interpreter, which shouldn't
+// use up fuel while idle
+pub fn check_interrupt() -> Result<()> {
+    active_state().map_or(Ok(()), |state| state.ctx.check_interrupt())
+}
+
+pub fn reader_limits() -> ReaderLimits {
+    active_state().map_or_else(ReaderLimits::default, |state| state.reader_limits.get())
+}
+
+pub fn with_coverage<T>(f: impl FnOnce(&mut Coverage) -> T) -> Option<T> {
+    let state = active_state()?;
+    let mut coverage = state.coverage.borrow_mut();
+    coverage.as_mut().map(f)
+}
+
+// Logs a form on entry and its result (or error) on exit, one level deeper
+// than the evaluation that requested it
+pub fn trace_eval(
+    tracer: &Tracer,
+    form: &MalValue,
+    eval: impl FnOnce() -> Result<MalValue>,
+) -> Result<MalValue> {
+    let depth = tracer.depth.get();
+    tracer.line(depth, &pr_str(form, true));
+    tracer.depth.set(depth + 1);
+    let result = eval();
+    tracer.depth.set(depth);
+    match &result {
+        Ok(value) => tracer.line(depth, &format!("→ {}", pr_str(value, true))),
+        Err(e) => tracer.line(depth, &format!("→ Error: {}", e)),
+    }
+    result
+}
+
+// Names of the calls in progress, outermost first
+pub fn call_stack() -> Vec<String> {
+    active_state()
+        .map(|state| state.ctx.call_stack.borrow().clone())
+        .unwrap_or_default()
+}
+
+// Returns the logical time in deterministic mode, advancing it by one tick per read
+pub fn logical_time() -> Option<i64> {
+    let state = active_state().filter(|state| state.deterministic.get())?;
+    let now = state.logical_clock.get();
+    state.logical_clock.set(now + 1);
+    Some(now)
+}
+
+// Advances the logical clock instead of sleeping; returns false outside deterministic mode
+pub fn logical_sleep(ms: i64) -> Result<bool> {
+    match active_state().filter(|state| state.deterministic.get()) {
+        Some(state) => {
+            let clock = state.logical_clock.get().checked_add(ms);
+            state.logical_clock.set(clock.ok_or("sleep overflows the logical clock")?);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// Fails for builtins that observe the wall clock or other nondeterministic sources
+pub fn forbid_in_deterministic(builtin: &str) -> Result<()> {
+    match active_state() {
+        Some(state) if state.deterministic.get() => Err(format!(
+            "{} is not available in deterministic mode",
+            builtin
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn next_random() -> u64 {
+    thread_local! {
+        static FALLBACK_RNG: Cell<u64> = Cell::new(wall_clock_seed());
+    }
+    match active_state() {
+        Some(state) => {
+            let (next, out) = next_rng(state.rng().get());
+            state.rng().set(next);
+            out
+        }
+        None => FALLBACK_RNG.with(|rng| {
+            let (next, out) = next_rng(rng.get());
+            rng.set(next);
+            out
+        }),
+    }
+}
+
+pub fn division_mode() -> DivisionMode {
+    active_state()
+        .map(|state| state.division_mode.get())
+        .unwrap_or(DivisionMode::Truncate)
+}
+
+// A false *print-readably* switches prn, pr, pr-str, and the REPL echo to
+// human-oriented output. It is read from the top-level environment, where
+// (def! *print-readably* false) sets it for good and (binding ...) for a while.
+pub const PRINT_READABLY: &str = "*print-readably*";
+
+pub fn print_readably() -> bool {
+    global_env().is_none_or(|env| {
+        let value = env.borrow().get(&PRINT_READABLY.to_string());
+        !matches!(value, Some(MalValue::Nil | MalValue::Bool(false)))
+    })
+}
+
+pub fn global_env() -> Option<Rc<RefCell<Env>>> {
+    active_state()?.global_env.borrow().clone()
+}
+
+pub fn strict_redef() -> bool {
+    active_state().is_some_and(|state| state.strict_redef.get())
+}
+
+// Queues callback to run ms after the scheduler's current time, and then every
+// ms again when repeating; returns the timer's id
+pub fn schedule(ms: i64, repeating: bool, callback: MalValue) -> Result<i64> {
+    let state = active_state().ok_or("Timers require an active interpreter")?;
+    let mut scheduler = state.scheduler.borrow_mut();
+    let due = scheduler.now.checked_add(ms).ok_or("Timer delay overflows the scheduler clock")?;
+    scheduler.next_id += 1;
+    let timer = Timer {
+        id: scheduler.next_id,
+        due,
+        interval: repeating.then_some(ms),
+        callback,
+    };
+    scheduler.timers.push(timer);
+    Ok(scheduler.next_id)
+}
+
+// Returns whether a timer with that id was still queued
+pub fn cancel_timer(id: i64) -> bool {
+    let Some(state) = active_state() else {
+        return false;
+    };
+    let mut scheduler = state.scheduler.borrow_mut();
+    let before = scheduler.timers.len();
+    scheduler.timers.retain(|timer| timer.id != id);
+    scheduler.timers.len() != before
+}
+
+// Removes the earliest timer (ties go to the one scheduled first), requeueing
+// it if it repeats, and advances the scheduler's clock to its due time.
+// Returns how long to wait before running it, and its callback.
+pub fn next_timer() -> Result<Option<(i64, MalValue)>> {
+    let Some(state) = active_state() else {
+        return Ok(None);
+    };
+    let mut scheduler = state.scheduler.borrow_mut();
+    let Some(index) = (0..scheduler.timers.len())
+        .min_by_key(|&i| (scheduler.timers[i].due, scheduler.timers[i].id))
+    else {
+        return Ok(None);
+    };
+    let mut timer = scheduler.timers.remove(index);
+    let delay = timer.due - scheduler.now;
+    scheduler.now = timer.due;
+    let callback = timer.callback.clone();
+    if let Some(interval) = timer.interval {
+        timer.due = timer
+            .due
+            .checked_add(interval)
+            .ok_or("Timer interval overflows the scheduler clock")?;
+        scheduler.timers.push(timer);
+    }
+    Ok(Some((delay, callback)))
+}
+
+// Specs registered by defspec, keyed by their keyword; redefining one replaces it
+pub fn define_spec(name: &str, spec: MalValue) -> Result<()> {
+    let state = active_state().ok_or("Specs require an active interpreter")?;
+    state.specs.borrow_mut().insert(name.to_string(), spec);
+    Ok(())
+}
+
+pub fn registered_spec(name: &str) -> Option<MalValue> {
+    active_state()?.specs.borrow().get(name).cloned()
+}
+
+// Record types defined by defrecord, with their field names in order
+pub fn define_record(name: &str, fields: Vec<String>) -> Result<()> {
+    let state = active_state().ok_or("Records require an active interpreter")?;
+    state.records.borrow_mut().insert(name.to_string(), fields);
+    Ok(())
+}
+
+pub fn record_fields(name: &str) -> Option<Vec<String>> {
+    active_state()?.records.borrow().get(name).cloned()
+}
+
+pub const DEFAULT_NAMESPACE: &str = "user";
+
+pub fn current_namespace() -> String {
+    active_state().map_or(DEFAULT_NAMESPACE.to_string(), |state| state.namespace.borrow().clone())
+}
+
+pub fn set_namespace(name: &str) -> Result<()> {
+    let state = active_state().ok_or("Namespaces require an active interpreter")?;
+    *state.namespace.borrow_mut() = name.to_string();
+    Ok(())
+}
+
+// Registers f as the implementation of a protocol method for a type; extending
+// a type again replaces its implementation
+pub fn extend_type(method: &str, type_name: &str, f: MalValue) -> Result<()> {
+    let state = active_state().ok_or("Protocols require an active interpreter")?;
+    state
+        .type_methods
+        .borrow_mut()
+        .entry(method.to_string())
+        .or_default()
+        .insert(type_name.to_string(), f);
+    Ok(())
+}
+
+pub fn type_method(method: &str, type_name: &str) -> Option<MalValue> {
+    let state = active_state()?;
+    let type_methods = state.type_methods.borrow();
+    type_methods.get(method)?.get(type_name).cloned()
+}
+
+// Defining a multimethod again replaces its dispatch function and drops its methods
+pub fn define_multimethod(name: &str, dispatch: MalValue) -> Result<()> {
+    let state = active_state().ok_or("Multimethods require an active interpreter")?;
+    state
+        .multimethods
+        .borrow_mut()
+        .insert(name.to_string(), (dispatch, Vec::new()));
+    Ok(())
+}
+
+// Adds the method for a dispatch value, replacing any earlier one for it
+pub fn add_method(name: &str, value: MalValue, f: MalValue) -> Result<()> {
+    let state = active_state().ok_or("Multimethods require an active interpreter")?;
+    let mut multimethods = state.multimethods.borrow_mut();
+    let (_, methods) = multimethods
+        .get_mut(name)
+        .ok_or_else(|| format!("{} is not a multimethod", name))?;
+    match methods.iter_mut().find(|(existing, _)| *existing == value) {
+        Some(method) => method.1 = f,
+        None => methods.push((value, f)),
+    }
+    Ok(())
+}
+
+pub fn multimethod(name: &str) -> Option<Multimethod> {
+    active_state()?.multimethods.borrow().get(name).cloned()
+}
+
+pub fn derive_parent(child: MalValue, parent: MalValue) -> Result<()> {
+    let state = active_state().ok_or("Hierarchies require an active interpreter")?;
+    let mut hierarchy = state.hierarchy.borrow_mut();
+    if !hierarchy.iter().any(|(c, p)| *c == child && *p == parent) {
+        hierarchy.push((child, parent));
+    }
+    Ok(())
+}
+
+pub fn parents(child: &MalValue) -> Vec<MalValue> {
+    active_state().map_or_else(Vec::new, |state| {
+        state
+            .hierarchy
+            .borrow()
+            .iter()
+            .filter(|(c, _)| c == child)
+            .map(|(_, p)| p.clone())
+            .collect()
+    })
+}
+
+// Notification points used by eval and the special forms
+pub fn notify_call(name: &str, args: &[MalValue]) {
+    if let Some(state) = active_state() {
+        for hook in state.hooks.borrow().on_call.iter() {
+            hook(name, args);
+        }
+    }
+}
+
+pub fn notify_error(err: &str) {
+    if let Some(state) = active_state() {
+        for hook in state.hooks.borrow().on_error.iter() {
+            hook(err);
+        }
+    }
+}
+
+// Macro expansion cache, keyed by the printed call form, so a macro call in a
+// loop expands on the first iteration only. A hit also requires the same form
+// (two forms can print alike) and the macro to resolve to the same definition,
+// so shadowing or redefinition never returns a stale expansion.
+pub fn cached_expansion(form: &MalValue, definition: &Rc<FnDef>) -> Option<MalValue> {
+    let state = active_state()?;
+    let cache = state.macro_cache.borrow();
+    cache
+        .get(&pr_str(form, true))
+        .filter(|cached| Rc::ptr_eq(&cached.definition, definition) && cached.form == *form)
+        .map(|cached| cached.expansion.clone())
+}
+
+pub fn cache_expansion(form: &MalValue, name: &str, definition: &Rc<FnDef>, expansion: &MalValue) {
+    if let Some(state) = active_state() {
+        let mut cache = state.macro_cache.borrow_mut();
+        // Forms typed at a long-running REPL are seldom seen twice, so start over
+        // rather than grow without bound
+        if cache.len() >= MACRO_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.insert(
+            pr_str(form, true),
+            CachedExpansion {
+                name: name.to_string(),
+                form: form.clone(),
+                definition: Rc::clone(definition),
+                expansion: expansion.clone(),
+            },
+        );
+    }
+}
+
+const MACRO_CACHE_LIMIT: usize = 4096;
+
+pub fn notify_def(symbol: &str, value: &MalValue) {
+    if let Some(state) = active_state() {
+        // Redefining a macro makes its cached expansions unreachable; drop them eagerly
+        state.macro_cache.borrow_mut().retain(|_, cached| cached.name != symbol);
+        for hook in state.hooks.borrow().on_def.iter() {
+            hook(symbol, value);
+        }
+    }
+}
+
@@ -0,0 +1,333 @@
+// The interpreter as a library: the step binaries are REPLs over it, and
+// embedders drive it through interpreter::Interpreter
+#[cfg(feature = "stdlib")]
+mod archive;
+pub mod bundle;
+mod core;
+mod coverage;
+mod edn;
+pub mod env;
+pub mod error;
+pub mod highlight;
+pub mod host;
+#[cfg(feature = "stdlib")]
+mod http;
+pub mod interpreter;
+mod json;
+#[cfg(feature = "stdlib")]
+mod msgpack;
+mod multimethod;
+mod output;
+mod pattern;
+pub mod printer;
+mod process;
+mod protocol;
+pub mod quasiquote;
+pub mod reader;
+mod record;
+mod resource;
+pub mod script;
+mod spec;
+#[cfg(feature = "sqlite")]
+mod sql;
+mod template;
+mod term;
+mod zipper;
+
+use core::{
+    bind_params, call, catch_panic, expand_macro, is_callable, is_no_print, is_truthy,
+    let_bindings, special_form,
+};
+use env::{Env, Function};
+use error::MalError;
+use interpreter::{
+    cache_expansion, cached_expansion, notify_call, notify_error, print_readably, trace_eval,
+    EvalCtx,
+};
+use printer::pr_str;
+use quasiquote::quasiquote_expand;
+use reader::{format_pest_error, read_forms, MalKey, MalMap, MalValue, ReaderError};
+use smallvec::SmallVec;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::result::Result as StdResult;
+
+// Custom Result type for our application
+type Result<T> = StdResult<T, MalError>;
+
+// Evaluated call arguments, inline for the common small-arity case
+type ArgVec = SmallVec<[MalValue; 4]>;
+
+fn read(
+    input: &str,
+) -> StdResult<impl Iterator<Item = StdResult<MalValue, ReaderError>> + '_, ReaderError> {
+    read_forms(input)
+}
+
+fn eval_each(list: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<Vec<MalValue>> {
+    list.iter().map(|x| eval(x, env.clone(), ctx)).collect()
+}
+
+// Evaluates a map literal: keys and values are evaluated but never called, and
+// when two keys evaluate to the same key the later one in key order wins
+fn eval_map(map: &MalMap, env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let mut evaluated = MalMap::new();
+    for (key, value) in map {
+        let key = eval(&MalValue::from(key.clone()), env.clone(), ctx)?;
+        let value = eval(value, env.clone(), ctx)?;
+        evaluated.insert(MalKey::try_from(&key)?, value);
+    }
+    Ok(MalValue::Map(evaluated))
+}
+
+fn eval_ast(ast: &MalValue, env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    match ast {
+        MalValue::Symbol(s) => {
+            if env.borrow().get(s).is_some() {
+                Ok(MalValue::Symbol(s.clone()))
+            } else {
+                // Return the symbol as is, assuming it might be defined later
+                Ok(MalValue::Symbol(s.clone()))
+            }
+        }
+        // Each collection keeps its own shape: a vector literal evaluates to a vector,
+        // never to a list, so code dispatching on vector? sees what it wrote
+        MalValue::Round(list) => eval_each(list, env, ctx).map(MalValue::Round),
+        MalValue::Square(list) => eval_each(list, env, ctx).map(MalValue::Square),
+        MalValue::Map(map) => eval_map(map, env, ctx),
+        MalValue::Mal(list) => eval_each(list, env, ctx).map(MalValue::Mal),
+        _ => Ok(ast.clone()),
+    }
+}
+
+// Name reported to call hooks: the head symbol, or a placeholder for anonymous functions
+fn call_name(head: &MalValue) -> String {
+    match head {
+        MalValue::Symbol(s) => s.clone(),
+        _ => "<anonymous>".to_string(),
+    }
+}
+
+// Under --trace-eval, symbols and collections are logged with their results;
+// self-evaluating scalars would only add noise to the tree
+fn eval(ast: &MalValue, env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    match ctx.tracer() {
+        Some(tracer)
+            if matches!(
+                ast,
+                MalValue::Symbol(_)
+                    | MalValue::Round(_)
+                    | MalValue::Square(_)
+                    | MalValue::Map(_)
+            ) =>
+        {
+            trace_eval(&tracer, ast, || eval_form(ast, env, ctx))
+        }
+        _ => eval_form(ast, env, ctx),
+    }
+}
+
+// Evaluates all but the last form of a body for their effects and returns the
+// last, for the caller to evaluate in tail position; None for an empty body
+fn body_tail(
+    body: &[MalValue],
+    env: &Rc<RefCell<Env>>,
+    ctx: &EvalCtx,
+) -> Result<Option<MalValue>> {
+    let Some((last, init)) = body.split_last() else {
+        return Ok(None);
+    };
+    for form in init {
+        eval(form, Rc::clone(env), ctx)?;
+    }
+    Ok(Some(last.clone()))
+}
+
+// Forms in tail position (the branches of if, the last form of do and let*
+// bodies, and the body of a user-defined function being called) replace ast
+// and env and go round the loop instead of recursing, so they don't grow the
+// native stack
+fn eval_form(ast: &MalValue, env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (mut ast, mut env) = (Cow::Borrowed(ast), env);
+    // The call whose body is being evaluated; a tail call takes over its frame
+    let mut frame = None;
+    loop {
+        ctx.step()?;
+        let list = match ast.as_ref() {
+            // Case for evaluating a single symbol
+            MalValue::Symbol(s) => {
+                return match env.borrow().get(s) {
+                    Some(value) => Ok(value),
+                    None => Err(format!("Symbol '{}' not found in environment", s).into()),
+                };
+            }
+
+            // Case for evaluating a list (represented as a Round value)
+            MalValue::Round(list) if !list.is_empty() => list,
+
+            // Other cases, delegate to eval_ast
+            _ => return eval_ast(&ast, env, ctx),
+        };
+
+        let head = match &list[0] {
+            MalValue::Symbol(name) => name.as_str(),
+            _ => "",
+        };
+        let tail = match head {
+            // (do) is nil; otherwise every form but the last is evaluated here
+            "do" => body_tail(&list[1..], &env, ctx)?,
+            "if" => {
+                let (condition, then, otherwise) = match &list[1..] {
+                    [condition, then] => (condition, then, None),
+                    [condition, then, otherwise] => (condition, then, Some(otherwise)),
+                    _ => return Err("if requires two or three arguments".into()),
+                };
+                if is_truthy(&eval(condition, Rc::clone(&env), ctx)?) {
+                    Some(then.clone())
+                } else {
+                    otherwise.cloned()
+                }
+            }
+            "quasiquote" => match &list[1..] {
+                [form] => Some(quasiquote_expand(form)),
+                _ => return Err("quasiquote requires exactly one argument".into()),
+            },
+            "let*" => {
+                let let_env = let_bindings(&list[1..], &env, ctx)?;
+                let tail = body_tail(&list[2..], &let_env, ctx)?;
+                env = let_env;
+                tail
+            }
+            _ => {
+                // Special forms are recognised by name and get their arguments unevaluated
+                if let Some(form) = special_form(head) {
+                    return catch_panic("special form", || form(&list[1..], env, ctx));
+                }
+
+                // Evaluate the first element to get the function
+                let func = eval(&list[0], env.clone(), ctx)?;
+
+                // MalValue::BuiltinFunction(Function::WithEnv(func, func_env)) => {
+                //     // Evaluate the arguments
+                //     let args: Vec<MalValue> = list[1..]
+                //         .iter()
+                //         .map(|x| eval(x, env.clone()))
+                //         .collect::<Result<Vec<MalValue>>>()?;
+                //     func(&args, func_env.clone())
+                // }
+                // A macro call is replaced by its expansion, which is evaluated in turn
+                if let MalValue::BuiltinFunction(Function::UserDefined(def)) = &func {
+                    if def.is_macro {
+                        let expansion = match cached_expansion(&ast, def) {
+                            Some(expansion) => expansion,
+                            None => {
+                                let expansion = expand_macro(&ast, def, ctx)?;
+                                cache_expansion(&ast, head, def, &expansion);
+                                expansion
+                            }
+                        };
+                        ast = Cow::Owned(expansion);
+                        continue;
+                    }
+                }
+                if !is_callable(&func) {
+                    return Err("First element is not a function".into());
+                }
+
+                // Evaluate the arguments; calls with up to four stay on the stack
+                let args = list[1..]
+                    .iter()
+                    .map(|x| eval(x, env.clone(), ctx))
+                    .collect::<Result<ArgVec>>()?;
+                let name = call_name(&list[0]);
+                notify_call(&name, &args);
+                // A partial of a fn* runs its body here too, with the stored arguments first
+                let (func, args) = match &func {
+                    MalValue::BuiltinFunction(Function::Partial(partial)) => (
+                        partial.func.clone(),
+                        partial.args.iter().cloned().chain(args).collect(),
+                    ),
+                    _ => (func, args),
+                };
+                let MalValue::BuiltinFunction(Function::UserDefined(def)) = &func else {
+                    let _frame = ctx.enter_call(&name);
+                    return call(&func, &args);
+                };
+                // The function's body replaces the call. The caller's frame goes
+                // first, since dropping a frame pops whatever is on top.
+                drop(frame.take());
+                frame = Some(ctx.enter_call(&name));
+                env = bind_params(def, &args)?;
+                body_tail(&def.body, &env, ctx)?
+            }
+        };
+        match tail {
+            Some(form) => ast = Cow::Owned(form),
+            None => return Ok(MalValue::NIL),
+        }
+    }
+}
+
+fn print(input: Vec<MalValue>) -> String {
+    input
+        .iter()
+        .map(|node| pr_str(node, print_readably()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Shifts *1 → *2 → *3 and stores the newest result in *1
+fn remember_result(result: &MalValue, env: &Rc<RefCell<Env>>) {
+    let env = env.borrow();
+    let previous = |name: &str| env.get(&name.to_string()).unwrap_or(MalValue::Nil);
+    let (one, two) = (previous("*1"), previous("*2"));
+    env.set("*3".to_string(), two);
+    env.set("*2".to_string(), one);
+    env.set("*1".to_string(), result.clone());
+}
+
+// Each form is read only once the forms before it have been evaluated, so an
+// (in-ns ...) affects the ::key forms after it on the same line
+fn rep(input: String, env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> String {
+    let mut evaluated = Vec::new();
+    // The reader returns errors for bad input, but a bug in it shouldn't end
+    // the session either
+    let mut forms = match catch_panic("reader", || Ok(read(&input))) {
+        Ok(Ok(forms)) => forms,
+        Ok(Err(e)) => return reader_error(e),
+        Err(e) => return eval_error(e, &env),
+    };
+    loop {
+        let form = match catch_panic("reader", || Ok(forms.next())) {
+            Ok(None) => break,
+            Ok(Some(Ok(form))) => form,
+            Ok(Some(Err(e))) => return reader_error(e),
+            Err(e) => return eval_error(e, &env),
+        };
+        match eval(&form, env.clone(), ctx) {
+            Ok(value) => evaluated.push(value),
+            Err(e) => return eval_error(e, &env),
+        }
+    }
+    match evaluated.last() {
+        Some(result) if is_no_print(result) => String::new(),
+        Some(result) => {
+            remember_result(result, &env);
+            print(evaluated)
+        }
+        None => print(evaluated),
+    }
+}
+
+fn eval_error(e: MalError, env: &Rc<RefCell<Env>>) -> String {
+    notify_error(&e.to_string());
+    env.borrow().set("*e".to_string(), e.clone().into_value());
+    format!("Error: {}", e)
+}
+
+fn reader_error(e: ReaderError) -> String {
+    let message = format_pest_error(*e);
+    notify_error(&message);
+    format!("Error: {:?}", message)
+}
@@ -0,0 +1,331 @@
+use crate::error::MalError;
+use crate::interpreter::reader_limits;
+use crate::printer::pr_str;
+use crate::reader::map_from_entries;
+use crate::MalValue;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Extension type ids for values MessagePack has no native representation for
+const EXT_KEYWORD: i8 = 1;
+const EXT_SYMBOL: i8 = 2;
+const EXT_SET: i8 = 3;
+const EXT_TAGGED: i8 = 4;
+
+// Encodes a value as MessagePack. Lists and vectors both become arrays.
+pub fn encode(value: &MalValue) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    encode_into(value, &mut out)?;
+    Ok(out)
+}
+
+fn encode_into(value: &MalValue, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        MalValue::Nil => out.push(0xc0),
+        MalValue::Bool(false) => out.push(0xc2),
+        MalValue::Bool(true) => out.push(0xc3),
+        MalValue::Number(n) => encode_int(*n, out),
+        MalValue::Float(f) => {
+            out.push(0xcb);
+            out.extend_from_slice(&f.to_be_bytes());
+        }
+        MalValue::String(s) => {
+            encode_len(s.len(), 0xa0, 0xd9, 0xda, 0xdb, out)?;
+            out.extend_from_slice(s.as_bytes());
+        }
+        MalValue::Bytes(b) => {
+            encode_len(b.len(), 0, 0xc4, 0xc5, 0xc6, out)?;
+            out.extend_from_slice(b);
+        }
+        MalValue::Round(items) | MalValue::Square(items) => {
+            encode_len(items.len(), 0x90, 0, 0xdc, 0xdd, out)?;
+            for item in items {
+                encode_into(item, out)?;
+            }
+        }
+        MalValue::Map(map) => {
+            encode_len(map.len(), 0x80, 0, 0xde, 0xdf, out)?;
+            for (key, value) in map {
+                encode_into(&key.clone().into(), out)?;
+                encode_into(value, out)?;
+            }
+        }
+        MalValue::Atom(k) => encode_ext(EXT_KEYWORD, k.as_bytes(), out)?,
+        MalValue::Symbol(s) => encode_ext(EXT_SYMBOL, s.as_bytes(), out)?,
+        MalValue::Set(items) => {
+            let payload = encode(&MalValue::Square(items.clone()))?;
+            encode_ext(EXT_SET, &payload, out)?;
+        }
+        MalValue::Tagged(tag, inner) => {
+            let payload = encode(&MalValue::Square(vec![
+                MalValue::String(tag.clone()),
+                (**inner).clone(),
+            ]))?;
+            encode_ext(EXT_TAGGED, &payload, out)?;
+        }
+        other => {
+            return Err(format!(
+                "Cannot encode value as MessagePack: {}",
+                pr_str(other, true)
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
+
+fn encode_int(n: i64, out: &mut Vec<u8>) {
+    match n {
+        0..=0x7f => out.push(n as u8),
+        -32..=-1 => out.push(n as i8 as u8),
+        0x80..=0xff => out.extend_from_slice(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            out.push(0xcd);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xce);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        _ if n > 0 => {
+            out.push(0xcf);
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+        -0x80..=-33 => out.extend_from_slice(&[0xd0, n as i8 as u8]),
+        -0x8000..=-0x81 => {
+            out.push(0xd1);
+            out.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            out.push(0xd2);
+            out.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        _ => {
+            out.push(0xd3);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+// Writes a length header, using the fixed-size marker when it fits in four
+// (arrays/maps) or five (strings) bits. A zero marker means the form is unavailable.
+fn encode_len(len: usize, fix: u8, m8: u8, m16: u8, m32: u8, out: &mut Vec<u8>) -> Result<()> {
+    let fix_limit = if fix == 0xa0 { 32 } else { 16 };
+    if fix != 0 && len < fix_limit {
+        out.push(fix | len as u8);
+    } else if m8 != 0 && len <= u8::MAX as usize {
+        out.extend_from_slice(&[m8, len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(m16);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as usize {
+        out.push(m32);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        return Err("Value too large to encode as MessagePack".into());
+    }
+    Ok(())
+}
+
+fn encode_ext(kind: i8, payload: &[u8], out: &mut Vec<u8>) -> Result<()> {
+    match payload.len() {
+        1 => out.push(0xd4),
+        2 => out.push(0xd5),
+        4 => out.push(0xd6),
+        8 => out.push(0xd7),
+        16 => out.push(0xd8),
+        len => encode_len(len, 0, 0xc7, 0xc8, 0xc9, out)?,
+    }
+    out.push(kind as u8);
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+// Decodes a single MessagePack value, rejecting trailing bytes
+pub fn decode(input: &[u8]) -> Result<MalValue> {
+    let mut decoder = Decoder {
+        input,
+        pos: 0,
+        depth: 0,
+        max_nesting: reader_limits().max_nesting,
+    };
+    decoder.whole()
+}
+
+// Arrays, maps, and extensions nest no deeper than the reader allows, so
+// hostile input can't overflow the stack
+struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+    max_nesting: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.input.len() - self.pos < n {
+            return Err("Unexpected end of MessagePack input".into());
+        }
+        let bytes = &self.input[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    fn int(&mut self, size: usize) -> Result<i64> {
+        let raw = self.uint(size)?;
+        let shift = 64 - 8 * size as u32;
+        Ok(((raw << shift) as i64) >> shift)
+    }
+
+    fn string(&mut self, len: usize) -> Result<MalValue> {
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map(MalValue::String)
+            .map_err(|_| "Invalid UTF-8 in MessagePack string".into())
+    }
+
+    fn nested<T>(&mut self, decode: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == self.max_nesting {
+            return Err(format!(
+                "MessagePack value nests more than {} levels deep",
+                self.max_nesting
+            )
+            .into());
+        }
+        self.depth += 1;
+        let result = decode(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn array(&mut self, len: usize) -> Result<Vec<MalValue>> {
+        self.nested(|decoder| (0..len).map(|_| decoder.value()).collect())
+    }
+
+    // Maps are read as len keys and values, which must be valid mal map keys
+    fn map(&mut self, len: usize) -> Result<MalValue> {
+        Ok(MalValue::Map(map_from_entries(&self.array(2 * len)?)?))
+    }
+
+    // The rest of the input as a single value
+    fn whole(&mut self) -> Result<MalValue> {
+        let value = self.value()?;
+        if self.pos != self.input.len() {
+            return Err(format!(
+                "Trailing bytes after MessagePack value at offset {}",
+                self.pos
+            )
+            .into());
+        }
+        Ok(value)
+    }
+
+    // A value encoded inside an extension's payload, one level deeper
+    fn payload(&self, payload: &[u8]) -> Result<MalValue> {
+        let mut decoder = Decoder {
+            input: payload,
+            pos: 0,
+            depth: self.depth,
+            max_nesting: self.max_nesting,
+        };
+        decoder.nested(Decoder::whole)
+    }
+
+    fn ext(&mut self, len: usize) -> Result<MalValue> {
+        let kind = self.byte()? as i8;
+        let payload = self.take(len)?;
+        let text = || {
+            String::from_utf8(payload.to_vec())
+                .map_err(|_| "Invalid UTF-8 in MessagePack extension".to_string())
+        };
+        match kind {
+            EXT_KEYWORD => Ok(MalValue::Atom(text()?)),
+            EXT_SYMBOL => Ok(MalValue::Symbol(text()?)),
+            EXT_SET => match self.payload(payload)? {
+                MalValue::Square(items) => Ok(MalValue::Set(items)),
+                _ => Err("Malformed MessagePack set extension".into()),
+            },
+            EXT_TAGGED => match self.payload(payload)? {
+                MalValue::Square(items) => match items.as_slice() {
+                    [MalValue::String(tag), value] => {
+                        Ok(MalValue::Tagged(tag.clone(), Box::new(value.clone())))
+                    }
+                    _ => Err("Malformed MessagePack tagged extension".into()),
+                },
+                _ => Err("Malformed MessagePack tagged extension".into()),
+            },
+            _ => Err(format!("Unsupported MessagePack extension type {}", kind).into()),
+        }
+    }
+
+    fn value(&mut self) -> Result<MalValue> {
+        let marker = self.byte()?;
+        match marker {
+            0x00..=0x7f => Ok(MalValue::Number(marker as i64)),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize),
+            0x90..=0x9f => Ok(MalValue::Square(self.array((marker & 0x0f) as usize)?)),
+            0xa0..=0xbf => self.string((marker & 0x1f) as usize),
+            0xc0 => Ok(MalValue::Nil),
+            0xc2 => Ok(MalValue::Bool(false)),
+            0xc3 => Ok(MalValue::Bool(true)),
+            0xc4..=0xc6 => {
+                let len = self.uint(1 << (marker - 0xc4))? as usize;
+                Ok(MalValue::Bytes(self.take(len)?.to_vec()))
+            }
+            0xc7..=0xc9 => {
+                let len = self.uint(1 << (marker - 0xc7))? as usize;
+                self.ext(len)
+            }
+            0xca => Ok(MalValue::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(MalValue::Float(f64::from_bits(self.uint(8)?))),
+            0xcc..=0xcf => {
+                let n = self.uint(1 << (marker - 0xcc))?;
+                i64::try_from(n)
+                    .map(MalValue::Number)
+                    .map_err(|_| format!("MessagePack integer out of range: {}", n).into())
+            }
+            0xd0..=0xd3 => Ok(MalValue::Number(self.int(1 << (marker - 0xd0))?)),
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4)),
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))? as usize;
+                self.string(len)
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(2 << (marker - 0xdc))? as usize;
+                Ok(MalValue::Square(self.array(len)?))
+            }
+            0xde | 0xdf => {
+                let len = self.uint(2 << (marker - 0xde))? as usize;
+                self.map(len)
+            }
+            0xe0..=0xff => Ok(MalValue::Number(marker as i8 as i64)),
+            _ => Err(format!("Invalid MessagePack marker 0x{:02x}", marker).into()),
+        }
+    }
+}
+
+pub fn msgpack_encode(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [value] => encode(value).map(MalValue::Bytes),
+        _ => Err("msgpack/encode requires exactly one argument".into()),
+    }
+}
+
+pub fn msgpack_decode(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Bytes(b)] => decode(b),
+        _ => Err("msgpack/decode requires exactly one bytes argument".into()),
+    }
+}
@@ -0,0 +1,132 @@
+use crate::core::{bound_builtin, call, check_redefinition, fn_star, is_callable};
+use crate::error::MalError;
+use crate::eval;
+use crate::interpreter::{
+    add_method, define_multimethod, derive_parent, multimethod, notify_def, parents, EvalCtx,
+};
+use crate::printer::pr_str;
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// The method for this dispatch value is used when no other method matches
+const DEFAULT_DISPATCH: &str = ":default";
+
+// True when child equals parent, reaches it through the parents added by
+// derive, or both are vectors of the same length whose elements are each isa?
+fn isa(child: &MalValue, parent: &MalValue) -> bool {
+    if child == parent {
+        return true;
+    }
+    if let (MalValue::Square(cs), MalValue::Square(ps)) = (child, parent) {
+        if cs.len() == ps.len() && cs.iter().zip(ps).all(|(c, p)| isa(c, p)) {
+            return true;
+        }
+    }
+    parents(child).iter().any(|ancestor| isa(ancestor, parent))
+}
+
+// The method whose dispatch value value is isa? and that is more specific than
+// every other such method, falling back to the :default method
+fn find_method(
+    name: &str,
+    value: &MalValue,
+    methods: &[(MalValue, MalValue)],
+) -> Result<MalValue> {
+    let candidates: Vec<&(MalValue, MalValue)> =
+        methods.iter().filter(|(key, _)| isa(value, key)).collect();
+    let best = candidates
+        .iter()
+        .find(|(key, _)| candidates.iter().all(|(other, _)| isa(key, other)));
+    if let Some((_, method)) = best {
+        return Ok(method.clone());
+    }
+    if !candidates.is_empty() {
+        let keys: Vec<String> = candidates.iter().map(|(key, _)| pr_str(key, true)).collect();
+        return Err(format!(
+            "{}: dispatch value {} matches {}, none of which is more specific",
+            name,
+            pr_str(value, true),
+            keys.join(" and ")
+        )
+        .into());
+    }
+    methods
+        .iter()
+        .find(|(key, _)| matches!(key, MalValue::Atom(k) if k == DEFAULT_DISPATCH))
+        .map(|(_, method)| method.clone())
+        .ok_or_else(|| {
+            format!("{}: no method for dispatch value {}", name, pr_str(value, true)).into()
+        })
+}
+
+fn dispatch(args: &[MalValue]) -> Result<MalValue> {
+    let (name, args) = match args {
+        [MalValue::String(name), MalValue::Round(args)] => (name, args),
+        _ => return Err("multimethod dispatch: malformed call".into()),
+    };
+    let (dispatch_fn, methods) =
+        multimethod(name).ok_or_else(|| format!("{} is not a multimethod", name))?;
+    let value = call(&dispatch_fn, args)?;
+    call(&find_method(name, &value, &methods)?, args)
+}
+
+// (defmulti area :shape) defines area as a function that calls the dispatch
+// function, here :shape, on its arguments and then the method for the result
+pub fn defmulti(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (name, dispatch_fn) = match args {
+        [MalValue::Symbol(name), dispatch_fn] => (name, eval(dispatch_fn, Rc::clone(&env), ctx)?),
+        _ => return Err("defmulti requires a name and a dispatch function".into()),
+    };
+    if !is_callable(&dispatch_fn) {
+        return Err(format!("defmulti: {} is not a function", pr_str(&dispatch_fn, true)).into());
+    }
+    check_redefinition("defmulti", name, &env)?;
+    define_multimethod(name, dispatch_fn)?;
+    let value = bound_builtin(dispatch, MalValue::String(name.clone()), &env);
+    env.borrow().set(name.clone(), value.clone());
+    notify_def(name, &value);
+    Ok(value)
+}
+
+// (defmethod area :square [s] (* (:side s) (:side s))) adds the method for a
+// dispatch value; the rest of the form is the parameters and body as for fn*
+pub fn defmethod(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (name, value, definition) = match args {
+        [MalValue::Symbol(name), value, definition @ ..] if !definition.is_empty() => {
+            (name, eval(value, Rc::clone(&env), ctx)?, definition)
+        }
+        _ => return Err("defmethod requires a name, a dispatch value, and parameters".into()),
+    };
+    let method = fn_star(definition, env, ctx)?;
+    add_method(name, value, method)?;
+    Ok(MalValue::Nil)
+}
+
+// (derive :square :shape) makes methods for :shape apply to :square too
+pub fn derive(args: &[MalValue]) -> Result<MalValue> {
+    let [child, parent] = args else {
+        return Err("derive requires a child and a parent".into());
+    };
+    // A cycle would make isa? loop forever
+    if isa(parent, child) {
+        return Err(format!(
+            "derive: deriving {} from {} would make a cycle",
+            pr_str(child, true),
+            pr_str(parent, true)
+        )
+        .into());
+    }
+    derive_parent(child.clone(), parent.clone())?;
+    Ok(MalValue::Nil)
+}
+
+pub fn isa_question(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [child, parent] => Ok(MalValue::Bool(isa(child, parent))),
+        _ => Err("isa? requires a child and a parent".into()),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::MalError;
+use std::cell::RefCell;
+use std::io::{self, Write};
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Out,
+    Err,
+}
+
+thread_local! {
+    // Open captures per stream, innermost last; only the innermost one receives text
+    static CAPTURES: RefCell<[Vec<String>; 2]> =
+        const { RefCell::new([Vec::new(), Vec::new()]) };
+}
+
+fn index(stream: Stream) -> usize {
+    match stream {
+        Stream::Out => 0,
+        Stream::Err => 1,
+    }
+}
+
+// All printing builtins write through here rather than calling print! directly,
+// so output can be redirected into a capture
+pub fn write(stream: Stream, text: &str) -> Result<()> {
+    let captured = CAPTURES.with(|captures| {
+        match captures.borrow_mut()[index(stream)].last_mut() {
+            Some(buffer) => {
+                buffer.push_str(text);
+                true
+            }
+            None => false,
+        }
+    });
+    if captured {
+        return Ok(());
+    }
+
+    let result = match stream {
+        Stream::Out => io::stdout().write_all(text.as_bytes()),
+        Stream::Err => io::stderr().write_all(text.as_bytes()),
+    };
+    result.map_err(|e| format!("Failed to write output: {}", e).into())
+}
+
+pub fn write_out(text: &str) -> Result<()> {
+    write(Stream::Out, text)
+}
+
+pub fn write_err(text: &str) -> Result<()> {
+    write(Stream::Err, text)
+}
+
+pub fn flush_out() -> Result<()> {
+    io::stdout()
+        .flush()
+        .map_err(|e| format!("Failed to flush output: {}", e).into())
+}
+
+// Runs body with everything written to stream collected instead of printed.
+// The capture is closed even if body fails, in which case the text is dropped.
+pub fn capture<T>(stream: Stream, body: impl FnOnce() -> Result<T>) -> Result<String> {
+    let (result, text) = capture_with(stream, body);
+    result.map(|_| text)
+}
+
+// Like capture, but keeps the text whatever body returns, alongside its value
+pub fn capture_with<T>(stream: Stream, body: impl FnOnce() -> T) -> (T, String) {
+    CAPTURES.with(|captures| captures.borrow_mut()[index(stream)].push(String::new()));
+    let value = body();
+    let text = CAPTURES.with(|captures| captures.borrow_mut()[index(stream)].pop());
+    (value, text.unwrap_or_default())
+}
@@ -0,0 +1,215 @@
+use crate::core::{bindable_symbol, is_truthy};
+use crate::error::MalError;
+use crate::eval;
+use crate::interpreter::EvalCtx;
+use crate::printer::pr_str;
+use crate::reader::MalKey;
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Patterns accepted by match:
+//   _               matches anything
+//   x               matches anything and binds it to x; a name used twice in
+//                   one pattern must match equal values
+//   1 "s" :k nil 'x matches an equal value
+//   [a b & more]    matches a list or vector element by element; after & one
+//                   pattern matches the remaining elements as a list
+//   {:k p}          matches a map whose value under :k matches p
+enum Pattern {
+    Any,
+    Bind(String),
+    Literal(MalValue),
+    Seq(Vec<Pattern>, Option<Box<Pattern>>),
+    Map(Vec<(MalKey, Pattern)>),
+}
+
+fn compile(pattern: &MalValue) -> Result<Pattern> {
+    match pattern {
+        MalValue::Symbol(s) if s == "_" => Ok(Pattern::Any),
+        MalValue::Symbol(_) => Ok(Pattern::Bind(bindable_symbol("match", pattern)?)),
+        MalValue::Square(items) => {
+            let (fixed, rest) = match items.iter().position(is_amp) {
+                Some(pos) => match &items[pos + 1..] {
+                    [rest] => (&items[..pos], Some(Box::new(compile(rest)?))),
+                    _ => return Err("match: expected one pattern after &".into()),
+                },
+                None => (&items[..], None),
+            };
+            let fixed = fixed.iter().map(compile).collect::<Result<Vec<Pattern>>>()?;
+            Ok(Pattern::Seq(fixed, rest))
+        }
+        MalValue::Map(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(key, pattern)| Ok((key.clone(), compile(pattern)?)))
+                .collect::<Result<Vec<(MalKey, Pattern)>>>()?;
+            Ok(Pattern::Map(entries))
+        }
+        MalValue::Round(items) => match items.as_slice() {
+            [MalValue::Symbol(quote), quoted] if quote == "quote" => {
+                Ok(Pattern::Literal(quoted.clone()))
+            }
+            _ => Err(format!("match: unsupported pattern {}", pr_str(pattern, true)).into()),
+        },
+        MalValue::Number(_)
+        | MalValue::Float(_)
+        | MalValue::String(_)
+        | MalValue::Atom(_)
+        | MalValue::Bool(_)
+        | MalValue::Nil => Ok(Pattern::Literal(pattern.clone())),
+        _ => Err(format!("match: unsupported pattern {}", pr_str(pattern, true)).into()),
+    }
+}
+
+// Equality that, unlike ==, tells lists from vectors, since [a b] and (quote x)
+// compile differently from (a b) and [quote x]
+fn same_form(a: &MalValue, b: &MalValue) -> bool {
+    match (a, b) {
+        (MalValue::Round(a), MalValue::Round(b)) | (MalValue::Square(a), MalValue::Square(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_form(a, b))
+        }
+        (MalValue::Map(a), MalValue::Map(b)) => {
+            a.len() == b.len()
+                && a.iter().zip(b).all(|((ka, va), (kb, vb))| ka == kb && same_form(va, vb))
+        }
+        (MalValue::Round(_) | MalValue::Square(_) | MalValue::Map(_), _) => false,
+        _ => a == b,
+    }
+}
+
+// Each pattern of a match form, next to the form it was compiled from
+type Compiled = Rc<[(MalValue, Pattern)]>;
+
+// Compiled entries are dropped all at once past this many forms
+const MAX_COMPILED: usize = 4096;
+
+thread_local! {
+    // Compiled patterns by the address of the match form's arguments. A form
+    // that is evaluated and freed can leave its address to a different one, so
+    // an entry is only used while its patterns are the same as the form's.
+    static COMPILED: RefCell<HashMap<usize, Compiled>> = RefCell::new(HashMap::new());
+}
+
+// The form's patterns, compiled the first time it is evaluated
+fn compiled(args: &[MalValue], patterns: &[&MalValue]) -> Result<Compiled> {
+    let key = args.as_ptr() as usize;
+    let cached = COMPILED.with_borrow(|cache| cache.get(&key).cloned());
+    if let Some(compiled) = cached.filter(|compiled| {
+        compiled.len() == patterns.len()
+            && compiled.iter().zip(patterns).all(|((source, _), form)| same_form(source, form))
+    }) {
+        return Ok(compiled);
+    }
+    let compiled = patterns
+        .iter()
+        .map(|form| Ok(((*form).clone(), compile(form)?)))
+        .collect::<Result<Compiled>>()?;
+    COMPILED.with_borrow_mut(|cache| {
+        if cache.len() >= MAX_COMPILED {
+            cache.clear();
+        }
+        cache.insert(key, Rc::clone(&compiled));
+    });
+    Ok(compiled)
+}
+
+fn is_amp(value: &MalValue) -> bool {
+    matches!(value, MalValue::Symbol(s) if s == "&")
+}
+
+// Tests value against pattern, adding any bindings; on failure the bindings may
+// hold partial results, so callers start each clause with a fresh list
+fn matches(pattern: &Pattern, value: &MalValue, bindings: &mut Vec<(String, MalValue)>) -> bool {
+    match pattern {
+        Pattern::Any => true,
+        Pattern::Bind(name) => match bindings.iter().find(|(bound, _)| bound == name) {
+            Some((_, previous)) => previous == value,
+            None => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+        },
+        Pattern::Literal(literal) => literal == value,
+        Pattern::Seq(fixed, rest) => {
+            let (MalValue::Round(items) | MalValue::Square(items)) = value else {
+                return false;
+            };
+            let length_ok = match rest {
+                Some(_) => items.len() >= fixed.len(),
+                None => items.len() == fixed.len(),
+            };
+            length_ok
+                && fixed
+                    .iter()
+                    .zip(items)
+                    .all(|(pattern, item)| matches(pattern, item, bindings))
+                && rest.as_ref().is_none_or(|rest| {
+                    let remaining = MalValue::Round(items[fixed.len()..].to_vec());
+                    matches(rest, &remaining, bindings)
+                })
+        }
+        Pattern::Map(entries) => {
+            let MalValue::Map(map) = value else {
+                return false;
+            };
+            entries.iter().all(|(key, pattern)| {
+                map.get(key)
+                    .is_some_and(|found| matches(pattern, found, bindings))
+            })
+        }
+    }
+}
+
+// (match expr pattern result ...) evaluates expr once and returns the result
+// of the first clause whose pattern matches it, with the pattern's names bound.
+// A clause may be guarded, as pattern :when test result; the test runs with
+// the bindings in scope and the clause is skipped unless it is truthy.
+pub fn match_form(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    let (expr, mut clauses) = match args.split_first() {
+        Some(split) => split,
+        None => return Err("match requires an expression and clauses".into()),
+    };
+    let mut patterns = Vec::new();
+    let mut bodies = Vec::new();
+    while let Some((pattern, rest)) = clauses.split_first() {
+        let (guard, result, rest) = match rest {
+            [MalValue::Atom(k), guard, result, rest @ ..] if k == ":when" => {
+                (Some(guard), result, rest)
+            }
+            [result, rest @ ..] => (None, result, rest),
+            [] => return Err("match: pattern without a result".into()),
+        };
+        patterns.push(pattern);
+        bodies.push((guard, result));
+        clauses = rest;
+    }
+    // Every pattern is compiled before anything is matched, so a malformed
+    // clause is reported even when an earlier one would have matched
+    let compiled = compiled(args, &patterns)?;
+
+    let value = eval(expr, Rc::clone(&env), ctx)?;
+    for ((_, pattern), (guard, result)) in compiled.iter().zip(bodies) {
+        let mut bindings = Vec::new();
+        if !matches(pattern, &value, &mut bindings) {
+            continue;
+        }
+        let clause_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
+            &env.borrow().get_bindings(),
+        )))));
+        for (name, bound) in bindings {
+            clause_env.borrow().set(name, bound);
+        }
+        if let Some(guard) = guard {
+            if !is_truthy(&eval(guard, Rc::clone(&clause_env), ctx)?) {
+                continue;
+            }
+        }
+        return eval(result, clause_env, ctx);
+    }
+    Err(format!("match: no clause matched {}", pr_str(&value, true)).into())
+}
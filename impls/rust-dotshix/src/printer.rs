@@ -1,5 +1,8 @@
+use crate::protocol::{extension, TO_STRING};
 use crate::Function;
 use crate::MalValue;
+use std::cell::RefCell;
+use std::rc::Rc;
 
 // Custom function to escape strings
 fn escape_string(s: &str) -> String {
@@ -17,6 +20,42 @@ fn escape_string(s: &str) -> String {
     escaped
 }
 
+thread_local! {
+    // Values whose to-string is running, so one that prints itself doesn't recurse
+    static PRINTING: RefCell<Vec<MalValue>> = const { RefCell::new(Vec::new()) };
+    // Atoms whose contents are being printed, so one that contains itself
+    // prints as (atom ...) where it recurs instead of without end
+    static ATOMS: RefCell<Vec<*const RefCell<MalValue>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn atom_string(cell: &Rc<RefCell<MalValue>>, print_readably: bool) -> String {
+    let ptr = Rc::as_ptr(cell);
+    if ATOMS.with(|atoms| atoms.borrow().contains(&ptr)) {
+        return "(atom ...)".to_string();
+    }
+    ATOMS.with(|atoms| atoms.borrow_mut().push(ptr));
+    let contents = pr_str(&cell.borrow(), print_readably);
+    ATOMS.with(|atoms| atoms.borrow_mut().pop());
+    format!("(atom {})", contents)
+}
+
+// A tagged value or host object whose type implements to-string prints as
+// whatever that returns; if it fails, or prints the value itself, the value
+// prints in its default form
+fn custom_string(node: &MalValue) -> Option<String> {
+    if PRINTING.with(|printing| printing.borrow().contains(node)) {
+        return None;
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(node.clone()));
+    let result = extension(TO_STRING, node);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    match result {
+        Ok(Some(MalValue::String(s))) => Some(s),
+        Ok(Some(other)) => Some(pr_str(&other, false)),
+        _ => None,
+    }
+}
+
 // Converts a MalValue to a String with optional readably formatting
 pub fn pr_str(node: &MalValue, print_readably: bool) -> String {
     match node {
@@ -30,6 +69,7 @@ pub fn pr_str(node: &MalValue, print_readably: bool) -> String {
         }
         MalValue::Symbol(s) => s.clone(),
         MalValue::Number(n) => n.to_string(),
+        MalValue::Float(f) => format_float(*f),
         MalValue::Bool(b) => b.to_string(),
         MalValue::Nil => "nil".to_string(),
         MalValue::Atom(a) => a.clone(),
@@ -49,14 +89,34 @@ pub fn pr_str(node: &MalValue, print_readably: bool) -> String {
                 .join(" ");
             format!("[{}]", contents)
         }
-        MalValue::Curly(r) => {
+        MalValue::Map(map) => {
+            let contents = map
+                .iter()
+                .map(|(k, v)| {
+                    let key = MalValue::from(k.clone());
+                    format!("{} {}", pr_str(&key, print_readably), pr_str(v, print_readably))
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            format!("{{{}}}", contents)
+        }
+        MalValue::Set(r) => {
             let contents = r
                 .iter()
                 .map(|v| pr_str(v, print_readably))
                 .collect::<Vec<String>>()
                 .join(" ");
-            format!("{{{}}}", contents)
+            format!("#{{{}}}", contents)
         }
+        MalValue::Tagged(tag, value) => custom_string(node)
+            .unwrap_or_else(|| format!("#{} {}", tag, pr_str(value, print_readably))),
+        MalValue::Resource(r) => {
+            let status = if r.is_open() { "" } else { " (closed)" };
+            format!("<#resource {}{}>", r.description(), status)
+        }
+        MalValue::Host(object) => custom_string(node)
+            .unwrap_or_else(|| format!("<#object {}>", object.type_name())),
+        MalValue::Ref(cell) => atom_string(cell, print_readably),
         MalValue::Comment(c) => c.clone(),
         MalValue::NonSpecialSeq(s) => s.clone(),
         MalValue::Mal(content) => content
@@ -66,10 +126,30 @@ pub fn pr_str(node: &MalValue, print_readably: bool) -> String {
             .join(" "),
         MalValue::BuiltinFunction(func) => match func {
             Function::Builtin(_) => "<#builtin function>".to_string(),
-            Function::SpecialForm(_) => "<#special form>".to_string(),
-            Function::UserDefined { .. } => "<#function>".to_string(),
+            Function::UserDefined(_) | Function::Partial(_) | Function::Composed(_) => {
+                "<#function>".to_string()
+            }
         },
+        MalValue::Bytes(b) => {
+            let contents = b
+                .iter()
+                .map(|byte| byte.to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+            format!("#bytes[{}]", contents)
+        }
         MalValue::EOI => "".to_string(),
     }
 }
 
+// Floats always print with a decimal point or exponent so they read back as
+// floats; the non-finite values use Clojure's ##Inf, ##-Inf, and ##NaN spellings
+fn format_float(f: f64) -> String {
+    if f.is_nan() {
+        "##NaN".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 { "##Inf" } else { "##-Inf" }.to_string()
+    } else {
+        format!("{:?}", f)
+    }
+}
@@ -0,0 +1,191 @@
+use crate::core::{call, is_callable};
+use crate::error::MalError;
+use crate::interpreter::{require, Permission};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::resource::{process_resource, with_process};
+use crate::MalValue;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pipe {
+    Stdout,
+    Stderr,
+}
+
+// A spawned child. Reader threads forward its output line by line over a
+// channel; the mal callbacks run on the interpreter's thread, inside
+// process/wait, because mal values can't cross threads.
+pub struct Process {
+    child: Child,
+    lines: Option<Receiver<(Pipe, String)>>,
+    on_stdout: Option<MalValue>,
+    on_stderr: Option<MalValue>,
+    exit_code: Option<MalValue>,
+}
+
+impl Process {
+    // Stops a child that is still running; used when its resource is closed
+    // and when a callback fails
+    pub fn kill(&mut self) {
+        if self.exit_code.is_none() {
+            let _ = self.child.kill();
+            let _ = self.reap();
+        }
+    }
+
+    // Waits for the child to exit, the first time, and returns its exit code,
+    // or nil if it was killed by a signal
+    fn reap(&mut self) -> Result<MalValue> {
+        if let Some(code) = &self.exit_code {
+            return Ok(code.clone());
+        }
+        let status = self.child.wait().map_err(|e| e.to_string())?;
+        let code = status
+            .code()
+            .map_or(MalValue::Nil, |code| MalValue::Number(code as i64));
+        self.exit_code = Some(code.clone());
+        Ok(code)
+    }
+}
+
+fn forward_lines(pipe: Pipe, source: impl Read + Send + 'static, sender: Sender<(Pipe, String)>) {
+    thread::spawn(move || {
+        for line in BufReader::new(source).lines() {
+            let Ok(line) = line else { break };
+            if sender.send((pipe, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn command_for(cmd: &MalValue) -> Result<Command> {
+    match cmd {
+        // A string goes through the shell, so pipes and globs work
+        MalValue::String(line) => {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(line);
+            Ok(command)
+        }
+        MalValue::Square(parts) | MalValue::Round(parts) => {
+            let parts = parts
+                .iter()
+                .map(|part| match part {
+                    MalValue::String(s) => Ok(s.clone()),
+                    _ => Err("process/spawn: command arguments must be strings".into()),
+                })
+                .collect::<Result<Vec<String>>>()?;
+            let (program, rest) = parts
+                .split_first()
+                .ok_or("process/spawn: the command vector is empty")?;
+            let mut command = Command::new(program);
+            command.args(rest);
+            Ok(command)
+        }
+        _ => Err(format!(
+            "process/spawn: {} is not a command string or vector",
+            pr_str(cmd, true)
+        )
+        .into()),
+    }
+}
+
+fn callback_option(options: Option<&MalMap>, key: &str) -> Result<Option<MalValue>> {
+    match options.and_then(|options| options.get(&MalKey::Keyword(key.to_string()))) {
+        None | Some(MalValue::Nil) => Ok(None),
+        Some(f) if is_callable(f) => Ok(Some(f.clone())),
+        Some(_) => Err(format!("process/spawn: {} must be a function", key).into()),
+    }
+}
+
+// (process/spawn cmd) or (process/spawn cmd {:on-stdout f :on-stderr g}).
+// A stream without a callback is passed straight through to the terminal.
+pub fn process_spawn(args: &[MalValue]) -> Result<MalValue> {
+    require(Permission::Shell, "process/spawn")?;
+    let (cmd, options) = match args {
+        [cmd] | [cmd, MalValue::Nil] => (cmd, None),
+        [cmd, MalValue::Map(options)] => (cmd, Some(options)),
+        _ => return Err("process/spawn requires a command and an optional options map".into()),
+    };
+    let on_stdout = callback_option(options, ":on-stdout")?;
+    let on_stderr = callback_option(options, ":on-stderr")?;
+
+    let mut command = command_for(cmd)?;
+    let piped = |callback: &Option<MalValue>| match callback {
+        Some(_) => Stdio::piped(),
+        None => Stdio::inherit(),
+    };
+    command.stdout(piped(&on_stdout)).stderr(piped(&on_stderr));
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("process/spawn: {}", e))?;
+
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(Pipe::Stdout, stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(Pipe::Stderr, stderr, sender);
+    }
+
+    let description = format!("process:{}", child.id());
+    Ok(process_resource(
+        description,
+        Process {
+            child,
+            lines: Some(receiver),
+            on_stdout,
+            on_stderr,
+            exit_code: None,
+        },
+    ))
+}
+
+// (process/wait p) delivers the child's output to the callbacks as it arrives,
+// then returns its exit code, or nil if it was killed by a signal. A callback
+// that fails kills the child, so it doesn't go on running unwatched.
+pub fn process_wait(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("process/wait requires exactly one process".into());
+    }
+    // The receiver is taken out so callbacks run without the resource borrowed
+    let (lines, on_stdout, on_stderr) = with_process(args, "process/wait", |process| {
+        Ok((
+            process.lines.take(),
+            process.on_stdout.clone(),
+            process.on_stderr.clone(),
+        ))
+    })?;
+
+    if let Some(lines) = lines {
+        for (pipe, line) in lines {
+            let callback = match pipe {
+                Pipe::Stdout => &on_stdout,
+                Pipe::Stderr => &on_stderr,
+            };
+            if let Some(callback) = callback {
+                if let Err(e) = call(callback, &[MalValue::String(line)]) {
+                    // The callback may have closed the process itself
+                    let _ = with_process(args, "process/wait", |process| {
+                        process.kill();
+                        Ok(())
+                    });
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    with_process(args, "process/wait", |process| {
+        process
+            .reap()
+            .map_err(|e| format!("process/wait: {}", e).into())
+    })
+}
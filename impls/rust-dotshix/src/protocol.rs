@@ -0,0 +1,224 @@
+use crate::core::{bound_builtin, builtin_named, call, check_redefinition, is_callable};
+use crate::error::MalError;
+use crate::interpreter::{extend_type, notify_def, type_method, EvalCtx};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Protocols the core builtins consult for tagged values and host objects:
+// printing calls to-string, count calls count-impl, and seq, first, rest, map,
+// and the other sequence functions call seq-impl. to-string replaces the
+// builtin of that name, which still handles every type with no implementation.
+pub const TO_STRING: &str = "to-string";
+pub const COUNT_IMPL: &str = "count-impl";
+pub const SEQ_IMPL: &str = "seq-impl";
+const CORE_PROTOCOLS: &[(&str, &[&str])] = &[
+    ("Printable", &[TO_STRING]),
+    ("Counted", &[COUNT_IMPL]),
+    ("Seqable", &[SEQ_IMPL]),
+];
+
+// Implementations registered for this type are used when no other type matches
+const DEFAULT_TYPE: &str = ":default";
+
+// The type a protocol method dispatches on: the tag of a tagged value or host
+// object, or the name of a built-in type
+pub fn dispatch_type(value: &MalValue) -> &str {
+    match value {
+        MalValue::Tagged(tag, _) => tag,
+        MalValue::Host(object) => object.type_name(),
+        MalValue::Nil => "nil",
+        MalValue::Bool(_) => "boolean",
+        MalValue::Number(_) => "number",
+        MalValue::Float(_) => "float",
+        MalValue::String(_) => "string",
+        MalValue::Atom(_) => "keyword",
+        MalValue::Symbol(_) => "symbol",
+        MalValue::Round(_) => "list",
+        MalValue::Square(_) => "vector",
+        MalValue::Map(_) => "map",
+        MalValue::Set(_) => "set",
+        MalValue::Bytes(_) => "bytes",
+        MalValue::BuiltinFunction(_) => "function",
+        MalValue::Ref(_) => "atom",
+        MalValue::Resource(_) => "resource",
+        _ => "unknown",
+    }
+}
+
+fn protocol_value(name: &str, methods: &[String]) -> MalValue {
+    let methods = methods.iter().map(|m| MalValue::Symbol(m.clone())).collect();
+    MalValue::Tagged(
+        "protocol".to_string(),
+        Box::new(MalValue::Map(MalMap::from([
+            (MalKey::keyword("name"), MalValue::String(name.to_string())),
+            (MalKey::keyword("methods"), MalValue::Square(methods)),
+        ]))),
+    )
+}
+
+fn protocol_methods(protocol: &MalValue) -> Option<Vec<&str>> {
+    let MalValue::Tagged(tag, inner) = protocol else {
+        return None;
+    };
+    match (tag.as_str(), &**inner) {
+        ("protocol", MalValue::Map(entries)) => match entries.get(&MalKey::keyword("methods")) {
+            Some(MalValue::Square(methods)) => methods
+                .iter()
+                .map(|m| match m {
+                    MalValue::Symbol(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn method_value(method: &str, env: &Rc<RefCell<Env>>) -> MalValue {
+    bound_builtin(dispatch, MalValue::String(method.to_string()), env)
+}
+
+fn dispatch(args: &[MalValue]) -> Result<MalValue> {
+    let (method, args) = match args {
+        [MalValue::String(method), MalValue::Round(args)] => (method, args),
+        _ => return Err("protocol dispatch: malformed call".into()),
+    };
+    let Some(target) = args.first() else {
+        return Err(format!("{} requires at least one argument", method).into());
+    };
+    let type_name = dispatch_type(target);
+    match type_method(method, type_name).or_else(|| type_method(method, DEFAULT_TYPE)) {
+        Some(implementation) => call(&implementation, args),
+        // A core protocol method that took over a builtin's name
+        None => match builtin_named(method) {
+            Some(builtin) => builtin(args),
+            None => Err(format!("No implementation of {} for type {}", method, type_name).into()),
+        },
+    }
+}
+
+// Calls a core protocol method on a tagged value or host object whose type
+// implements it; None leaves the builtin to its usual behaviour
+pub fn extension(method: &str, value: &MalValue) -> Result<Option<MalValue>> {
+    if !matches!(value, MalValue::Tagged(..) | MalValue::Host(_)) {
+        return Ok(None);
+    }
+    match type_method(method, dispatch_type(value)) {
+        Some(implementation) => call(&implementation, std::slice::from_ref(value)).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn define(name: &str, methods: &[String], env: &Rc<RefCell<Env>>) -> MalValue {
+    let protocol = protocol_value(name, methods);
+    for method in methods {
+        let value = method_value(method, env);
+        env.borrow().set(method.clone(), value.clone());
+        notify_def(method, &value);
+    }
+    env.borrow().set(name.to_string(), protocol.clone());
+    notify_def(name, &protocol);
+    protocol
+}
+
+pub fn define_core_protocols(env: &Rc<RefCell<Env>>) {
+    for (name, methods) in CORE_PROTOCOLS {
+        let methods: Vec<String> = methods.iter().map(|m| m.to_string()).collect();
+        define(name, &methods, env);
+    }
+}
+
+// (defprotocol Shape (area [s]) (scale [s factor])) defines Shape and a function
+// for each method, which calls the implementation for its first argument's type
+pub fn defprotocol(args: &[MalValue], env: Rc<RefCell<Env>>, _ctx: &EvalCtx) -> Result<MalValue> {
+    let (name, signatures) = match args {
+        [MalValue::Symbol(name), signatures @ ..] if !signatures.is_empty() => (name, signatures),
+        _ => return Err("defprotocol requires a name and method signatures".into()),
+    };
+    let methods = signatures
+        .iter()
+        .map(|signature| match signature {
+            MalValue::Round(parts) => match parts.as_slice() {
+                [MalValue::Symbol(method), MalValue::Square(params), ..] if !params.is_empty() => {
+                    Ok(method.clone())
+                }
+                _ => Err(format!(
+                    "defprotocol: {} is not a method signature like (name [this ...])",
+                    pr_str(signature, true)
+                )
+                .into()),
+            },
+            _ => Err(format!(
+                "defprotocol: {} is not a method signature like (name [this ...])",
+                pr_str(signature, true)
+            )
+            .into()),
+        })
+        .collect::<Result<Vec<String>>>()?;
+    check_redefinition("defprotocol", name, &env)?;
+    for method in &methods {
+        check_redefinition("defprotocol", method, &env)?;
+    }
+    Ok(define(name, &methods, &env))
+}
+
+fn type_arg(value: &MalValue) -> Result<String> {
+    match value {
+        MalValue::String(name) | MalValue::Symbol(name) => Ok(name.clone()),
+        MalValue::Atom(k) if k == DEFAULT_TYPE => Ok(k.clone()),
+        _ => Err(format!(
+            "extend: {} is not a type name or :default",
+            pr_str(value, true)
+        )
+        .into()),
+    }
+}
+
+// (extend "point" Shape {:area (fn* [p] ...) :scale (fn* [p k] ...)}); the
+// type is a tag, a built-in type name like "vector", or :default
+pub fn extend(args: &[MalValue]) -> Result<MalValue> {
+    let (type_name, protocol, implementations) = match args {
+        [type_name, protocol, MalValue::Map(implementations)] => {
+            (type_arg(type_name)?, protocol, implementations)
+        }
+        _ => return Err("extend requires a type, a protocol, and a map of methods".into()),
+    };
+    let methods = protocol_methods(protocol)
+        .ok_or_else(|| format!("extend: {} is not a protocol", pr_str(protocol, true)))?;
+    for (key, implementation) in implementations {
+        let MalKey::Keyword(key) = key else {
+            return Err("extend: methods must be keyword and function pairs".into());
+        };
+        let method = &key[1..];
+        if !methods.contains(&method) {
+            return Err(format!("extend: protocol has no method {}", method).into());
+        }
+        if !is_callable(implementation) {
+            return Err(format!("extend: implementation of {} is not a function", method).into());
+        }
+        extend_type(method, &type_name, implementation.clone())?;
+    }
+    Ok(MalValue::Nil)
+}
+
+// (satisfies? Shape x) is true when x's type implements every method of Shape
+pub fn satisfies_question(args: &[MalValue]) -> Result<MalValue> {
+    let [protocol, value] = args else {
+        return Err("satisfies? requires a protocol and a value".into());
+    };
+    let methods = protocol_methods(protocol)
+        .ok_or_else(|| format!("satisfies?: {} is not a protocol", pr_str(protocol, true)))?;
+    let type_name = dispatch_type(value);
+    Ok(MalValue::Bool(
+        methods
+            .iter()
+            .all(|method| type_method(method, type_name).is_some()),
+    ))
+}
@@ -0,0 +1,106 @@
+use crate::error::MalError;
+use crate::eval;
+use crate::interpreter::EvalCtx;
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+fn symbol(name: &str) -> MalValue {
+    MalValue::Symbol(name.to_string())
+}
+
+// Matches (name x) and returns x
+fn unwrap_call<'a>(ast: &'a MalValue, name: &str) -> Option<&'a MalValue> {
+    match ast {
+        MalValue::Round(items) if items.len() == 2 && items[0] == symbol(name) => Some(&items[1]),
+        _ => None,
+    }
+}
+
+// Rewrites the body of a quasiquote into plain code that builds the same
+// structure: unquoted forms are left to evaluate, everything else is quoted
+// or rebuilt with cons. The result contains no quasiquote forms, so it can be
+// inspected, printed, or handed straight to eval.
+pub fn quasiquote_expand(ast: &MalValue) -> MalValue {
+    expand(ast, 0)
+}
+
+// depth counts the quasiquotes nested inside the one being expanded. An
+// unquote or splice-unquote only evaluates at depth 0; deeper ones are rebuilt
+// as data, with their contents expanded one level up, so `(a `(b ~(c ~x)))
+// fills in x alone.
+fn expand(ast: &MalValue, depth: usize) -> MalValue {
+    if let Some(inner) = unwrap_call(ast, "unquote") {
+        if depth == 0 {
+            return inner.clone();
+        }
+        return rebuild("unquote", inner, depth - 1);
+    }
+    if let Some(inner) = unwrap_call(ast, "quasiquote") {
+        return rebuild("quasiquote", inner, depth + 1);
+    }
+
+    match ast {
+        MalValue::Round(items) => expand_sequence(items, depth),
+        MalValue::Square(items) => {
+            MalValue::Round(vec![symbol("vec"), expand_sequence(items, depth)])
+        }
+        MalValue::Symbol(_) | MalValue::Map(_) | MalValue::Set(_) => {
+            MalValue::Round(vec![symbol("quote"), ast.clone()])
+        }
+        _ => ast.clone(),
+    }
+}
+
+// Code for the form (name inner), with inner expanded at depth
+fn rebuild(name: &str, inner: &MalValue, depth: usize) -> MalValue {
+    let empty = MalValue::Round(Vec::new());
+    let inner = MalValue::Round(vec![symbol("cons"), expand(inner, depth), empty]);
+    let quoted = MalValue::Round(vec![symbol("quote"), symbol(name)]);
+    MalValue::Round(vec![symbol("cons"), quoted, inner])
+}
+
+// Folds the elements from the right into (cons a (cons b ())) so each element
+// is expanded independently; a spliced element joins with concat instead
+fn expand_sequence(items: &[MalValue], depth: usize) -> MalValue {
+    items
+        .iter()
+        .rev()
+        .fold(MalValue::Round(Vec::new()), |acc, item| {
+            match unwrap_call(item, "splice-unquote") {
+                Some(spliced) if depth == 0 => {
+                    MalValue::Round(vec![symbol("concat"), spliced.clone(), acc])
+                }
+                Some(spliced) => MalValue::Round(vec![
+                    symbol("cons"),
+                    rebuild("splice-unquote", spliced, depth - 1),
+                    acc,
+                ]),
+                None => MalValue::Round(vec![symbol("cons"), expand(item, depth), acc]),
+            }
+        })
+}
+
+// (quasiquote form) is handled in eval, which evaluates the expansion in tail
+// position; this is the same for callers outside it
+pub fn quasiquote(args: &[MalValue], env: Rc<RefCell<Env>>, ctx: &EvalCtx) -> Result<MalValue> {
+    match args {
+        [form] => eval(&quasiquote_expand(form), env, ctx),
+        _ => Err("quasiquote requires exactly one argument".into()),
+    }
+}
+
+// (quasiquoteexpand form) returns the code quasiquote would evaluate
+pub fn quasiquoteexpand(
+    args: &[MalValue],
+    _env: Rc<RefCell<Env>>,
+    _ctx: &EvalCtx,
+) -> Result<MalValue> {
+    match args {
+        [form] => Ok(quasiquote_expand(form)),
+        _ => Err("quasiquoteexpand requires exactly one argument".into()),
+    }
+}
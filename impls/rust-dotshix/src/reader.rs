@@ -1,39 +1,79 @@
 use crate::env::Function;
+use crate::host::HostObject;
+use crate::interpreter::{current_namespace, reader_limits};
+use crate::printer::pr_str;
+use crate::resource::Resource;
 use log::debug;
-use pest::error::{Error, ErrorVariant};
+use pest::error::{Error, ErrorVariant, InputLocation, LineColLocation};
 use pest::iterators::Pair;
-use pest::Parser;
+use pest::{Parser, Position, RuleType};
 use pest_derive::Parser;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
 
 #[derive(Parser)]
 #[grammar = "mal.pest"]
 pub struct MalParser;
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone)]
 pub enum MalValue {
     String(String),        // Represents a LISP string, e.g., "hello"
     Symbol(String),        // Represents a LISP symbol, e.g., +, some-function
     Number(i64),           // Represents a LISP number, e.g., 123
+    Float(f64),            // Represents a floating point number, e.g., 1.5
     Bool(bool),            // Represents a LISP boolean, e.g., true or false
     Nil,                   // Represents LISP nil
     Round(Vec<MalValue>),  // Represents a LISP list, e.g., (1 2 3)
     Square(Vec<MalValue>), // Represents a LISP list, e.g., [1 2 3]
-    Curly(Vec<MalValue>),  // Represents a LISP list, e.g., [1 2 3]
+    Map(MalMap),           // Represents a hash-map, e.g., {:a 1}
     Mal(Vec<MalValue>),    // Represents a LISP S-expression, e.g., (+ 1 2)
     Comment(String),       // Represents a LISP comment, e.g., ; this is a comment
     NonSpecialSeq(String), // Represents a sequence of characters that are not special symbols, e.g., abc123
     Atom(String), // Represents a LISP atom, e.g., a single, indivisible unit like a variable name or keyword
     BuiltinFunction(Function),
+    Bytes(Vec<u8>),        // Represents a byte array, e.g., the contents of a binary file
+    Set(Vec<MalValue>),    // Represents a set of distinct values, e.g., #{1 2 3}
+    Tagged(String, Box<MalValue>), // Represents a tagged literal, e.g., #inst "2024-01-01"
+    Resource(Rc<Resource>), // Represents an open file handle
+    Host(Rc<HostObject>), // Represents an opaque Rust value, e.g., a compiled regex
+    Ref(Rc<RefCell<MalValue>>), // Represents a mal atom, e.g., (atom 1); Atom is taken by keywords
     // Other(String),         // Represents any other token not specifically categorized, e.g., +
     EOI, // Represents the end of input
 }
 
+// nil, booleans, and numbers live inline in the enum rather than behind a
+// pointer, so the shared copies of nil, true, and false are these constants,
+// which compile to the bare variant wherever they are used; an integer of any
+// size is built just as cheaply. Eval and the predicates and comparisons it
+// calls most return them, so none of those results allocates.
+impl MalValue {
+    pub const NIL: MalValue = MalValue::Nil;
+    pub const TRUE: MalValue = MalValue::Bool(true);
+    pub const FALSE: MalValue = MalValue::Bool(false);
+
+    pub const fn bool(value: bool) -> MalValue {
+        if value {
+            MalValue::TRUE
+        } else {
+            MalValue::FALSE
+        }
+    }
+}
+
+// The cost that is left is moving a value as large as the biggest variant, so
+// keep the enum from growing unnoticed
+const _: () = assert!(std::mem::size_of::<MalValue>() <= 32);
+
 impl PartialEq for MalValue {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (MalValue::String(s1), MalValue::String(s2)) => s1 == s2,
             (MalValue::Symbol(s1), MalValue::Symbol(s2)) => s1 == s2,
             (MalValue::Number(n1), MalValue::Number(n2)) => n1 == n2,
+            // Integers and floats are distinct types, so (= 1 1.0) is false
+            (MalValue::Float(f1), MalValue::Float(f2)) => f1 == f2,
             (MalValue::Bool(b1), MalValue::Bool(b2)) => b1 == b2,
             (MalValue::Nil, MalValue::Nil) => true,
             // Consider Round and Square equal if their contents are equal
@@ -41,20 +81,124 @@ impl PartialEq for MalValue {
             (MalValue::Square(v1), MalValue::Square(v2)) => v1 == v2,
             (MalValue::Round(v1), MalValue::Square(v2)) => v1 == v2,
             (MalValue::Square(v1), MalValue::Round(v2)) => v1 == v2,
-            (MalValue::Curly(v1), MalValue::Curly(v2)) => v1 == v2,
+            (MalValue::Map(m1), MalValue::Map(m2)) => m1 == m2,
             //(MalValue::Mal(v1), MalValue::Mal(v2)) => v1 == v2,
             //(MalValue::Comment(c1), MalValue::Comment(c2)) => c1 == c2,
             //(MalValue::NonSpecialSeq(s1), MalValue::NonSpecialSeq(s2)) => s1 == s2,
             (MalValue::Atom(a1), MalValue::Atom(a2)) => a1 == a2,
             // Compare function pointers for equality
             (MalValue::BuiltinFunction(f1), MalValue::BuiltinFunction(f2)) => f1 == f2,
+            (MalValue::Bytes(b1), MalValue::Bytes(b2)) => b1 == b2,
+            // Sets are equal if they hold the same elements, regardless of order
+            (MalValue::Set(s1), MalValue::Set(s2)) => {
+                s1.len() == s2.len() && s1.iter().all(|v| s2.contains(v))
+            }
+            (MalValue::Tagged(t1, v1), MalValue::Tagged(t2, v2)) => t1 == t2 && v1 == v2,
+            // Resources are only equal to themselves
+            (MalValue::Resource(r1), MalValue::Resource(r2)) => Rc::ptr_eq(r1, r2),
+            (MalValue::Host(h1), MalValue::Host(h2)) => Rc::ptr_eq(h1, h2),
+            // Atoms are mutable cells, so two atoms are equal only if they are the same one
+            (MalValue::Ref(a1), MalValue::Ref(a2)) => Rc::ptr_eq(a1, a2),
             (MalValue::EOI, MalValue::EOI) => true,
             _ => false, // Default case for non-matching variants
         }
     }
 }
 
-pub fn format_pest_error(error: Error<Rule>) -> String {
+// The subset of values usable as map keys and set members. Converting from a
+// MalValue fails for anything without stable equality, like functions or lists.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MalKey {
+    String(String),
+    Keyword(String),
+    Number(i64),
+    Symbol(String),
+    Bool(bool),
+    Nil,
+}
+
+impl TryFrom<&MalValue> for MalKey {
+    type Error = String;
+
+    fn try_from(value: &MalValue) -> Result<Self, Self::Error> {
+        match value {
+            MalValue::String(s) => Ok(MalKey::String(s.clone())),
+            MalValue::Atom(k) => Ok(MalKey::Keyword(k.clone())),
+            MalValue::Number(n) => Ok(MalKey::Number(*n)),
+            MalValue::Symbol(s) => Ok(MalKey::Symbol(s.clone())),
+            MalValue::Bool(b) => Ok(MalKey::Bool(*b)),
+            MalValue::Nil => Ok(MalKey::Nil),
+            other => Err(format!(
+                "{} cannot be used as a map key or set member",
+                pr_str(other, true)
+            )),
+        }
+    }
+}
+
+impl From<MalKey> for MalValue {
+    fn from(key: MalKey) -> Self {
+        match key {
+            MalKey::String(s) => MalValue::String(s),
+            MalKey::Keyword(k) => MalValue::Atom(k),
+            MalKey::Number(n) => MalValue::Number(n),
+            MalKey::Symbol(s) => MalValue::Symbol(s),
+            MalKey::Bool(b) => MalValue::Bool(b),
+            MalKey::Nil => MalValue::Nil,
+        }
+    }
+}
+
+impl MalKey {
+    // The key :name
+    pub fn keyword(name: &str) -> MalKey {
+        MalKey::Keyword(format!(":{}", name))
+    }
+}
+
+// Map entries are kept sorted by key, so a map always prints the same way
+pub type MalMap = BTreeMap<MalKey, MalValue>;
+
+// Builds a map from alternating keys and values; a repeated key keeps the last value
+pub fn map_from_entries(items: &[MalValue]) -> Result<MalMap, String> {
+    if !items.len().is_multiple_of(2) {
+        return Err("Map literal must contain an even number of forms".to_string());
+    }
+    items
+        .chunks(2)
+        .map(|entry| Ok((MalKey::try_from(&entry[0])?, entry[1].clone())))
+        .collect()
+}
+
+// Looks up key in map; a value that can't be a key is never found
+pub fn map_get<'a>(map: &'a MalMap, key: &MalValue) -> Option<&'a MalValue> {
+    MalKey::try_from(key).ok().and_then(|key| map.get(&key))
+}
+
+impl MalValue {
+    // Iterates over the elements of any sequential value: list and vector items,
+    // set members, map entries as [k v] vectors, one-character strings, and bytes
+    // as numbers. nil is an empty sequence; non-sequences return None.
+    pub fn iter_seq(&self) -> Option<Box<dyn Iterator<Item = MalValue> + '_>> {
+        match self {
+            MalValue::Round(items) | MalValue::Square(items) | MalValue::Set(items) => {
+                Some(Box::new(items.iter().cloned()))
+            }
+            MalValue::Map(map) => Some(Box::new(
+                map.iter()
+                    .map(|(key, value)| MalValue::Square(vec![key.clone().into(), value.clone()])),
+            )),
+            MalValue::String(s) => Some(Box::new(
+                s.chars().map(|c| MalValue::String(c.to_string())),
+            )),
+            MalValue::Bytes(b) => Some(Box::new(b.iter().map(|byte| MalValue::Number(*byte as i64)))),
+            MalValue::Nil => Some(Box::new(std::iter::empty())),
+            _ => None,
+        }
+    }
+}
+
+pub fn format_pest_error<R: RuleType>(error: Error<R>) -> String {
     match error.variant {
         ErrorVariant::ParsingError {
             positives,
@@ -79,23 +223,384 @@ pub fn format_pest_error(error: Error<Rule>) -> String {
     }
 }
 
-pub fn parse_input(input: &str) -> Result<Vec<MalValue>, Box<Error<Rule>>> {
+// Grammar failures and malformed tokens alike surface as pest errors pointing at the input
+pub type ReaderError = Box<Error<Rule>>;
+
+// The part of the input a reader error points at, for reports that carry positions
+pub fn error_span(error: &Error<Rule>) -> Span {
+    let (start, end) = match error.location {
+        InputLocation::Pos(pos) => (pos, pos),
+        InputLocation::Span(span) => span,
+    };
+    let ((line, column), end_line) = match error.line_col {
+        LineColLocation::Pos(pos) => (pos, pos.0),
+        LineColLocation::Span(start, end) => (start, end.0),
+    };
+    Span {
+        start,
+        end,
+        line,
+        column,
+        end_line,
+    }
+}
+
+fn reader_error(pair: &Pair<Rule>, message: String) -> ReaderError {
+    Box::new(Error::new_from_span(
+        ErrorVariant::CustomError { message },
+        pair.as_span(),
+    ))
+}
+
+// The single child of a wrapper rule such as quote or deref
+fn only_child<'i>(pair: Pair<'i, Rule>) -> Result<Pair<'i, Rule>, ReaderError> {
+    let rule = pair.as_rule();
+    pair.clone()
+        .into_inner()
+        .next()
+        .ok_or_else(|| reader_error(&pair, format!("Expected a form after {:?}", rule)))
+}
+
+// Bounds on what one call to the reader accepts, so untrusted input can't
+// exhaust memory or, since pest and build_ast recurse once per level of
+// nesting, overflow the stack. Input over a limit is rejected before parsing
+// with an ordinary reader error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaderLimits {
+    pub max_input_bytes: usize,
+    pub max_nesting: usize,
+    // Bytes in one string literal, including escapes
+    pub max_string_length: usize,
+    // Every form counts, nested or not: (a "b") is three
+    pub max_forms: usize,
+}
+
+// The deepest nesting any configuration may allow: the reader, eval, and the
+// printer all recurse per level, and past this a debug build overflows even
+// the main thread's 8 MiB stack
+pub const MAX_NESTING: usize = 1024;
+
+impl Default for ReaderLimits {
+    fn default() -> Self {
+        ReaderLimits {
+            max_input_bytes: 64 * 1024 * 1024,
+            max_nesting: 256,
+            max_string_length: 16 * 1024 * 1024,
+            max_forms: 4 * 1024 * 1024,
+        }
+    }
+}
+
+fn limit_error(input: &str, pos: usize, message: String) -> ReaderError {
+    let position = Position::new(input, pos).unwrap_or_else(|| Position::from_start(input));
+    Box::new(Error::new_from_pos(ErrorVariant::CustomError { message }, position))
+}
+
+// Scans for forms outside comments. Reader macros such as 'x nest too,
+// counting one level each for the form that follows them.
+fn check_limits(input: &str) -> Result<(), ReaderError> {
+    let limits = reader_limits();
+    if input.len() > limits.max_input_bytes {
+        let message = format!("Input is longer than {} bytes", limits.max_input_bytes);
+        return Err(limit_error(input, 0, message));
+    }
+    // The levels each open bracket added, its own and its prefixes'
+    let mut open: Vec<usize> = Vec::new();
+    let (mut depth, mut prefixes, mut forms) = (0, 0, 0);
+    // Whether the previous character continued a symbol, number, or keyword
+    let mut in_token = false;
+    let mut chars = input.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        // Each bracketed form, string, comment, reader macro, and token is a form
+        let starts_form = matches!(c, '"' | ';' | '(' | '[' | '{' | '\'' | '`' | '~' | '@' | '^');
+        let token_char = !starts_form && !matches!(c, ')' | ']' | '}' | ',') && !c.is_whitespace();
+        if starts_form || (token_char && !in_token) {
+            forms += 1;
+        }
+        in_token = token_char;
+        match c {
+            '"' => {
+                let mut end = input.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = i;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                if end - pos - 1 > limits.max_string_length {
+                    let message =
+                        format!("String is longer than {} bytes", limits.max_string_length);
+                    return Err(limit_error(input, pos, message));
+                }
+                prefixes = 0;
+            }
+            ';' => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            '(' | '[' | '{' => {
+                open.push(prefixes + 1);
+                depth += prefixes + 1;
+                prefixes = 0;
+            }
+            ')' | ']' | '}' => {
+                depth -= open.pop().unwrap_or(0);
+                prefixes = 0;
+            }
+            '\'' | '`' | '~' | '@' | '^' => prefixes += 1,
+            c if c.is_whitespace() || c == ',' => {}
+            _ => prefixes = 0,
+        }
+        if depth + prefixes > limits.max_nesting {
+            let message = format!("Input nests more than {} levels deep", limits.max_nesting);
+            return Err(limit_error(input, pos, message));
+        }
+        if forms > limits.max_forms {
+            let message = format!("Input has more than {} forms", limits.max_forms);
+            return Err(limit_error(input, pos, message));
+        }
+    }
+    Ok(())
+}
+
+pub fn parse_input(input: &str) -> Result<Vec<MalValue>, ReaderError> {
+    check_limits(input)?;
     let pairs = MalParser::parse(Rule::mal, input).map_err(Box::new)?;
     let mut ast = Vec::new();
 
     for pair in pairs {
-        let node = build_ast(pair);
+        let node = build_ast(pair)?;
         ast.push(node);
     }
 
     Ok(ast)
 }
 
-fn build_ast(pair: Pair<Rule>) -> MalValue {
+// Where a form came from: byte offsets into the input plus the 1-based line and
+// column of its first character and the line it ends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+}
+
+// A parsed form with its span. Collections and reader macros such as 'x keep
+// their sub-forms in children, in source order; value is built from the same
+// forms parse_input would have produced for the text. Comment fields are only
+// filled in by parse_input_with_comments.
+#[derive(Debug, Clone)]
+pub struct Spanned {
+    pub value: MalValue,
+    pub span: Span,
+    pub children: Vec<Spanned>,
+    pub leading_comments: Vec<String>,
+    pub trailing_comments: Vec<String>,
+}
+
+// Like parse_input, but keeps source positions for tooling (formatter, editor
+// integration, error reporting). Eval never sees these.
+pub fn parse_input_spanned(input: &str) -> Result<Vec<Spanned>, ReaderError> {
+    parse_spanned(input, false)
+}
+
+// Like parse_input_spanned, but comments are attached to forms instead of
+// appearing as Comment values: a comment on the line where the previous form
+// ends trails that form, and any other comment leads the next form. Comments
+// with no following form in their file or collection stay as Comment entries.
+pub fn parse_input_with_comments(input: &str) -> Result<Vec<Spanned>, ReaderError> {
+    parse_spanned(input, true)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Open,        // ( [ {
+    Close,       // ) ] }
+    ReaderMacro, // ' ` ~ ~@ @ ^
+    String,
+    Number, // integers and floats
+    Keyword,
+    Symbol,
+    Boolean,
+    Nil,
+    Comment,
+    Invalid, // malformed numbers, ratios, unterminated strings
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+// Splits input into tokens, classified by the same rules the reader uses, for
+// syntax highlighting and editor tooling. Unlike parse_input it never fails:
+// unbalanced input just has unmatched Open or Close tokens, and text the reader
+// would reject comes back as Invalid tokens. Whitespace and commas are skipped.
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let Ok(mut pairs) = MalParser::parse(Rule::tokens, input) else {
+        // Unreachable: every character is at least an invalid token
+        return Vec::new();
+    };
+    let tokens = pairs.next().map(|pair| pair.into_inner());
+    tokens
+        .into_iter()
+        .flatten()
+        .filter_map(|pair| {
+            let kind = match pair.as_rule() {
+                Rule::open => TokenKind::Open,
+                Rule::close => TokenKind::Close,
+                Rule::reader_macro => TokenKind::ReaderMacro,
+                Rule::STRING => TokenKind::String,
+                Rule::number | Rule::float | Rule::symbolic_float => TokenKind::Number,
+                Rule::atom => TokenKind::Keyword,
+                Rule::symbol => TokenKind::Symbol,
+                Rule::boolean => TokenKind::Boolean,
+                Rule::nil => TokenKind::Nil,
+                Rule::COMMENT => TokenKind::Comment,
+                Rule::EOI => return None,
+                _ => TokenKind::Invalid,
+            };
+            Some(Token {
+                kind,
+                span: span_of(&pair),
+            })
+        })
+        .collect()
+}
+
+fn parse_spanned(input: &str, attach: bool) -> Result<Vec<Spanned>, ReaderError> {
+    let forms = top_level_pairs(input)?
+        .map(|p| build_spanned(p, attach))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(if attach { attach_comments(forms) } else { forms })
+}
+
+fn top_level_pairs(input: &str) -> Result<impl Iterator<Item = Pair<'_, Rule>>, ReaderError> {
+    check_limits(input)?;
+    Ok(MalParser::parse(Rule::mal, input)
+        .map_err(Box::new)?
+        .flat_map(|mal| mal.into_inner())
+        .filter(|p| p.as_rule() != Rule::EOI))
+}
+
+// The top-level forms of input, each built only when it is taken, so that a
+// form built after an earlier one is evaluated sees its effects: ::key resolves
+// against the namespace an earlier (in-ns ...) switched to. Syntax errors are
+// still found up front; invalid literals (an odd map, an out-of-range number)
+// only when their form is reached.
+pub fn read_forms(
+    input: &str,
+) -> Result<impl Iterator<Item = Result<MalValue, ReaderError>> + '_, ReaderError> {
+    Ok(top_level_pairs(input)?.map(build_ast))
+}
+
+// Like read_forms, keeping source positions as parse_input_spanned does
+pub fn read_forms_spanned(
+    input: &str,
+) -> Result<impl Iterator<Item = Result<Spanned, ReaderError>> + '_, ReaderError> {
+    Ok(top_level_pairs(input)?.map(|p| build_spanned(p, false)))
+}
+
+fn span_of(pair: &Pair<Rule>) -> Span {
+    let pest_span = pair.as_span();
+    let (line, column) = pest_span.start_pos().line_col();
+    Span {
+        start: pest_span.start(),
+        end: pest_span.end(),
+        line,
+        column,
+        end_line: pest_span.end_pos().line_col().0,
+    }
+}
+
+fn build_spanned(pair: Pair<Rule>, attach: bool) -> Result<Spanned, ReaderError> {
+    let span = span_of(&pair);
+
+    let compound = matches!(
+        pair.as_rule(),
+        Rule::round
+            | Rule::square
+            | Rule::curly
+            | Rule::quote
+            | Rule::quasiquote
+            | Rule::unquote
+            | Rule::splicing_unquote
+            | Rule::deref
+            | Rule::metadata
+    );
+    let mut children = Vec::new();
+    if compound {
+        children = pair
+            .clone()
+            .into_inner()
+            .map(|p| build_spanned(p, attach))
+            .collect::<Result<Vec<_>, _>>()?;
+        if attach {
+            children = attach_comments(children);
+        }
+    }
+
+    // Collections are rebuilt from their children rather than parsed a second time
+    let values = || children.iter().map(|child| child.value.clone()).collect();
+    let value = match pair.as_rule() {
+        Rule::round => MalValue::Round(values()),
+        Rule::square => MalValue::Square(values()),
+        Rule::curly => MalValue::Map(
+            map_from_entries(&values()).map_err(|message| reader_error(&pair, message))?,
+        ),
+        _ => build_ast(pair)?,
+    };
+
+    Ok(Spanned {
+        value,
+        span,
+        children,
+        leading_comments: Vec::new(),
+        trailing_comments: Vec::new(),
+    })
+}
+
+fn attach_comments(forms: Vec<Spanned>) -> Vec<Spanned> {
+    let mut result: Vec<Spanned> = Vec::new();
+    let mut pending: Vec<Spanned> = Vec::new();
+
+    for mut form in forms {
+        let MalValue::Comment(text) = &form.value else {
+            form.leading_comments = pending
+                .drain(..)
+                .filter_map(|comment| match comment.value {
+                    MalValue::Comment(text) => Some(text),
+                    _ => None,
+                })
+                .collect();
+            result.push(form);
+            continue;
+        };
+        match result.last_mut() {
+            Some(previous) if pending.is_empty() && previous.span.end_line == form.span.line => {
+                previous.trailing_comments.push(text.clone());
+            }
+            _ => pending.push(form),
+        }
+    }
+
+    result.extend(pending);
+    result
+}
+
+fn build_ast(pair: Pair<Rule>) -> Result<MalValue, ReaderError> {
     debug!("Processing rule: {:?}", pair.as_rule());
     //debug!("Pair content: {:?}", pair.as_str());
 
-    match pair.as_rule() {
+    let value = match pair.as_rule() {
         Rule::STRING => {
             let content_with_quotes = pair.as_str();
             // Remove the surrounding quotes
@@ -112,11 +617,38 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::number => {
-            let content = pair.as_str().parse::<i64>().unwrap();
+            let content = pair.as_str().parse::<i64>().map_err(|_| {
+                reader_error(&pair, format!("Number out of range: {}", pair.as_str()))
+            })?;
             debug!("NUMBER content: {:?}", content);
             MalValue::Number(content)
         }
 
+        Rule::float => {
+            let content = pair.as_str().parse::<f64>().map_err(|_| {
+                reader_error(&pair, format!("Invalid float: {}", pair.as_str()))
+            })?;
+            debug!("FLOAT content: {:?}", content);
+            MalValue::Float(content)
+        }
+
+        Rule::symbolic_float => MalValue::Float(match pair.as_str() {
+            "##Inf" => f64::INFINITY,
+            "##-Inf" => f64::NEG_INFINITY,
+            _ => f64::NAN,
+        }),
+
+        Rule::ratio => {
+            return Err(reader_error(
+                &pair,
+                format!("{}: ratio literals are unsupported, use (/ a b)", pair.as_str()),
+            ))
+        }
+
+        Rule::malformed_number => {
+            return Err(reader_error(&pair, format!("Invalid number: {}", pair.as_str())))
+        }
+
         Rule::boolean => {
             let content = pair.as_str() == "true";
             debug!("BOOLEAN content: {:?}", content);
@@ -124,19 +656,20 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::round => {
-            let content = pair.into_inner().map(build_ast).collect::<Vec<_>>();
+            let content = pair.into_inner().map(build_ast).collect::<Result<Vec<_>, _>>()?;
             debug!("ROUND content: {:?}", content);
             MalValue::Round(content)
         }
         Rule::square => {
-            let content = pair.into_inner().map(build_ast).collect::<Vec<_>>();
+            let content = pair.into_inner().map(build_ast).collect::<Result<Vec<_>, _>>()?;
             debug!("SQUARE content: {:?}", content);
             MalValue::Square(content)
         }
         Rule::curly => {
-            let content = pair.into_inner().map(build_ast).collect::<Vec<_>>();
+            let content = pair.clone().into_inner().map(build_ast).collect::<Result<Vec<_>, _>>()?;
             debug!("CURLY content: {:?}", content);
-            MalValue::Curly(content)
+            let map = map_from_entries(&content).map_err(|message| reader_error(&pair, message))?;
+            MalValue::Map(map)
         }
 
         Rule::COMMENT => {
@@ -146,15 +679,15 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::quote => {
-            let inner_pair = pair.into_inner().next().unwrap();
-            let quoted_value = build_ast(inner_pair);
+            let inner_pair = only_child(pair)?;
+            let quoted_value = build_ast(inner_pair)?;
             debug!("QUOTE content: {:?}", quoted_value);
             MalValue::Round(vec![MalValue::Symbol("quote".to_string()), quoted_value])
         }
 
         Rule::quasiquote => {
-            let inner_pair = pair.into_inner().next().unwrap();
-            let quoted_value = build_ast(inner_pair);
+            let inner_pair = only_child(pair)?;
+            let quoted_value = build_ast(inner_pair)?;
             debug!("QUASIQUOTE content: {:?}", quoted_value);
             MalValue::Round(vec![
                 MalValue::Symbol("quasiquote".to_string()),
@@ -163,15 +696,15 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::unquote => {
-            let inner_pair = pair.into_inner().next().unwrap();
-            let quoted_value = build_ast(inner_pair);
+            let inner_pair = only_child(pair)?;
+            let quoted_value = build_ast(inner_pair)?;
             debug!("UNQUOTE content: {:?}", quoted_value);
             MalValue::Round(vec![MalValue::Symbol("unquote".to_string()), quoted_value])
         }
 
         Rule::splicing_unquote => {
-            let inner_pair = pair.into_inner().next().unwrap();
-            let quoted_value = build_ast(inner_pair);
+            let inner_pair = only_child(pair)?;
+            let quoted_value = build_ast(inner_pair)?;
             debug!("SPLICING-UNQUOTE content: {:?}", quoted_value);
             MalValue::Round(vec![
                 MalValue::Symbol("splice-unquote".to_string()),
@@ -180,32 +713,53 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::deref => {
-            let inner_pair = pair.into_inner().next().unwrap();
-            let quoted_value = build_ast(inner_pair);
+            let inner_pair = only_child(pair)?;
+            let quoted_value = build_ast(inner_pair)?;
             debug!("DEREF content: {:?}", quoted_value);
             MalValue::Round(vec![MalValue::Symbol("deref".to_string()), quoted_value])
         }
 
+        // ::key is read as :ns/key in the current namespace, as of when its
+        // top-level form is built (see read_forms)
         Rule::atom => {
-            let content = pair.as_str().to_string();
+            let content = match pair.as_str().strip_prefix("::") {
+                Some(name) if name.is_empty() || name.contains('/') => {
+                    return Err(reader_error(
+                        &pair,
+                        format!("Invalid keyword: {}", pair.as_str()),
+                    ));
+                }
+                Some(name) => format!(":{}/{}", current_namespace(), name),
+                None => pair.as_str().to_string(),
+            };
             debug!("ATOM content: {:?}", content);
             MalValue::Atom(content)
         }
 
+        // ^m1 ^m2 form reads as a single (with-meta form m), where m merges the
+        // metadata maps in reading order so later keys win
         Rule::metadata => {
-            let mut inner_pairs = pair.into_inner();
-            let meta_pair = inner_pairs.next().unwrap();
-            debug!("META pair content: {:?}", meta_pair);
-            let meta_value = build_ast(meta_pair);
-            debug!("META value: {:?}", meta_value);
-            let target_pair = inner_pairs.next().unwrap();
-            debug!("META TARGET pair content: {:?}", target_pair);
-            let target_value = build_ast(target_pair);
+            let mut entries = MalMap::new();
+            let mut current = pair;
+            while current.as_rule() == Rule::metadata {
+                let missing =
+                    || reader_error(&current, "Metadata requires a map and a form".to_string());
+                let mut inner_pairs = current.clone().into_inner();
+                let meta_pair = inner_pairs.next().ok_or_else(missing)?;
+                let target_pair = inner_pairs.next().ok_or_else(missing)?;
+                debug!("META pair content: {:?}", meta_pair);
+                let meta_value = build_ast(meta_pair.clone())?;
+                debug!("META value: {:?}", meta_value);
+                entries.extend(metadata_entries(&meta_pair, meta_value)?);
+                current = target_pair;
+            }
+            debug!("META TARGET pair content: {:?}", current);
+            let target_value = build_ast(current)?;
             debug!("META TARGET value: {:?}", target_value);
             MalValue::Round(vec![
                 MalValue::Symbol("with-meta".to_string()),
                 target_value,
-                meta_value,
+                MalValue::Map(entries),
             ])
         }
 
@@ -221,12 +775,15 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
 
         Rule::mal => {
-            let content = pair.into_inner().map(build_ast).collect::<Vec<_>>();
+            let content = pair
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::EOI)
+                .map(build_ast)
+                .collect::<Result<Vec<_>, _>>()?;
             debug!("Mal content: {:?}", content);
-            if content.len() == 1 {
-                content.into_iter().next().unwrap()
-            } else {
-                MalValue::Mal(content)
+            match <[MalValue; 1]>::try_from(content) {
+                Ok([value]) => value,
+                Err(content) => MalValue::Mal(content),
             }
         }
         Rule::EOI => {
@@ -235,8 +792,25 @@ fn build_ast(pair: Pair<Rule>) -> MalValue {
         }
         _ => {
             // debug!("Unexpected rule encountered: {:?}", pair.as_rule());
-            panic!("Unexpected rule: {:?}", pair.as_rule());
+            let rule = pair.as_rule();
+            return Err(reader_error(&pair, format!("Unexpected rule: {:?}", rule)));
+        }
+    };
+    Ok(value)
+}
+
+// Expands metadata shorthand: ^:kw means {:kw true} and ^Sym or ^"Str" means {:tag Sym}
+fn metadata_entries(pair: &Pair<Rule>, meta: MalValue) -> Result<MalMap, ReaderError> {
+    match meta {
+        MalValue::Map(map) => Ok(map),
+        MalValue::Atom(k) => Ok(MalMap::from([(MalKey::Keyword(k), MalValue::Bool(true))])),
+        MalValue::Symbol(_) | MalValue::String(_) => {
+            Ok(MalMap::from([(MalKey::Keyword(":tag".to_string()), meta)]))
         }
+        _ => Err(reader_error(
+            pair,
+            "Metadata must be a map, keyword, symbol, or string".to_string(),
+        )),
     }
 }
 
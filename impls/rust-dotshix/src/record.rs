@@ -0,0 +1,144 @@
+use crate::core::{bindable_symbol, bound_builtin, check_redefinition};
+use crate::error::MalError;
+use crate::interpreter::{define_record, notify_def, record_fields, EvalCtx};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::{Env, MalValue};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// A record is a map tagged with its type, #Point {:x 1 :y 2}. map?, get,
+// contains?, keys, vals, count, seq, and keyword lookup treat it as the map,
+// and assoc and conj keep the tag, so records can be used anywhere a map can
+// while still dispatching protocols on their own type.
+
+// The fields of a record, or None for any other value
+pub fn record_entries(value: &MalValue) -> Option<&MalMap> {
+    match value {
+        MalValue::Tagged(tag, inner) => match &**inner {
+            MalValue::Map(entries) if record_fields(tag).is_some() => Some(entries),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// A copy of record with entries as its fields
+pub fn with_entries(record: &MalValue, entries: MalMap) -> MalValue {
+    match record {
+        MalValue::Tagged(tag, _) => MalValue::Tagged(tag.clone(), Box::new(MalValue::Map(entries))),
+        _ => MalValue::Map(entries),
+    }
+}
+
+// The generated functions are bound builtins whose data is the record's name
+fn generated_args(args: &[MalValue]) -> Result<(&str, Vec<String>, &[MalValue])> {
+    match args {
+        [MalValue::String(name), MalValue::Round(args)] => {
+            let fields =
+                record_fields(name).ok_or_else(|| format!("Unknown record type {}", name))?;
+            Ok((name, fields, args))
+        }
+        _ => Err("record: malformed call".into()),
+    }
+}
+
+fn positional(args: &[MalValue]) -> Result<MalValue> {
+    let (name, fields, args) = generated_args(args)?;
+    if args.len() != fields.len() {
+        return Err(format!(
+            "->{} expects {} arguments but got {}",
+            name,
+            fields.len(),
+            args.len()
+        )
+        .into());
+    }
+    let entries = fields
+        .iter()
+        .zip(args)
+        .map(|(field, value)| (MalKey::keyword(field), value.clone()))
+        .collect();
+    Ok(MalValue::Tagged(name.to_string(), Box::new(MalValue::Map(entries))))
+}
+
+// Missing fields are nil; keys that aren't fields are kept as they are
+fn from_map(args: &[MalValue]) -> Result<MalValue> {
+    let (name, fields, args) = generated_args(args)?;
+    let mut entries = match args {
+        [MalValue::Map(map)] => map.clone(),
+        [MalValue::Nil] => MalMap::new(),
+        _ => return Err(format!("map->{} requires a map", name).into()),
+    };
+    for field in &fields {
+        entries.entry(MalKey::keyword(field)).or_insert(MalValue::Nil);
+    }
+    Ok(MalValue::Tagged(name.to_string(), Box::new(MalValue::Map(entries))))
+}
+
+fn predicate(args: &[MalValue]) -> Result<MalValue> {
+    let (name, _, args) = generated_args(args)?;
+    match args {
+        [MalValue::Tagged(tag, _)] => Ok(MalValue::Bool(tag == name)),
+        [_] => Ok(MalValue::Bool(false)),
+        _ => Err(format!("{}? requires exactly one argument", name).into()),
+    }
+}
+
+// An accessor's data is [name :field]; it only accepts records of that type
+fn accessor(args: &[MalValue]) -> Result<MalValue> {
+    let (name, field, args) = match args {
+        [MalValue::Square(data), MalValue::Round(args)] => match data.as_slice() {
+            [MalValue::String(name), MalValue::Atom(field)] => (name, field, args),
+            _ => return Err("record: malformed call".into()),
+        },
+        _ => return Err("record: malformed call".into()),
+    };
+    let accessor = format!("{}-{}", name, &field[1..]);
+    match args.as_slice() {
+        [record @ MalValue::Tagged(tag, _)] if tag == name => Ok(record_entries(record)
+            .and_then(|entries| entries.get(&MalKey::Keyword(field.clone())))
+            .cloned()
+            .unwrap_or(MalValue::Nil)),
+        [other] => Err(format!("{}: {} is not a {}", accessor, pr_str(other, true), name).into()),
+        _ => Err(format!("{} requires exactly one argument", accessor).into()),
+    }
+}
+
+// (defrecord Point [x y]) defines ->Point and map->Point constructors, the
+// predicate Point?, and the accessors Point-x and Point-y
+pub fn defrecord(args: &[MalValue], env: Rc<RefCell<Env>>, _ctx: &EvalCtx) -> Result<MalValue> {
+    let (name, fields) = match args {
+        [MalValue::Symbol(name), MalValue::Square(fields)] => (name, fields),
+        _ => return Err("defrecord requires a name and a vector of fields".into()),
+    };
+    let fields = fields
+        .iter()
+        .map(|field| bindable_symbol("defrecord", field))
+        .collect::<Result<Vec<String>>>()?;
+
+    let type_name = MalValue::String(name.clone());
+    let mut definitions = vec![
+        (format!("->{}", name), bound_builtin(positional, type_name.clone(), &env)),
+        (format!("map->{}", name), bound_builtin(from_map, type_name.clone(), &env)),
+        (format!("{}?", name), bound_builtin(predicate, type_name.clone(), &env)),
+    ];
+    for field in &fields {
+        let data = MalValue::Square(vec![type_name.clone(), MalKey::keyword(field).into()]);
+        definitions.push((format!("{}-{}", name, field), bound_builtin(accessor, data, &env)));
+    }
+    for (symbol, _) in &definitions {
+        check_redefinition("defrecord", symbol, &env)?;
+    }
+
+    define_record(name, fields)?;
+    for (symbol, value) in definitions {
+        env.borrow().set(symbol.clone(), value.clone());
+        notify_def(&symbol, &value);
+    }
+    Ok(MalValue::Symbol(name.clone()))
+}
+
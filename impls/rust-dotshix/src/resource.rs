@@ -0,0 +1,333 @@
+use crate::edn::{read_edn, write_edn};
+use crate::error::MalError;
+use crate::interpreter::{require, Permission};
+use crate::process::Process;
+use crate::reader::{map_get, MalKey, MalMap};
+use crate::MalValue;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+enum Stream {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    Store(Store),
+    Process(Process),
+    #[cfg(feature = "sqlite")]
+    Sql(rusqlite::Connection),
+}
+
+// A key-value store kept in memory and saved to its file as an EDN map after
+// every change, so the file is always a complete snapshot
+struct Store {
+    path: String,
+    entries: MalMap,
+}
+
+impl Store {
+    fn save(&self) -> Result<()> {
+        let text = write_edn(&MalValue::Map(self.entries.clone()))?;
+        // Write beside the store and rename over it, so a crash never leaves half a file
+        let temp = format!("{}.tmp", self.path);
+        fs::write(&temp, text)
+            .and_then(|_| fs::rename(&temp, &self.path))
+            .map_err(|e| format!("{}: {}", self.path, e).into())
+    }
+}
+
+// An opaque handle to an open file. The stream is dropped on close, so closing twice is harmless.
+pub struct Resource {
+    description: String,
+    stream: RefCell<Option<Stream>>,
+}
+
+impl Resource {
+    pub fn is_open(&self) -> bool {
+        self.stream.borrow().is_some()
+    }
+
+    pub fn close(&self) -> Result<()> {
+        match self.stream.borrow_mut().take() {
+            Some(Stream::Writer(mut writer)) => writer
+                .flush()
+                .map_err(|e| format!("close: {}: {}", self.description, e).into()),
+            Some(Stream::Process(mut process)) => {
+                process.kill();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+impl fmt::Debug for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Resource({})", self.description)
+    }
+}
+
+fn resource_arg<'a>(args: &'a [MalValue], name: &str) -> Result<&'a Rc<Resource>> {
+    match args.first() {
+        Some(MalValue::Resource(r)) => Ok(r),
+        _ => Err(format!("{} requires a resource as its first argument", name).into()),
+    }
+}
+
+fn closed_error(name: &str, resource: &Resource) -> String {
+    format!("{}: {} is closed", name, resource.description)
+}
+
+// (file/open path) opens for reading; mode "w" truncates and "a" appends
+pub fn file_open(args: &[MalValue]) -> Result<MalValue> {
+    let (path, mode) = match args {
+        [MalValue::String(path)] => (path, "r"),
+        [MalValue::String(path), MalValue::String(mode)] => (path, mode.as_str()),
+        _ => return Err("file/open requires a path and an optional mode string".into()),
+    };
+
+    let stream = match mode {
+        "r" => {
+            require(Permission::FsRead, "file/open")?;
+            File::open(path).map(|f| Stream::Reader(BufReader::new(f)))
+        }
+        "w" | "a" => {
+            require(Permission::FsWrite, "file/open")?;
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(mode == "w")
+                .append(mode == "a")
+                .open(path)
+                .map(|f| Stream::Writer(BufWriter::new(f)))
+        }
+        _ => return Err(format!("file/open: unknown mode '{}'", mode).into()),
+    }
+    .map_err(|e| format!("file/open: {}: {}", path, e))?;
+
+    Ok(MalValue::Resource(Rc::new(Resource {
+        description: format!("file:{}", path),
+        stream: RefCell::new(Some(stream)),
+    })))
+}
+
+// Returns the next line without its terminator, or nil at end of file
+pub fn file_read_line(args: &[MalValue]) -> Result<MalValue> {
+    let resource = resource_arg(args, "file/read-line")?;
+    let mut stream = resource.stream.borrow_mut();
+    let reader = match stream.as_mut() {
+        Some(Stream::Reader(reader)) => reader,
+        Some(_) => return Err("file/read-line: resource is not readable".into()),
+        None => return Err(closed_error("file/read-line", resource).into()),
+    };
+
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .map_err(|e| format!("file/read-line: {}", e))?;
+    if read == 0 {
+        return Ok(MalValue::Nil);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(MalValue::String(line))
+}
+
+pub fn file_read_all(args: &[MalValue]) -> Result<MalValue> {
+    let resource = resource_arg(args, "file/read-all")?;
+    let mut stream = resource.stream.borrow_mut();
+    let reader = match stream.as_mut() {
+        Some(Stream::Reader(reader)) => reader,
+        Some(_) => return Err("file/read-all: resource is not readable".into()),
+        None => return Err(closed_error("file/read-all", resource).into()),
+    };
+
+    let mut contents = String::new();
+    reader
+        .read_to_string(&mut contents)
+        .map_err(|e| format!("file/read-all: {}", e))?;
+    Ok(MalValue::String(contents))
+}
+
+pub fn file_write(args: &[MalValue]) -> Result<MalValue> {
+    let resource = resource_arg(args, "file/write")?;
+    let data = match args {
+        [_, MalValue::String(s)] => s.as_bytes(),
+        [_, MalValue::Bytes(b)] => b.as_slice(),
+        _ => return Err("file/write requires a resource and a string or bytes".into()),
+    };
+
+    let mut stream = resource.stream.borrow_mut();
+    match stream.as_mut() {
+        Some(Stream::Writer(writer)) => writer
+            .write_all(data)
+            .map(|_| MalValue::Nil)
+            .map_err(|e| format!("file/write: {}", e).into()),
+        Some(_) => Err("file/write: resource is not writable".into()),
+        None => Err(closed_error("file/write", resource).into()),
+    }
+}
+
+// (kv/open path) loads the store at path, starting empty if the file doesn't exist
+pub fn kv_open(args: &[MalValue]) -> Result<MalValue> {
+    let path = match args {
+        [MalValue::String(path)] => path,
+        _ => return Err("kv/open requires a path".into()),
+    };
+    require(Permission::FsRead, "kv/open")?;
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("kv/open: {}: {}", path, e).into()),
+    };
+    let entries = match read_edn(&contents).map_err(|e| format!("kv/open: {}: {}", path, e))? {
+        MalValue::Nil => MalMap::new(),
+        MalValue::Map(map) => map,
+        _ => return Err(format!("kv/open: {} does not contain a map", path).into()),
+    };
+
+    Ok(MalValue::Resource(Rc::new(Resource {
+        description: format!("kv:{}", path),
+        stream: RefCell::new(Some(Stream::Store(Store {
+            path: path.clone(),
+            entries,
+        }))),
+    })))
+}
+
+// Runs f on the store behind a kv resource
+fn with_store<T>(
+    args: &[MalValue],
+    name: &str,
+    f: impl FnOnce(&mut Store) -> Result<T>,
+) -> Result<T> {
+    let resource = resource_arg(args, name)?;
+    let mut stream = resource.stream.borrow_mut();
+    match stream.as_mut() {
+        Some(Stream::Store(store)) => f(store),
+        Some(_) => Err(format!("{}: resource is not a key-value store", name).into()),
+        None => Err(closed_error(name, resource).into()),
+    }
+}
+
+// (kv/get store key) or (kv/get store key default)
+pub fn kv_get(args: &[MalValue]) -> Result<MalValue> {
+    let (key, default) = match args {
+        [_, key] => (key, MalValue::Nil),
+        [_, key, default] => (key, default.clone()),
+        _ => return Err("kv/get requires a store, a key, and an optional default".into()),
+    };
+    with_store(args, "kv/get", |store| {
+        Ok(map_get(&store.entries, key).cloned().unwrap_or(default))
+    })
+}
+
+// (kv/put store key value) saves the store and returns value
+pub fn kv_put(args: &[MalValue]) -> Result<MalValue> {
+    let (key, value) = match args {
+        [_, key, value] => (key, value),
+        _ => return Err("kv/put requires a store, a key, and a value".into()),
+    };
+    require(Permission::FsWrite, "kv/put")?;
+    // Refuse values the file can't hold before they reach the in-memory copy
+    let key = MalKey::try_from(key).map_err(|e| format!("kv/put: {}", e))?;
+    write_edn(value).map_err(|e| format!("kv/put: {}", e))?;
+    with_store(args, "kv/put", |store| {
+        store.entries.insert(key.clone(), value.clone());
+        store.save().map_err(|e| format!("kv/put: {}", e))?;
+        Ok(value.clone())
+    })
+}
+
+pub fn kv_delete(args: &[MalValue]) -> Result<MalValue> {
+    let key = match args {
+        [_, key] => key,
+        _ => return Err("kv/delete requires a store and a key".into()),
+    };
+    require(Permission::FsWrite, "kv/delete")?;
+    with_store(args, "kv/delete", |store| {
+        if let Ok(key) = MalKey::try_from(key) {
+            store.entries.remove(&key);
+        }
+        store.save().map_err(|e| format!("kv/delete: {}", e))?;
+        Ok(MalValue::Nil)
+    })
+}
+
+pub fn kv_keys(args: &[MalValue]) -> Result<MalValue> {
+    if args.len() != 1 {
+        return Err("kv/keys requires a store".into());
+    }
+    with_store(args, "kv/keys", |store| {
+        Ok(MalValue::Round(store.entries.keys().cloned().map(MalValue::from).collect()))
+    })
+}
+
+pub fn process_resource(description: String, process: Process) -> MalValue {
+    MalValue::Resource(Rc::new(Resource {
+        description,
+        stream: RefCell::new(Some(Stream::Process(process))),
+    }))
+}
+
+// Runs f on the child behind a process resource
+pub fn with_process<T>(
+    args: &[MalValue],
+    name: &str,
+    f: impl FnOnce(&mut Process) -> Result<T>,
+) -> Result<T> {
+    let resource = resource_arg(args, name)?;
+    let mut stream = resource.stream.borrow_mut();
+    match stream.as_mut() {
+        Some(Stream::Process(process)) => f(process),
+        Some(_) => Err(format!("{}: resource is not a process", name).into()),
+        None => Err(closed_error(name, resource).into()),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub fn sql_resource(description: String, connection: rusqlite::Connection) -> MalValue {
+    MalValue::Resource(Rc::new(Resource {
+        description,
+        stream: RefCell::new(Some(Stream::Sql(connection))),
+    }))
+}
+
+// Runs f on the connection behind a sql resource
+#[cfg(feature = "sqlite")]
+pub fn with_sql<T>(
+    args: &[MalValue],
+    name: &str,
+    f: impl FnOnce(&rusqlite::Connection) -> Result<T>,
+) -> Result<T> {
+    let resource = resource_arg(args, name)?;
+    let stream = resource.stream.borrow();
+    match stream.as_ref() {
+        Some(Stream::Sql(connection)) => f(connection),
+        Some(_) => Err(format!("{}: resource is not a database", name).into()),
+        None => Err(closed_error(name, resource).into()),
+    }
+}
+
+pub fn close(args: &[MalValue]) -> Result<MalValue> {
+    let resource = resource_arg(args, "close")?;
+    resource.close().map(|_| MalValue::Nil)
+}
+
+pub fn open_question(args: &[MalValue]) -> Result<MalValue> {
+    let resource = resource_arg(args, "open?")?;
+    Ok(MalValue::Bool(resource.is_open()))
+}
@@ -0,0 +1,228 @@
+use crate::bundle;
+use crate::core::is_no_print;
+use crate::coverage;
+use crate::interpreter::{print_readably, Interpreter};
+use crate::json::Json;
+use crate::output::{capture_with, Stream};
+use crate::printer::pr_str;
+use crate::reader::{
+    error_span, format_pest_error, read_forms_spanned, ReaderError, Span, Spanned,
+};
+use crate::MalValue;
+use std::fs;
+
+// A top-level form as read, or the reader error in it
+type Form = Result<Spanned, ReaderError>;
+
+// A script to run: source text, or forms already read and macroexpanded by
+// the compile command
+pub enum Source {
+    Text(String),
+    Compiled(Vec<Spanned>),
+}
+
+impl Source {
+    // Reads the file at path, telling a compiled script from source by its header
+    pub fn read(path: &str) -> Result<Source, String> {
+        let bytes = fs::read(path).map_err(|e| e.to_string())?;
+        if bundle::is_bundle(&bytes) {
+            return bundle::load(&bytes).map(Source::Compiled).map_err(|e| e.to_string());
+        }
+        String::from_utf8(bytes)
+            .map(Source::Text)
+            .map_err(|_| "stream did not contain valid UTF-8".into())
+    }
+
+    // The top-level forms, source text read a form at a time (see read_forms)
+    pub fn forms(&self) -> Result<Box<dyn Iterator<Item = Form> + '_>, ReaderError> {
+        Ok(match self {
+            Source::Text(text) => Box::new(read_forms_spanned(text)?),
+            Source::Compiled(forms) => Box::new(forms.iter().cloned().map(Ok)),
+        })
+    }
+}
+
+// How a non-interactive run reports back: as the REPL would, or as a single
+// JSON document on stdout for other tools to consume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+// Why a run failed, each with its own exit status so shell pipelines can
+// tell a malformed script from one that failed or threw while running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FailureKind {
+    Runtime,
+    Reader,
+    Thrown,
+}
+
+impl FailureKind {
+    fn exit_code(self) -> i32 {
+        match self {
+            FailureKind::Runtime => 1,
+            FailureKind::Reader => 2,
+            FailureKind::Thrown => 3,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FailureKind::Runtime => "runtime",
+            FailureKind::Reader => "reader",
+            FailureKind::Thrown => "thrown",
+        }
+    }
+}
+
+// Where a failed run stopped: the message and the top-level form (or, for
+// reader errors, the position in the input) it came from
+struct Failure {
+    kind: FailureKind,
+    message: String,
+    span: Span,
+}
+
+struct Run {
+    results: Vec<MalValue>,
+    failure: Option<Failure>,
+}
+
+// Evaluates source's top-level forms in order, reading each as it is reached,
+// and stops at the first error. name is the file, or <expr> for -e.
+fn run_forms(name: &str, source: &Source, interpreter: &Interpreter) -> Run {
+    let mut run = Run {
+        results: Vec::new(),
+        failure: None,
+    };
+    let forms = match source.forms() {
+        Ok(forms) => forms,
+        Err(e) => {
+            run.failure = Some(reader_failure(e));
+            return run;
+        }
+    };
+
+    let ctx = interpreter.ctx();
+    let file = coverage::start_file(name);
+    let mut forms = forms.enumerate();
+    for (index, form) in forms.by_ref() {
+        let form = match form {
+            Ok(form) => form,
+            Err(e) => {
+                run.failure = Some(reader_failure(e));
+                break;
+            }
+        };
+        coverage::form_read(file, index, &form);
+        if let MalValue::Comment(_) = form.value {
+            continue;
+        }
+        coverage::form_evaluated(file, index);
+        // Each top-level form gets a fuel budget of its own
+        ctx.refuel();
+        match crate::eval(&form.value, interpreter.env(), ctx) {
+            Ok(value) => run.results.push(value),
+            Err(error) => {
+                let kind = if error.is_thrown() {
+                    FailureKind::Thrown
+                } else {
+                    FailureKind::Runtime
+                };
+                run.failure = Some(Failure {
+                    kind,
+                    message: error.to_string(),
+                    span: form.span,
+                });
+                break;
+            }
+        }
+    }
+    coverage::forms_skipped(file, forms);
+    run
+}
+
+fn reader_failure(e: ReaderError) -> Failure {
+    let span = error_span(&e);
+    Failure {
+        kind: FailureKind::Reader,
+        message: format_pest_error(*e),
+        span,
+    }
+}
+
+// Runs a file or -e expression to completion; with echo_last the final result is
+// printed the way the REPL would. Returns the process exit status: 0 on success,
+// 1 for a runtime error, 2 for a reader error, and 3 for an uncaught throw.
+pub fn run(
+    interpreter: &Interpreter,
+    name: &str,
+    source: &Source,
+    echo_last: bool,
+    format: OutputFormat,
+) -> i32 {
+    let _active = interpreter.activate();
+    let run = match format {
+        OutputFormat::Text => {
+            let run = run_forms(name, source, interpreter);
+            match &run.failure {
+                Some(failure) => eprintln!("Error: {}", failure.message),
+                None => {
+                    let last = run.results.last().filter(|last| echo_last && !is_no_print(last));
+                    if let Some(last) = last {
+                        println!("{}", pr_str(last, print_readably()));
+                    }
+                }
+            }
+            run
+        }
+        // Errors are part of the report, so in this format stdout carries everything
+        OutputFormat::Json => {
+            let ((run, out), err) = capture_with(Stream::Err, || {
+                capture_with(Stream::Out, || run_forms(name, source, interpreter))
+            });
+            println!("{}", report(&run, out, err).encode());
+            run
+        }
+    };
+    run.failure.map_or(0, |failure| failure.kind.exit_code())
+}
+
+fn report(run: &Run, out: String, err: String) -> Json {
+    let results = run
+        .results
+        .iter()
+        .map(|value| Json::String(pr_str(value, true)))
+        .collect();
+    let errors = run
+        .failure
+        .iter()
+        .map(|failure| {
+            Json::Object(vec![
+                ("kind", Json::String(failure.kind.name().to_string())),
+                ("message", Json::String(failure.message.clone())),
+                ("span", span_json(&failure.span)),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("ok", Json::Bool(run.failure.is_none())),
+        ("results", Json::Array(results)),
+        ("errors", Json::Array(errors)),
+        ("stdout", Json::String(out)),
+        ("stderr", Json::String(err)),
+    ])
+}
+
+fn span_json(span: &Span) -> Json {
+    let number = |n: usize| Json::Number(n as i64);
+    Json::Object(vec![
+        ("start", number(span.start)),
+        ("end", number(span.end)),
+        ("line", number(span.line)),
+        ("column", number(span.column)),
+        ("end_line", number(span.end_line)),
+    ])
+}
@@ -0,0 +1,270 @@
+use crate::core::{builtin_name, call};
+use crate::error::MalError;
+use crate::interpreter::{define_spec, registered_spec};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::{Function, MalValue};
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// A spec is ordinary data:
+//   - a keyword names a spec registered with defspec
+//   - a map {key spec ...} requires a map holding every key, each conforming
+//   - spec/and, spec/or, spec/coll-of, and spec/nilable build #spec/... values
+//   - any other function is a predicate, passing when it returns a truthy value
+// Keyword and map specs take precedence over calling them as functions.
+
+// Self-referential specs are allowed for nested data, but a spec that reaches
+// itself without descending into the value would never finish
+const MAX_DEPTH: usize = 512;
+
+struct Problem {
+    path: Vec<MalValue>,
+    expected: String,
+    value: Option<MalValue>,
+}
+
+fn combinator(spec: &MalValue) -> Option<(&str, &MalValue)> {
+    match spec {
+        MalValue::Tagged(tag, inner) if tag.starts_with("spec/") => Some((tag, inner)),
+        _ => None,
+    }
+}
+
+fn combined(inner: &MalValue) -> &[MalValue] {
+    match inner {
+        MalValue::Square(specs) => specs,
+        _ => std::slice::from_ref(inner),
+    }
+}
+
+// Checks the shape of a spec when it is built, so a typo fails at defspec
+// rather than on the first value validated. Named specs may be defined later.
+fn check_spec(spec: &MalValue, name: &str) -> Result<()> {
+    match spec {
+        MalValue::Atom(_) => Ok(()),
+        MalValue::Map(entries) => entries.values().try_for_each(|spec| check_spec(spec, name)),
+        _ if combinator(spec).is_some() => Ok(()),
+        MalValue::BuiltinFunction(_) => Ok(()),
+        _ => Err(format!("{}: {} is not a spec", name, pr_str(spec, true)).into()),
+    }
+}
+
+fn describe(spec: &MalValue) -> String {
+    match spec {
+        MalValue::Atom(name) => name.clone(),
+        MalValue::Map(_) => "map".to_string(),
+        MalValue::BuiltinFunction(Function::Builtin(func)) => builtin_name(*func)
+            .map(str::to_string)
+            .unwrap_or_else(|| pr_str(spec, true)),
+        _ => match combinator(spec) {
+            Some((tag, inner)) => {
+                let parts: Vec<String> = combined(inner).iter().map(describe).collect();
+                format!("({} {})", tag, parts.join(" "))
+            }
+            None => pr_str(spec, true),
+        },
+    }
+}
+
+fn is_nilable(spec: &MalValue) -> bool {
+    matches!(combinator(spec), Some(("spec/nilable", _)))
+}
+
+// Appends every way value fails spec to problems
+fn conform(
+    spec: &MalValue,
+    value: &MalValue,
+    path: &mut Vec<MalValue>,
+    problems: &mut Vec<Problem>,
+    depth: usize,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        let message = format!("spec {} nests too deeply; does it refer to itself?", describe(spec));
+        return Err(message.into());
+    }
+    let failure = |path: &[MalValue], expected: String| Problem {
+        path: path.to_vec(),
+        expected,
+        value: Some(value.clone()),
+    };
+    match spec {
+        MalValue::Atom(name) => {
+            let spec = registered_spec(name).ok_or_else(|| format!("unknown spec {}", name))?;
+            conform(&spec, value, path, problems, depth + 1)
+        }
+        MalValue::Map(keys) => {
+            let MalValue::Map(entries) = value else {
+                problems.push(failure(path, "map".to_string()));
+                return Ok(());
+            };
+            for (key, key_spec) in keys {
+                path.push(MalValue::from(key.clone()));
+                match entries.get(key) {
+                    Some(found) => conform(key_spec, found, path, problems, depth + 1)?,
+                    None if is_nilable(key_spec) => {}
+                    None => problems.push(Problem {
+                        path: path.clone(),
+                        expected: describe(key_spec),
+                        value: None,
+                    }),
+                }
+                path.pop();
+            }
+            Ok(())
+        }
+        MalValue::BuiltinFunction(_) => {
+            let result = call(spec, std::slice::from_ref(value))?;
+            if matches!(result, MalValue::Nil | MalValue::Bool(false)) {
+                problems.push(failure(path, describe(spec)));
+            }
+            Ok(())
+        }
+        _ => match combinator(spec) {
+            // Reports only the first failing part, since the rest assume it passed
+            Some(("spec/and", inner)) => {
+                let before = problems.len();
+                for part in combined(inner) {
+                    conform(part, value, path, problems, depth + 1)?;
+                    if problems.len() > before {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+            Some(("spec/or", inner)) => {
+                for part in combined(inner) {
+                    let mut attempt = Vec::new();
+                    conform(part, value, path, &mut attempt, depth + 1)?;
+                    if attempt.is_empty() {
+                        return Ok(());
+                    }
+                }
+                problems.push(failure(path, describe(spec)));
+                Ok(())
+            }
+            Some(("spec/nilable", _)) if matches!(value, MalValue::Nil) => Ok(()),
+            Some(("spec/nilable", inner)) => conform(inner, value, path, problems, depth + 1),
+            Some(("spec/coll-of", inner)) => {
+                let (MalValue::Round(items) | MalValue::Square(items)) = value else {
+                    problems.push(failure(path, describe(spec)));
+                    return Ok(());
+                };
+                for (index, item) in items.iter().enumerate() {
+                    path.push(MalValue::Number(index as i64));
+                    conform(inner, item, path, problems, depth + 1)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            _ => Err(format!("{} is not a spec", pr_str(spec, true)).into()),
+        },
+    }
+}
+
+fn problems_for(args: &[MalValue], name: &str) -> Result<Vec<Problem>> {
+    let [spec, value] = args else {
+        return Err(format!("{} requires a spec and a value", name).into());
+    };
+    check_spec(spec, name)?;
+    let mut problems = Vec::new();
+    conform(spec, value, &mut Vec::new(), &mut problems, 0)?;
+    Ok(problems)
+}
+
+// (defspec ::user {:name string? :age int?}) returns the spec's keyword
+pub fn defspec(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Atom(name), spec] => {
+            check_spec(spec, "defspec")?;
+            define_spec(name, spec.clone())?;
+            Ok(args[0].clone())
+        }
+        _ => Err("defspec requires a keyword and a spec".into()),
+    }
+}
+
+pub fn valid_question(args: &[MalValue]) -> Result<MalValue> {
+    Ok(MalValue::Bool(problems_for(args, "valid?")?.is_empty()))
+}
+
+// One line per problem, e.g. `[:age] "x" fails int?`; nil when value conforms
+pub fn explain(args: &[MalValue]) -> Result<MalValue> {
+    let problems = problems_for(args, "explain")?;
+    if problems.is_empty() {
+        return Ok(MalValue::Nil);
+    }
+    let lines: Vec<String> = problems
+        .into_iter()
+        .map(|problem| {
+            let at = if problem.path.is_empty() {
+                String::new()
+            } else {
+                format!("{} ", pr_str(&MalValue::Square(problem.path), true))
+            };
+            match problem.value {
+                Some(value) => format!("{}{} fails {}", at, pr_str(&value, true), problem.expected),
+                None => format!("{}is missing, expected {}", at, problem.expected),
+            }
+        })
+        .collect();
+    Ok(MalValue::String(lines.join("\n")))
+}
+
+// A missing key is reported with :missing true in place of :value
+pub fn explain_data(args: &[MalValue]) -> Result<MalValue> {
+    let problems = problems_for(args, "explain-data")?;
+    if problems.is_empty() {
+        return Ok(MalValue::Nil);
+    }
+    let problems = problems
+        .into_iter()
+        .map(|problem| {
+            let mut entry = MalMap::from([
+                (MalKey::keyword("path"), MalValue::Square(problem.path)),
+                (MalKey::keyword("expected"), MalValue::String(problem.expected)),
+            ]);
+            match problem.value {
+                Some(value) => entry.insert(MalKey::keyword("value"), value),
+                None => entry.insert(MalKey::keyword("missing"), MalValue::Bool(true)),
+            };
+            MalValue::Map(entry)
+        })
+        .collect();
+    Ok(MalValue::Square(problems))
+}
+
+fn build(tag: &str, specs: &[MalValue]) -> Result<MalValue> {
+    if specs.is_empty() {
+        return Err(format!("{} requires at least one spec", tag).into());
+    }
+    specs.iter().try_for_each(|spec| check_spec(spec, tag))?;
+    Ok(MalValue::Tagged(tag.to_string(), Box::new(MalValue::Square(specs.to_vec()))))
+}
+
+fn build_one(tag: &str, args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [spec] => {
+            check_spec(spec, tag)?;
+            Ok(MalValue::Tagged(tag.to_string(), Box::new(spec.clone())))
+        }
+        _ => Err(format!("{} requires exactly one spec", tag).into()),
+    }
+}
+
+pub fn spec_and(args: &[MalValue]) -> Result<MalValue> {
+    build("spec/and", args)
+}
+
+pub fn spec_or(args: &[MalValue]) -> Result<MalValue> {
+    build("spec/or", args)
+}
+
+pub fn spec_coll_of(args: &[MalValue]) -> Result<MalValue> {
+    build_one("spec/coll-of", args)
+}
+
+pub fn spec_nilable(args: &[MalValue]) -> Result<MalValue> {
+    build_one("spec/nilable", args)
+}
@@ -0,0 +1,140 @@
+use crate::env::BuiltinFn;
+use crate::error::MalError;
+use crate::interpreter::{require, Permission};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::resource::{sql_resource, with_sql};
+use crate::MalValue;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Registered alongside core's builtins when the sqlite feature is enabled
+pub const SQL_BUILTINS: &[(&str, BuiltinFn, &str)] = &[
+    ("sql/open", sql_open, "Opens a SQLite database file, or \":memory:\""),
+    (
+        "sql/query",
+        sql_query,
+        "Runs a query with optional parameters, returning a vector of row maps",
+    ),
+    (
+        "sql/execute",
+        sql_execute,
+        "Runs a statement with optional parameters, returning the number of changed rows",
+    ),
+];
+
+fn sql_error(name: &str, e: rusqlite::Error) -> MalError {
+    format!("{}: {}", name, e).into()
+}
+
+// (sql/open path); opening a file may create it, so both fs permissions are
+// needed. An in-memory database needs them too, since ATTACH and VACUUM INTO
+// read and write files named in the SQL itself.
+pub fn sql_open(args: &[MalValue]) -> Result<MalValue> {
+    let path = match args {
+        [MalValue::String(path)] => path,
+        _ => return Err("sql/open requires a path".into()),
+    };
+    require(Permission::FsRead, "sql/open")?;
+    require(Permission::FsWrite, "sql/open")?;
+    let connection = if path == ":memory:" {
+        Connection::open_in_memory()
+    } else {
+        Connection::open(path)
+    }
+    .map_err(|e| sql_error("sql/open", e))?;
+    Ok(sql_resource(format!("sql:{}", path), connection))
+}
+
+fn to_sql(value: &MalValue, name: &str) -> Result<Value> {
+    Ok(match value {
+        MalValue::Nil => Value::Null,
+        MalValue::Bool(b) => Value::Integer(*b as i64),
+        MalValue::Number(n) => Value::Integer(*n),
+        MalValue::Float(f) => Value::Real(*f),
+        MalValue::String(s) => Value::Text(s.clone()),
+        MalValue::Bytes(b) => Value::Blob(b.clone()),
+        _ => {
+            return Err(format!(
+                "{}: cannot pass {} as a parameter",
+                name,
+                pr_str(value, true)
+            )
+            .into())
+        }
+    })
+}
+
+fn from_sql(value: ValueRef) -> MalValue {
+    match value {
+        ValueRef::Null => MalValue::Nil,
+        ValueRef::Integer(n) => MalValue::Number(n),
+        ValueRef::Real(f) => MalValue::Float(f),
+        ValueRef::Text(t) => MalValue::String(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => MalValue::Bytes(b.to_vec()),
+    }
+}
+
+// Splits (f db sql [params]) into the statement text and its bound parameters
+fn statement_args<'a>(args: &'a [MalValue], name: &str) -> Result<(&'a str, Vec<Value>)> {
+    let (sql, params) = match args {
+        [_, MalValue::String(sql)] => (sql, &[][..]),
+        [_, MalValue::String(sql), MalValue::Square(params) | MalValue::Round(params)] => {
+            (sql, &params[..])
+        }
+        _ => {
+            return Err(format!(
+                "{} requires a database, a SQL string, and an optional parameter vector",
+                name
+            )
+            .into())
+        }
+    };
+    let params = params
+        .iter()
+        .map(|p| to_sql(p, name))
+        .collect::<Result<Vec<Value>>>()?;
+    Ok((sql, params))
+}
+
+// Each row becomes a map from keywordized column names to values
+pub fn sql_query(args: &[MalValue]) -> Result<MalValue> {
+    let (sql, params) = statement_args(args, "sql/query")?;
+    with_sql(args, "sql/query", |connection| {
+        let mut statement = connection
+            .prepare(sql)
+            .map_err(|e| sql_error("sql/query", e))?;
+        let columns: Vec<MalKey> = statement
+            .column_names()
+            .into_iter()
+            .map(MalKey::keyword)
+            .collect();
+        let mut rows = statement
+            .query(params_from_iter(params))
+            .map_err(|e| sql_error("sql/query", e))?;
+
+        let mut result = Vec::new();
+        while let Some(row) = rows.next().map_err(|e| sql_error("sql/query", e))? {
+            let mut entries = MalMap::new();
+            for (i, column) in columns.iter().enumerate() {
+                let value = row.get_ref(i).map_err(|e| sql_error("sql/query", e))?;
+                entries.insert(column.clone(), from_sql(value));
+            }
+            result.push(MalValue::Map(entries));
+        }
+        Ok(MalValue::Square(result))
+    })
+}
+
+pub fn sql_execute(args: &[MalValue]) -> Result<MalValue> {
+    let (sql, params) = statement_args(args, "sql/execute")?;
+    with_sql(args, "sql/execute", |connection| {
+        connection
+            .execute(sql, params_from_iter(params))
+            .map(|changed| MalValue::Number(changed as i64))
+            .map_err(|e| sql_error("sql/execute", e))
+    })
+}
@@ -1,201 +1,172 @@
-mod core;
-mod env;
-mod printer;
-mod reader;
-
-use core::create_repl_env;
-use env::{Env, Function};
-use env_logger;
-use pest::error::Error;
-use printer::pr_str;
-use reader::{format_pest_error, parse_input, MalValue, Rule};
+use rust_dotshix::bundle;
+use rust_dotshix::highlight::ReplHelper;
+use rust_dotshix::interpreter::{DivisionMode, Interpreter, Tracer};
+use rust_dotshix::reader::MalValue;
+use rust_dotshix::script::{self, OutputFormat, Source};
 use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result as RustylineResult};
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::result::Result as StdResult;
-
-// Custom Result type for our application
-type Result<T> = StdResult<T, String>;
+use rustyline::history::DefaultHistory;
+use rustyline::{Editor, Result as RustylineResult};
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+// Defaults for --trace-depth and --trace-length
+const TRACE_DEPTH: usize = 16;
+const TRACE_LENGTH: usize = 80;
+
+fn parse_limit(arg: &str, value: &str) -> usize {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid number in {}", arg);
+        std::process::exit(2);
+    })
+}
 
-fn read(input: String) -> StdResult<Vec<MalValue>, Box<Error<Rule>>> {
-    parse_input(&input)
+fn option_value(option: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("Missing value for {}", option);
+        std::process::exit(2);
+    })
 }
 
-fn eval_ast(ast: &MalValue, env: Rc<RefCell<Env>>) -> Result<MalValue> {
-    match ast {
-        MalValue::Symbol(s) => {
-            if env.borrow().get(s).is_some() {
-                Ok(MalValue::Symbol(s.clone()))
-            } else {
-                // Return the symbol as is, assuming it might be defined later
-                Ok(MalValue::Symbol(s.clone()))
+// compile FILE [-o OUT] writes FILE with its macros expanded to OUT, by
+// default FILE with a .malc extension, which runs like the source it came from
+fn compile_command(interpreter: &Interpreter, mut args: impl Iterator<Item = String>) -> i32 {
+    let (mut input, mut output) = (None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(option_value(&arg, args.next())),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => {
+                eprintln!("Unknown option: {}", arg);
+                return 2;
             }
         }
-        MalValue::Round(list)
-        | MalValue::Square(list)
-        | MalValue::Curly(list)
-        | MalValue::Mal(list) => {
-            let eval_list: Result<Vec<MalValue>> =
-                list.iter().map(|x| eval(x, env.clone())).collect();
-            eval_list.map(|eval_list| match ast {
-                MalValue::Round(_) => MalValue::Round(eval_list),
-                MalValue::Square(_) => MalValue::Square(eval_list),
-                MalValue::Curly(_) => MalValue::Curly(eval_list),
-                MalValue::Mal(_) => MalValue::Mal(eval_list),
-                _ => unreachable!(),
-            })
+    }
+    let Some(input) = input else {
+        eprintln!("Usage: compile FILE [-o OUT]");
+        return 2;
+    };
+    let output = output.unwrap_or_else(|| {
+        let path = Path::new(&input).with_extension("malc");
+        path.to_string_lossy().into_owned()
+    });
+    match bundle::compile_file(interpreter, &input, &output) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
         }
-        _ => Ok(ast.clone()),
     }
 }
 
-fn eval(ast: &MalValue, env: Rc<RefCell<Env>>) -> Result<MalValue> {
-    match ast {
-        // Case for evaluating a single symbol
-        MalValue::Symbol(s) => {
-            if let Some(value) = env.borrow().get(s) {
-                Ok(value.clone())
-            } else {
-                Err(format!("Symbol '{}' not found in environment", s))
-            }
-        }
+fn main() -> RustylineResult<()> {
+    env_logger::init();
 
-        // Case for evaluating a list (represented as a Round value)
-        MalValue::Round(list) => {
-            if list.is_empty() {
-                return Ok(MalValue::Round(list.clone()));
+    let interpreter = Interpreter::new();
+    let interrupted = interpreter.interrupt_handle();
+    // At the prompt rustyline handles Ctrl-C itself; this only fires mid-evaluation
+    if let Err(e) = ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed)) {
+        eprintln!("Warning: could not install Ctrl-C handler: {}", e);
+    }
+    let mut trace: Option<Option<String>> = None;
+    let (mut trace_depth, mut trace_length) = (TRACE_DEPTH, TRACE_LENGTH);
+    // A file or -e expression runs non-interactively instead of starting the REPL
+    let mut script: Option<(String, Source, bool)> = None;
+    let mut format = OutputFormat::Text;
+    let mut argv = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--deterministic" => interpreter.set_deterministic(),
+            "--strict-redef" => interpreter.set_strict_redef(),
+            "--float-division" => interpreter.set_division_mode(DivisionMode::Float),
+            "--trace-eval" => trace = Some(None),
+            "--coverage" => interpreter.enable_coverage(),
+            "compile" if script.is_none() => {
+                std::process::exit(compile_command(&interpreter, args.by_ref()))
             }
-
-            // Evaluate the first element to get the function
-            let func = eval(&list[0], env.clone())?;
-
-            match func {
-                MalValue::BuiltinFunction(Function::SpecialForm(func)) => {
-                    // Pass unevaluated arguments to the special form
-                    func(&list[1..], env.clone())
-                }
-                // MalValue::BuiltinFunction(Function::WithEnv(func, func_env)) => {
-                //     // Evaluate the arguments
-                //     let args: Vec<MalValue> = list[1..]
-                //         .iter()
-                //         .map(|x| eval(x, env.clone()))
-                //         .collect::<Result<Vec<MalValue>>>()?;
-                //     func(&args, func_env.clone())
-                // }
-                MalValue::BuiltinFunction(Function::Builtin(func)) => {
-                    // Evaluate the arguments
-                    let args: Vec<MalValue> = list[1..]
-                        .iter()
-                        .map(|x| eval(x, env.clone()))
-                        .collect::<Result<Vec<MalValue>>>()?;
-                    func(&args)
-                }
-                MalValue::BuiltinFunction(Function::UserDefined {
-                    params,
-                    rest_param,
-                    body,
-                    env: func_env,
-                }) => {
-                    // Evaluate the arguments
-                    let args: Vec<MalValue> = list[1..]
-                        .iter()
-                        .map(|x| eval(x, env.clone()))
-                        .collect::<Result<Vec<MalValue>>>()?;
-
-                    let num_fixed_params = params.len();
-                    let num_args = args.len();
-
-                    if num_args < num_fixed_params {
-                        return Err(format!(
-                            "Expected at least {} arguments but got {}",
-                            num_fixed_params, num_args
-                        ));
-                    }
-
-                    // Create a new environment for the function
-                    let new_env = Rc::new(RefCell::new(Env::new(Some(Rc::clone(
-                        &func_env.borrow().get_bindings(),
-                    )))));
-
-                    // Bind fixed parameters
-                    for (param, arg) in params.iter().zip(args.iter()) {
-                        new_env.borrow_mut().set(param.clone(), arg.clone());
-                    }
-
-                    // Handle rest parameter
-                    if let Some(rest_param_name) = rest_param {
-                        let rest_args = args[num_fixed_params..].to_vec();
-                        new_env
-                            .borrow_mut()
-                            .set(rest_param_name.clone(), MalValue::Round(rest_args));
-                    } else if num_args > num_fixed_params {
-                        return Err(format!(
-                            "Expected {} arguments but got {}",
-                            num_fixed_params, num_args
-                        ));
+            "-e" => {
+                let source = Source::Text(option_value(&arg, args.next()));
+                script = Some(("<expr>".to_string(), source, true));
+            }
+            "--output" => {
+                format = match option_value(&arg, args.next()).as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    other => {
+                        eprintln!("Unknown output format: {}", other);
+                        std::process::exit(2);
                     }
-
-                    // Evaluate the function body
-                    let mut result = MalValue::Nil;
-                    for expr in body.iter() {
-                        result = eval(expr, Rc::clone(&new_env))?;
+                }
+            }
+            _ => {
+                if !arg.starts_with('-') && script.is_none() {
+                    match Source::read(&arg) {
+                        Ok(source) => script = Some((arg, source, false)),
+                        Err(e) => {
+                            eprintln!("Cannot read {}: {}", arg, e);
+                            std::process::exit(2);
+                        }
                     }
-
-                    Ok(result)
+                    // Everything after the script belongs to it, options included
+                    argv.extend(args.by_ref().map(MalValue::String));
+                } else if let Some(path) = arg.strip_prefix("--trace-eval=") {
+                    trace = Some(Some(path.to_string()));
+                } else if let Some(n) = arg.strip_prefix("--trace-depth=") {
+                    trace_depth = parse_limit(&arg, n);
+                } else if let Some(n) = arg.strip_prefix("--trace-length=") {
+                    trace_length = parse_limit(&arg, n);
+                } else if let Some(n) = arg.strip_prefix("--fuel=") {
+                    interpreter.set_fuel(Some(parse_limit(&arg, n) as u64));
+                } else {
+                    eprintln!("Unknown option: {}", arg);
+                    std::process::exit(2);
                 }
-                _ => Err("First element is not a function".to_string()),
             }
         }
-
-        // Other cases, delegate to eval_ast
-        _ => eval_ast(ast, env),
     }
-}
-
-fn eval_all(input: Vec<MalValue>, env: Rc<RefCell<Env>>) -> Result<Vec<MalValue>> {
-    input.into_iter().map(|x| eval(&x, env.clone())).collect()
-}
+    if let Some(path) = trace {
+        let sink: Box<dyn Write> = match path {
+            None => Box::new(io::stderr()),
+            Some(path) => match File::create(&path) {
+                Ok(file) => Box::new(LineWriter::new(file)),
+                Err(e) => {
+                    eprintln!("Cannot open trace file {}: {}", path, e);
+                    std::process::exit(2);
+                }
+            },
+        };
+        interpreter.set_tracer(Tracer::new(sink, trace_depth, trace_length));
+    }
 
-fn print(input: Vec<MalValue>) -> String {
-    input
-        .iter()
-        .map(|node| pr_str(node, true))
-        .collect::<Vec<String>>()
-        .join(" ")
-}
+    interpreter
+        .env()
+        .borrow()
+        .set("*ARGV*".to_string(), MalValue::Round(argv));
 
-fn rep(input: String, env: Rc<RefCell<Env>>) -> String {
-    match read(input) {
-        Ok(parsed) => match eval_all(parsed, env.clone()) {
-            Ok(evaluated) => print(evaluated),
-            Err(e) => format!("Error: {}", e),
-        },
-        Err(e) => format!("Error: {:?}", format_pest_error(*e)),
+    if let Some((name, source, echo_last)) = script {
+        let status = script::run(&interpreter, &name, &source, echo_last, format);
+        if let Some(report) = interpreter.coverage_report() {
+            eprint!("{}", report);
+        }
+        std::process::exit(status);
     }
-}
-
-fn main() -> RustylineResult<()> {
-    env_logger::init();
 
-    let mut rl = DefaultEditor::new()?;
+    // Only the REPL needs the line editor, so scripts never set up the terminal
+    let mut rl = Editor::<ReplHelper, DefaultHistory>::new()?;
+    rl.set_helper(Some(ReplHelper));
     rl.set_auto_add_history(true);
-    let repl_env = create_repl_env();
-
     loop {
         let readline = rl.readline("user> ");
         // ownerproof-4219578-1730745905-59db954c3998
-        // NOTE PROBABLY DELETE THIS LATER
-        // part of test cases
-        rep(
-            "(def! not (fn* (a) (if a false true)))".to_string(),
-            repl_env.clone(),
-        );
         match readline {
             Ok(line) => {
-                let result = rep(line, repl_env.clone());
-                println!("{}", result);
+                let result = interpreter.rep(&line);
+                if !result.is_empty() {
+                    println!("{}", result);
+                }
             }
 
             Err(ReadlineError::Interrupted) => {
@@ -212,6 +183,9 @@ fn main() -> RustylineResult<()> {
             }
         }
     }
+    if let Some(report) = interpreter.coverage_report() {
+        eprint!("{}", report);
+    }
 
     Ok(())
 }
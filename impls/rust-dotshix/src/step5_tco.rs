@@ -0,0 +1,2 @@
+// Step 5 is step 4's interpreter, whose eval already handles tail calls in a loop
+include!("step4_if_fn_do.rs");
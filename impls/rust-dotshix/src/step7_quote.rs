@@ -0,0 +1,2 @@
+// Step 7 is step 4's interpreter, whose eval already handles quote and quasiquote
+include!("step4_if_fn_do.rs");
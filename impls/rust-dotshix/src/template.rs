@@ -0,0 +1,143 @@
+use crate::error::MalError;
+use crate::printer::pr_str;
+use crate::reader::MalKey;
+use crate::MalValue;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// Mustache-lite: {{name}} inserts a value (printed as str would), {{a.b}}
+// looks up a nested key, and {{.}} is the current item. {{#xs}}...{{/xs}}
+// repeats its body for each element of a sequence, or renders it once for any
+// other truthy value; {{^xs}}...{{/xs}} renders only when xs is falsy or empty.
+// Names look up keyword keys first, then string keys; a missing name is empty.
+enum Node {
+    Text(String),
+    Var(String),
+    Section(String, bool, Vec<Node>),
+}
+
+fn parse(template: &str) -> Result<Vec<Node>> {
+    let mut stack: Vec<(String, bool, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find("{{") {
+        if open > 0 {
+            nodes.push(Node::Text(rest[..open].to_string()));
+        }
+        let after = &rest[open + 2..];
+        let close = after
+            .find("}}")
+            .ok_or("render: unclosed {{ in template")?;
+        let tag = after[..close].trim();
+        rest = &after[close + 2..];
+
+        if let Some(name) = tag.strip_prefix('#').or(tag.strip_prefix('^')) {
+            let inverted = tag.starts_with('^');
+            stack.push((name.trim().to_string(), inverted, std::mem::take(&mut nodes)));
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            let (open_name, inverted, outer) = stack
+                .pop()
+                .ok_or_else(|| format!("render: {{{{/{}}}}} without a matching section", name))?;
+            if open_name != name {
+                return Err(format!(
+                    "render: section {} closed by {{{{/{}}}}}",
+                    open_name, name
+                )
+                .into());
+            }
+            let body = std::mem::replace(&mut nodes, outer);
+            nodes.push(Node::Section(open_name, inverted, body));
+        } else {
+            nodes.push(Node::Var(tag.to_string()));
+        }
+    }
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_string()));
+    }
+    match stack.pop() {
+        Some((name, _, _)) => Err(format!("render: section {} is never closed", name).into()),
+        None => Ok(nodes),
+    }
+}
+
+fn get_key(map: &MalValue, key: &str) -> Option<MalValue> {
+    let MalValue::Map(entries) = map else {
+        return None;
+    };
+    entries
+        .get(&MalKey::keyword(key))
+        .or_else(|| entries.get(&MalKey::String(key.to_string())))
+        .cloned()
+}
+
+// Resolves a possibly dotted name against the context stack, innermost first
+fn lookup(contexts: &[MalValue], name: &str) -> Option<MalValue> {
+    if name == "." {
+        return contexts.last().cloned();
+    }
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    let mut value = contexts.iter().rev().find_map(|ctx| get_key(ctx, first))?;
+    for part in parts {
+        value = get_key(&value, part)?;
+    }
+    Some(value)
+}
+
+fn truthy(value: &Option<MalValue>) -> bool {
+    match value {
+        None | Some(MalValue::Nil) | Some(MalValue::Bool(false)) => false,
+        Some(MalValue::Round(items)) | Some(MalValue::Square(items)) => !items.is_empty(),
+        Some(_) => true,
+    }
+}
+
+fn render_nodes(nodes: &[Node], contexts: &mut Vec<MalValue>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => match lookup(contexts, name) {
+                None | Some(MalValue::Nil) => {}
+                Some(value) => out.push_str(&pr_str(&value, false)),
+            },
+            Node::Section(name, inverted, body) => {
+                let value = lookup(contexts, name);
+                if *inverted {
+                    if !truthy(&value) {
+                        render_nodes(body, contexts, out);
+                    }
+                    continue;
+                }
+                if !truthy(&value) {
+                    continue;
+                }
+                let items = match value {
+                    Some(MalValue::Round(items)) | Some(MalValue::Square(items)) => items,
+                    Some(other) => vec![other],
+                    None => Vec::new(),
+                };
+                for item in items {
+                    contexts.push(item);
+                    render_nodes(body, contexts, out);
+                    contexts.pop();
+                }
+            }
+        }
+    }
+}
+
+// (render template data)
+pub fn render(args: &[MalValue]) -> Result<MalValue> {
+    let (template, data) = match args {
+        [MalValue::String(template)] => (template, MalValue::Nil),
+        [MalValue::String(template), data] => (template, data.clone()),
+        _ => return Err("render requires a template string and a data map".into()),
+    };
+    let nodes = parse(template)?;
+    let mut out = String::new();
+    render_nodes(&nodes, &mut vec![data], &mut out);
+    Ok(MalValue::String(out))
+}
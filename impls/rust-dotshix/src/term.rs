@@ -0,0 +1,200 @@
+use crate::error::MalError;
+use crate::output::{write_err, write_out};
+use crate::printer::pr_str;
+use crate::reader::{MalKey, MalMap};
+use crate::MalValue;
+use std::cell::RefCell;
+use std::io::{self, IsTerminal};
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// SGR codes for the styles term/color accepts
+const STYLES: &[(&str, u8)] = &[
+    (":bold", 1),
+    (":dim", 2),
+    (":italic", 3),
+    (":underline", 4),
+    (":black", 30),
+    (":red", 31),
+    (":green", 32),
+    (":yellow", 33),
+    (":blue", 34),
+    (":magenta", 35),
+    (":cyan", 36),
+    (":white", 37),
+    (":gray", 90),
+];
+
+// Honours the NO_COLOR convention (https://no-color.org)
+pub fn color_enabled() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+// (term/color :red "text") or (term/color [:bold :green] "text") returns the
+// text wrapped in ANSI escapes; it prints nothing itself
+pub fn term_color(args: &[MalValue]) -> Result<MalValue> {
+    let (styles, text) = match args {
+        [style @ MalValue::Atom(_), text] => (std::slice::from_ref(style), text),
+        [MalValue::Square(styles) | MalValue::Round(styles), text] => (&styles[..], text),
+        _ => return Err("term/color requires a style keyword or vector and a value".into()),
+    };
+    let text = pr_str(text, false);
+    let codes = styles
+        .iter()
+        .map(|style| {
+            STYLES
+                .iter()
+                .find(|(name, _)| matches!(style, MalValue::Atom(k) if k == name))
+                .map(|(_, code)| code.to_string())
+                .ok_or_else(|| format!("term/color: unknown style {}", pr_str(style, true)).into())
+        })
+        .collect::<Result<Vec<String>>>()?;
+    if !color_enabled() || codes.is_empty() {
+        return Ok(MalValue::String(text));
+    }
+    Ok(MalValue::String(format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)))
+}
+
+pub fn term_clear(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("term/clear takes no arguments".into());
+    }
+    write_out("\x1b[2J\x1b[H")?;
+    Ok(MalValue::Nil)
+}
+
+// (term/move row col) puts the cursor at a 1-based row and column
+pub fn term_move(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(row), MalValue::Number(col)] if *row >= 1 && *col >= 1 => {
+            write_out(&format!("\x1b[{};{}H", row, col))?;
+            Ok(MalValue::Nil)
+        }
+        _ => Err("term/move requires a row and a column, both starting at 1".into()),
+    }
+}
+
+// (tty?) checks stdout; (tty? :stdin) and (tty? :stderr) check the others
+pub fn tty_question(args: &[MalValue]) -> Result<MalValue> {
+    let is_tty = match args {
+        [] => io::stdout().is_terminal(),
+        [MalValue::Atom(k)] if k == ":stdout" => io::stdout().is_terminal(),
+        [MalValue::Atom(k)] if k == ":stdin" => io::stdin().is_terminal(),
+        [MalValue::Atom(k)] if k == ":stderr" => io::stderr().is_terminal(),
+        _ => return Err("tty? takes an optional :stdin, :stdout, or :stderr".into()),
+    };
+    Ok(MalValue::Bool(is_tty))
+}
+
+// Columns and rows of the terminal, asking the terminal first, then the
+// COLUMNS and LINES variables, and finally assuming 80x24
+pub fn terminal_size() -> (i64, i64) {
+    let from_env = |name: &str, default: i64| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &i64| n > 0)
+            .unwrap_or(default)
+    };
+    ioctl_size().unwrap_or_else(|| (from_env("COLUMNS", 80), from_env("LINES", 24)))
+}
+
+#[cfg(unix)]
+fn ioctl_size() -> Option<(i64, i64)> {
+    [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO]
+        .into_iter()
+        .find_map(|fd| {
+            let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+            // SAFETY: TIOCGWINSZ only writes a winsize into the struct we pass
+            let ok = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0;
+            (ok && size.ws_col > 0).then_some((size.ws_col as i64, size.ws_row as i64))
+        })
+}
+
+#[cfg(not(unix))]
+fn ioctl_size() -> Option<(i64, i64)> {
+    None
+}
+
+pub fn term_width(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("term-width takes no arguments".into());
+    }
+    Ok(MalValue::Number(terminal_size().0))
+}
+
+pub fn term_height(args: &[MalValue]) -> Result<MalValue> {
+    if !args.is_empty() {
+        return Err("term-height takes no arguments".into());
+    }
+    Ok(MalValue::Number(terminal_size().1))
+}
+
+// Bars are at most this wide, leaving room for the counts on narrow terminals
+const BAR_WIDTH: i64 = 30;
+
+// A progress bar is an atom holding {:total n :done k}, so scripts can read
+// it like any other state; progress-tick advances it and redraws on stderr
+pub fn progress_bar(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [MalValue::Number(total)] if *total > 0 => {
+            let bar = bar_state(*total, 0);
+            draw_bar(*total, 0)?;
+            Ok(MalValue::Ref(Rc::new(RefCell::new(bar))))
+        }
+        _ => Err("progress-bar requires a positive total".into()),
+    }
+}
+
+fn bar_state(total: i64, done: i64) -> MalValue {
+    MalValue::Map(MalMap::from([
+        (MalKey::keyword("total"), MalValue::Number(total)),
+        (MalKey::keyword("done"), MalValue::Number(done)),
+    ]))
+}
+
+fn draw_bar(total: i64, done: i64) -> Result<()> {
+    let width = BAR_WIDTH.min(terminal_size().0 - 25).max(10);
+    let filled = done * width / total;
+    let line = format!(
+        "\r[{}{}] {:>3}% {}/{}",
+        "#".repeat(filled as usize),
+        ".".repeat((width - filled) as usize),
+        done * 100 / total,
+        done,
+        total
+    );
+    write_err(&line)?;
+    if done == total {
+        write_err("\n")?;
+    }
+    Ok(())
+}
+
+// (progress-tick bar) or (progress-tick bar n); stops at the total
+pub fn progress_tick(args: &[MalValue]) -> Result<MalValue> {
+    let (cell, step) = match args {
+        [MalValue::Ref(cell)] => (cell, 1),
+        [MalValue::Ref(cell), MalValue::Number(n)] if *n >= 0 => (cell, *n),
+        _ => return Err("progress-tick requires a progress bar and an optional step".into()),
+    };
+    let (total, done) = match &*cell.borrow() {
+        MalValue::Map(entries) => match (
+            entries.get(&MalKey::keyword("total")),
+            entries.get(&MalKey::keyword("done")),
+        ) {
+            (Some(MalValue::Number(total)), Some(MalValue::Number(done))) => (*total, *done),
+            _ => return Err("progress-tick: not a progress bar".into()),
+        },
+        _ => return Err("progress-tick: not a progress bar".into()),
+    };
+    if done == total {
+        return Ok(MalValue::Number(done));
+    }
+    let done = done.saturating_add(step).min(total);
+    *cell.borrow_mut() = bar_state(total, done);
+    draw_bar(total, done)?;
+    Ok(MalValue::Number(done))
+}
@@ -0,0 +1,206 @@
+use crate::core::{call, is_callable};
+use crate::error::MalError;
+use crate::host::host_value;
+use crate::printer::pr_str;
+use crate::reader::map_from_entries;
+use crate::MalValue;
+use std::rc::Rc;
+
+use std::result::Result as StdResult;
+type Result<T> = StdResult<T, MalError>;
+
+// A zipper location is a host object of type zip/loc, so it can be kept and
+// passed around like any other value but never needs converting to and from
+// mal data. path is the frame of node's parent, which links to the frames
+// above it; moving only rebuilds the innermost frame, so every location in a
+// walk shares the frames further up.
+const LOC_TAG: &str = "zip/loc";
+
+// The kind of collection a frame's children are rebuilt into on the way up
+#[derive(Clone, Copy)]
+enum Kind {
+    List,
+    Vector,
+    Map,
+}
+
+// node's siblings in order, and where its parent sits in turn
+#[derive(Clone)]
+struct Frame {
+    lefts: Vec<MalValue>,
+    kind: Kind,
+    rights: Vec<MalValue>,
+    up: Option<Rc<Frame>>,
+}
+
+#[derive(Clone)]
+struct Loc {
+    node: MalValue,
+    path: Option<Rc<Frame>>,
+}
+
+// A map's children are its keys and values, alternating
+fn children(node: &MalValue) -> Option<(Kind, Vec<MalValue>)> {
+    match node {
+        MalValue::Round(items) => Some((Kind::List, items.clone())),
+        MalValue::Square(items) => Some((Kind::Vector, items.clone())),
+        MalValue::Map(map) => Some((
+            Kind::Map,
+            map.iter()
+                .flat_map(|(key, value)| [key.clone().into(), value.clone()])
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+// Fails when edits have left a map with a key that can't be a map key
+fn with_children(kind: Kind, items: Vec<MalValue>) -> Result<MalValue> {
+    match kind {
+        Kind::List => Ok(MalValue::Round(items)),
+        Kind::Vector => Ok(MalValue::Square(items)),
+        Kind::Map => map_from_entries(&items)
+            .map(MalValue::Map)
+            .map_err(|e| format!("zip: {}", e).into()),
+    }
+}
+
+fn decode(value: &MalValue, name: &str) -> Result<Loc> {
+    match value {
+        MalValue::Host(object) if object.type_name() == LOC_TAG => object
+            .downcast_ref::<Loc>()
+            .cloned()
+            .ok_or_else(|| "zip: malformed location".into()),
+        _ => Err(format!("{}: {} is not a zipper location", name, pr_str(value, true)).into()),
+    }
+}
+
+fn encode(loc: Loc) -> MalValue {
+    host_value(LOC_TAG, loc)
+}
+
+fn loc_arg(args: &[MalValue], name: &str) -> Result<Loc> {
+    match args {
+        [loc] => decode(loc, name),
+        _ => Err(format!("{} requires exactly one location", name).into()),
+    }
+}
+
+// Moves up one level, rebuilding the parent from node and its siblings
+fn up(loc: Loc) -> Result<Option<Loc>> {
+    let Some(frame) = loc.path else {
+        return Ok(None);
+    };
+    let frame = Rc::unwrap_or_clone(frame);
+    let mut items = frame.lefts;
+    items.push(loc.node);
+    items.extend(frame.rights);
+    Ok(Some(Loc {
+        node: with_children(frame.kind, items)?,
+        path: frame.up,
+    }))
+}
+
+// Moves to the sibling that f takes from the innermost frame, putting node
+// back on the other side; None at the root or when f finds no sibling
+fn sideways(loc: Loc, f: impl FnOnce(&mut Frame, MalValue) -> Option<MalValue>) -> Option<Loc> {
+    let mut frame = Rc::unwrap_or_clone(loc.path?);
+    let node = f(&mut frame, loc.node)?;
+    Some(Loc {
+        node,
+        path: Some(Rc::new(frame)),
+    })
+}
+
+pub fn zip_zipper(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [root] => Ok(encode(Loc {
+            node: root.clone(),
+            path: None,
+        })),
+        _ => Err("zip/zipper requires exactly one root value".into()),
+    }
+}
+
+pub fn zip_node(args: &[MalValue]) -> Result<MalValue> {
+    Ok(loc_arg(args, "zip/node")?.node)
+}
+
+// Moves to the leftmost child of a list, vector, or map; nil at a leaf or an
+// empty collection. The movement functions all return nil when there is
+// nowhere to go.
+pub fn zip_down(args: &[MalValue]) -> Result<MalValue> {
+    let loc = loc_arg(args, "zip/down")?;
+    let Some((kind, mut rights)) = children(&loc.node).filter(|(_, items)| !items.is_empty())
+    else {
+        return Ok(MalValue::Nil);
+    };
+    let first = rights.remove(0);
+    let frame = Frame {
+        lefts: Vec::new(),
+        kind,
+        rights,
+        up: loc.path,
+    };
+    Ok(encode(Loc {
+        node: first,
+        path: Some(Rc::new(frame)),
+    }))
+}
+
+pub fn zip_up(args: &[MalValue]) -> Result<MalValue> {
+    Ok(up(loc_arg(args, "zip/up")?)?.map_or(MalValue::Nil, encode))
+}
+
+pub fn zip_right(args: &[MalValue]) -> Result<MalValue> {
+    let loc = sideways(loc_arg(args, "zip/right")?, |frame, node| {
+        if frame.rights.is_empty() {
+            return None;
+        }
+        frame.lefts.push(node);
+        Some(frame.rights.remove(0))
+    });
+    Ok(loc.map_or(MalValue::Nil, encode))
+}
+
+pub fn zip_left(args: &[MalValue]) -> Result<MalValue> {
+    let loc = sideways(loc_arg(args, "zip/left")?, |frame, node| {
+        let previous = frame.lefts.pop()?;
+        frame.rights.insert(0, node);
+        Some(previous)
+    });
+    Ok(loc.map_or(MalValue::Nil, encode))
+}
+
+pub fn zip_replace(args: &[MalValue]) -> Result<MalValue> {
+    match args {
+        [loc, node] => {
+            let mut loc = decode(loc, "zip/replace")?;
+            loc.node = node.clone();
+            Ok(encode(loc))
+        }
+        _ => Err("zip/replace requires a location and a value".into()),
+    }
+}
+
+// (zip/edit loc f x ...) replaces the node with (f node x ...)
+pub fn zip_edit(args: &[MalValue]) -> Result<MalValue> {
+    let (loc, f, extra) = match args {
+        [loc, f, extra @ ..] if is_callable(f) => (loc, f, extra),
+        _ => return Err("zip/edit requires a location and a function".into()),
+    };
+    let mut loc = decode(loc, "zip/edit")?;
+    let mut call_args = vec![loc.node];
+    call_args.extend(extra.iter().cloned());
+    loc.node = call(f, &call_args)?;
+    Ok(encode(loc))
+}
+
+// Zips all the way up and returns the root with every edit applied
+pub fn zip_root(args: &[MalValue]) -> Result<MalValue> {
+    let mut loc = loc_arg(args, "zip/root")?;
+    while loc.path.is_some() {
+        loc = up(loc)?.expect("a location with a path can move up");
+    }
+    Ok(loc.node)
+}
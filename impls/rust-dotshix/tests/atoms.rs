@@ -0,0 +1,18 @@
+mod common;
+use common::{eval, eval_after};
+
+#[test]
+fn atoms_print_with_their_contents() {
+    assert_eq!(eval("(atom {:a [1]})"), "(atom {:a [1]})");
+    assert_eq!(eval("(def! b (atom 1)) [b b (atom b)]"), "[(atom 1) (atom 1) (atom (atom 1))]");
+    assert_eq!(eval("(def! a (atom [1 2])) `[0 ~@@a]"), "[0 1 2]");
+}
+
+#[test]
+fn an_atom_that_contains_itself_prints_once() {
+    let cycle = "(def! a (atom nil)) (reset! a [1 a])";
+    assert_eq!(eval_after(cycle, "a"), "(atom [1 (atom ...)])");
+    assert_eq!(eval_after(cycle, "(pr-str (clone a))"), "\"(atom [1 (atom ...)])\"");
+    let twice = "[(atom [1 (atom ...)]) (atom [1 (atom ...)])]";
+    assert_eq!(eval_after(cycle, "[a a]"), twice);
+}
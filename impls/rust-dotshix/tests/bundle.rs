@@ -0,0 +1,67 @@
+use rust_dotshix::bundle::{compile, is_bundle, load};
+use rust_dotshix::interpreter::Interpreter;
+use rust_dotshix::printer::pr_str;
+use std::fs;
+use std::process::{Command, Stdio};
+
+const SCRIPT: &str = "(defmacro! unless (fn* [c a b] `(if ~c ~b ~a)))
+(def! show (fn* [x] (unless (nil? x) (str \"got \" x) \"nothing\")))
+(println (show 1) (show nil))
+(prn '(nil true 1 -2.5 \"s\\n\" :k [x] {:a (1)}) ##Inf)";
+
+// The forms a compiled script loads back as, printed readably
+fn round_trip(source: &str) -> Vec<String> {
+    let bundle = compile(&Interpreter::new(), source).expect("compile failed");
+    assert!(is_bundle(&bundle));
+    let forms = load(&bundle).expect("load failed");
+    forms.iter().map(|form| pr_str(&form.value, true)).collect()
+}
+
+#[test]
+fn values_load_back_unchanged() {
+    let source = "'(nil true false 1 -2.5 ##-Inf \"s\\n\" sym :k [x (y)] {:a {:b 1}})";
+    assert_eq!(round_trip(source), [format!("(quote {})", &source[1..])]);
+}
+
+#[test]
+fn macro_calls_are_expanded_ahead_of_time() {
+    let forms = round_trip(SCRIPT);
+    assert_eq!(forms[1], "(def! show (fn* [x] (if (nil? x) \"nothing\" (str \"got \" x))))");
+    // Quoted code is data and stays as written
+    assert_eq!(round_trip("'(unless a b c)"), ["(quote (unless a b c))"]);
+}
+
+#[test]
+fn auto_resolved_keywords_follow_in_ns_while_compiling() {
+    let source = "::a (in-ns 'app) ::a";
+    assert_eq!(round_trip(source), [":user/a", "(in-ns (quote app))", ":app/a"]);
+}
+
+#[test]
+fn damaged_bundles_are_rejected() {
+    let bundle = compile(&Interpreter::new(), "(+ 1 2)").unwrap();
+    assert!(load(&bundle[..bundle.len() - 1]).is_err());
+    assert!(load(b"(+ 1 2)").is_err_and(|e| e.to_string() == "not a compiled script"));
+}
+
+#[test]
+fn compiled_scripts_run_like_their_source() {
+    let dir = std::env::temp_dir().join(format!("rust-dotshix-bundle-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, compiled) = (dir.join("script.mal"), dir.join("script.malc"));
+    fs::write(&source, SCRIPT).unwrap();
+    let run = |args: &[&std::path::Path]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_step7_quote"))
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .expect("failed to run the interpreter");
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    run(&["compile".as_ref(), &source, "-o".as_ref(), &compiled]);
+    let expected = "got 1 nothing\n(nil true 1 -2.5 \"s\\n\" :k [x] {:a (1)}) ##Inf\n";
+    assert_eq!(run(&[&source]), expected);
+    assert_eq!(run(&[&compiled]), expected);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,47 @@
+use rust_dotshix::printer::pr_str;
+use rust_dotshix::reader::{parse_input_with_comments, Spanned};
+
+// A form with its leading comments before it and trailing ones after it, and a
+// collection's children in angle brackets
+fn outline(form: &Spanned) -> String {
+    let mut parts = form.leading_comments.clone();
+    if form.children.is_empty() {
+        parts.push(pr_str(&form.value, true));
+    } else {
+        let children: Vec<String> = form.children.iter().map(outline).collect();
+        parts.push(format!("<{}>", children.join(" ")));
+    }
+    parts.extend(form.trailing_comments.iter().cloned());
+    parts.join(" ")
+}
+
+fn outlines(input: &str) -> Vec<String> {
+    parse_input_with_comments(input).unwrap().iter().map(outline).collect()
+}
+
+#[test]
+fn comments_on_their_own_line_lead_the_next_form() {
+    assert_eq!(outlines("; a\n; b\n(f 1)\nx"), ["; a ; b <f 1>", "x"]);
+    assert_eq!(outlines("(f 1)\n\n; about x\nx"), ["<f 1>", "; about x x"]);
+}
+
+#[test]
+fn comments_after_a_form_on_its_last_line_trail_it() {
+    assert_eq!(outlines("(f 1) ; one\nx ; two"), ["<f 1> ; one", "x ; two"]);
+    assert_eq!(outlines("(f\n 1) ; ends here\nx"), ["<f 1> ; ends here", "x"]);
+    // The comment after the first trails it; the next one leads x
+    assert_eq!(outlines("1 ; one\n; lead\nx"), ["1 ; one", "; lead x"]);
+}
+
+#[test]
+fn comments_inside_collections_attach_to_their_elements() {
+    assert_eq!(outlines("[1 ; one\n ; lead\n 2]"), ["<1 ; one ; lead 2>"]);
+    assert_eq!(outlines("(f ; x\n {:a ; y\n 1})"), ["<f ; x <:a ; y 1>>"]);
+}
+
+#[test]
+fn comments_with_no_form_after_them_stay_comments() {
+    assert_eq!(outlines("x\n; the end"), ["x", "; the end"]);
+    assert_eq!(outlines("[1\n ; dangling\n]"), ["<1 ; dangling>"]);
+    assert_eq!(outlines("; only a comment"), ["; only a comment"]);
+}
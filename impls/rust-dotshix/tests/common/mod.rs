@@ -0,0 +1,44 @@
+// Each test file uses its own subset of these
+#![allow(dead_code)]
+
+use std::process::{Command, Output, Stdio};
+
+// Runs source with -e in a fresh interpreter, after any command-line flags
+pub fn run(flags: &[&str], source: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_step7_quote"))
+        .args(flags)
+        .arg("-e")
+        .arg(source)
+        .stdin(Stdio::null())
+        .output()
+        .expect("failed to run the interpreter")
+}
+
+// Everything source printed, ending with its last value printed readably
+pub fn eval(source: &str) -> String {
+    eval_with(&[], source)
+}
+
+// Like eval, with prelude's definitions in scope for source
+pub fn eval_after(prelude: &str, source: &str) -> String {
+    eval(&format!("{} {}", prelude, source))
+}
+
+pub fn eval_with(flags: &[&str], source: &str) -> String {
+    let output = run(flags, source);
+    assert!(
+        output.status.success(),
+        "{} failed: {}",
+        source,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim_end().to_string()
+}
+
+// The message source fails with
+pub fn error(source: &str) -> String {
+    let output = run(&[], source);
+    assert!(!output.status.success(), "{} succeeded", source);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stderr.trim_end().trim_start_matches("Error: ").to_string()
+}
@@ -0,0 +1,30 @@
+mod common;
+use common::{eval_with, run};
+
+const DETERMINISTIC: &[&str] = &["--deterministic"];
+
+#[test]
+fn runs_repeat_exactly() {
+    let source = "[(rand-int 1000) (rand-int 1000) (time-ms) (do (sleep 5) (time-ms))]";
+    assert_eq!(eval_with(DETERMINISTIC, source), eval_with(DETERMINISTIC, source));
+}
+
+#[test]
+fn rand_int_covers_its_range() {
+    let source = "(sort (distinct (repeatedly 300 (fn* [] (rand-int 3)))))";
+    assert_eq!(eval_with(DETERMINISTIC, source), "(0 1 2)");
+    assert_eq!(eval_with(DETERMINISTIC, "(rand-int 1)"), "0");
+}
+
+#[test]
+fn wall_clock_network_shell_and_env_builtins_are_denied() {
+    for (source, message) in [
+        ("(now)", "now is not available in deterministic mode"),
+        ("(process/spawn [\"date\"] {})", "process/spawn requires the shell permission"),
+        ("(getenv \"HOME\")", "getenv requires the env permission"),
+    ] {
+        let output = run(DETERMINISTIC, source);
+        assert!(!output.status.success(), "{}", source);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message), "{}", source);
+    }
+}
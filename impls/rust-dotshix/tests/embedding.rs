@@ -0,0 +1,180 @@
+use rust_dotshix::interpreter::{call_stack, Interpreter, Permission};
+use rust_dotshix::printer::pr_str;
+use rust_dotshix::reader::{ReaderLimits, MAX_NESTING};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn rep_prints_results_and_errors() {
+    let interpreter = Interpreter::new();
+    assert_eq!(interpreter.rep("(def! x 2) (* x 21)"), "2 42");
+    assert_eq!(interpreter.rep("(+ x 1)"), "3");
+    assert_eq!(interpreter.rep("(nope)"), "Error: Symbol 'nope' not found in environment");
+}
+
+#[test]
+fn malformed_input_is_a_reader_error() {
+    let interpreter = Interpreter::new();
+    for input in ["^{:a 1}", "@", "~@", "{:a}", "\"abc", ")", "(1 (2"] {
+        assert!(interpreter.rep(input).starts_with("Error: "), "{}", input);
+    }
+    assert!(interpreter.rep("99999999999999999999").contains("Number out of range"));
+    assert_eq!(interpreter.rep(""), "");
+    assert_eq!(interpreter.rep("(+ 1 2)"), "3");
+}
+
+#[test]
+fn no_print_hides_only_a_last_result_it_returns() {
+    let interpreter = Interpreter::new();
+    interpreter.rep("42");
+    assert_eq!(interpreter.rep("(no-print)"), "");
+    assert_eq!(interpreter.rep("*1"), "42");
+    assert!(interpreter.rep("(do (no-print) (undefined-sym))").starts_with("Error: "));
+    assert_eq!(interpreter.rep("(+ 1 2)"), "3");
+    interpreter.rep("(def! f (fn* [] (no-print)))");
+    assert_eq!(interpreter.rep("(do (f) 42)"), "42");
+    assert_eq!(interpreter.rep("(f)"), "");
+}
+
+#[test]
+fn each_form_on_a_line_is_read_after_the_one_before_it_runs() {
+    let interpreter = Interpreter::new();
+    assert_eq!(interpreter.rep("(in-ns 'app) ::k"), "app :app/k");
+    assert_eq!(interpreter.rep("::k"), ":app/k");
+    assert!(interpreter.rep("(def! x 1) {:a}").contains("even number of forms"));
+    assert_eq!(interpreter.rep("x"), "1");
+}
+
+#[test]
+fn interpreters_do_not_share_definitions() {
+    let (a, b) = (Interpreter::new(), Interpreter::new());
+    a.rep("(def! x 1)");
+    assert_eq!(a.rep("x"), "1");
+    assert!(b.rep("x").starts_with("Error: "));
+}
+
+#[test]
+fn on_call_sees_each_call_with_its_arguments() {
+    let interpreter = Interpreter::new();
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let seen = Rc::clone(&calls);
+    interpreter.on_call(move |name, args| {
+        let args: Vec<String> = args.iter().map(|arg| pr_str(arg, true)).collect();
+        seen.borrow_mut().push(format!("{} {}", name, args.join(" ")));
+    });
+    interpreter.rep("(def! inc (fn* [n] (+ n 1))) (inc 41)");
+    assert_eq!(*calls.borrow(), ["inc 41", "+ 41 1"]);
+}
+
+#[test]
+fn on_error_and_on_def_report_to_the_embedder() {
+    let interpreter = Interpreter::new();
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let errors = Rc::clone(&events);
+    interpreter.on_error(move |err| errors.borrow_mut().push(format!("error: {}", err)));
+    let defs = Rc::clone(&events);
+    interpreter.on_def(move |symbol, value| {
+        defs.borrow_mut().push(format!("def: {} {}", symbol, pr_str(value, true)));
+    });
+    interpreter.rep("(def! answer 42)");
+    interpreter.rep("(throw \"boom\")");
+    assert_eq!(*events.borrow(), ["def: answer 42", "error: boom"]);
+}
+
+#[test]
+fn revoked_permissions_deny_privileged_builtins() {
+    let interpreter = Interpreter::new();
+    assert!(interpreter.has_permission(Permission::FsRead));
+    interpreter.revoke(Permission::FsRead);
+    assert!(!interpreter.has_permission(Permission::FsRead));
+    assert_eq!(
+        interpreter.rep("(slurp \"Cargo.toml\")"),
+        "Error: Permission denied: slurp requires the fs-read permission"
+    );
+    interpreter.grant(Permission::FsRead);
+    assert!(interpreter.rep("(slurp \"Cargo.toml\")").contains("[package]"));
+}
+
+#[test]
+fn permission_denials_are_ex_info_naming_the_permission() {
+    let interpreter = Interpreter::new();
+    interpreter.revoke(Permission::Net);
+    assert_eq!(
+        interpreter.rep("(try* (http/serve 0 (fn* [r] r)) (catch* e (ex-data e)))"),
+        "{:builtin \"http/serve\" :permission :net :type :permission-denied}"
+    );
+    interpreter.revoke(Permission::Env);
+    assert_eq!(
+        interpreter.rep("(try* (getenv \"PATH\") (catch* e (ex-message e)))"),
+        "\"Permission denied: getenv requires the env permission\""
+    );
+    interpreter.grant(Permission::Env);
+    assert_eq!(interpreter.rep("(string? (getenv \"PATH\"))"), "true");
+    assert_eq!(interpreter.rep("(getenv \"MAL_DOTSHIX_UNSET_VARIABLE\")"), "nil");
+}
+
+#[test]
+fn revoke_all_leaves_pure_computation() {
+    let interpreter = Interpreter::new();
+    interpreter.revoke_all();
+    assert_eq!(interpreter.rep("(+ 1 2)"), "3");
+    assert!(interpreter.rep("(process/spawn \"true\" [])").contains("shell permission"));
+}
+
+#[test]
+fn fuel_limits_each_top_level_evaluation() {
+    let interpreter = Interpreter::new();
+    interpreter.rep("(def! spin (fn* [n] (if (= n 0) :done (spin (- n 1)))))");
+    interpreter.set_fuel(Some(500));
+    assert_eq!(interpreter.rep("(spin 1000)"), "Error: Evaluation budget exhausted");
+    // Each evaluation starts with the full budget, whatever the last one used
+    assert_eq!(interpreter.rep("(spin 10)"), ":done");
+    assert_eq!(interpreter.rep("(spin 10)"), ":done");
+    interpreter.set_fuel(None);
+    assert_eq!(interpreter.rep("(spin 1000)"), ":done");
+}
+
+#[test]
+fn call_hooks_can_read_the_call_stack() {
+    let interpreter = Interpreter::new();
+    let stacks = Rc::new(RefCell::new(Vec::new()));
+    let seen = Rc::clone(&stacks);
+    interpreter.on_call(move |name, _| {
+        if name == "+" {
+            seen.borrow_mut().push(call_stack().join(" > "));
+        }
+    });
+    interpreter.rep("(def! inner (fn* [x] (+ x 1))) (def! outer (fn* [x] (* (inner x) 2)))");
+    interpreter.rep("(outer 1)");
+    interpreter.rep("(+ 1 2)");
+    assert_eq!(*stacks.borrow(), ["outer > inner", ""]);
+}
+
+#[test]
+fn reader_limits_apply_to_the_repl_and_read_string() {
+    let interpreter = Interpreter::new();
+    interpreter.set_reader_limits(ReaderLimits {
+        max_input_bytes: 64,
+        max_nesting: 2,
+        max_string_length: 8,
+        max_forms: 8,
+    });
+    assert_eq!(interpreter.rep("(+ 1 (* 2 3))"), "7");
+    assert!(interpreter.rep("(+ 1 (* 2 (- 3)))").contains("nests more than 2 levels deep"));
+    assert!(interpreter.rep("(read-string \"[[[1]]]\")").contains("nests more than 2"));
+    assert!(interpreter.rep("(str \"abcdefghi\")").contains("String is longer than 8 bytes"));
+    assert!(interpreter.rep("(+ 1 2 3 4 5 6 7 8)").contains("more than 8 forms"));
+    assert!(interpreter.rep(&format!("\"{}\"", " ".repeat(64))).contains("longer than 64"));
+}
+
+#[test]
+fn reader_nesting_is_capped() {
+    let interpreter = Interpreter::new();
+    interpreter.set_reader_limits(ReaderLimits {
+        max_nesting: usize::MAX,
+        ..ReaderLimits::default()
+    });
+    let deep = format!("{}{}", "[".repeat(MAX_NESTING + 1), "]".repeat(MAX_NESTING + 1));
+    let message = format!("nests more than {} levels deep", MAX_NESTING);
+    assert!(interpreter.rep(&deep).contains(&message));
+}
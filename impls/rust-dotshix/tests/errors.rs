@@ -0,0 +1,37 @@
+mod common;
+use common::{eval, run};
+
+#[test]
+fn catch_binds_the_value_as_thrown() {
+    assert_eq!(eval("(try* (throw {:a 1}) (catch* e e))"), "{:a 1}");
+    assert_eq!(eval("(try* (throw nil) (catch* e [e]))"), "[nil]");
+    assert_eq!(eval("(try* (try* (throw [1]) (catch* e (throw e))) (catch* e e))"), "[1]");
+    assert_eq!(eval("(try* (try* (throw :k)) (catch* e e))"), ":k");
+    assert_eq!(eval("(try* (map (fn* [x] (throw x)) [7]) (catch* e e))"), "7");
+}
+
+#[test]
+fn thrown_values_and_errors_with_the_same_message_stay_apart() {
+    let message = "\"Symbol 'x' not found in environment\"";
+    assert_eq!(
+        eval(&format!("(try* (throw {}) (catch* e (string? e)))", message)),
+        "true"
+    );
+    // An error whose text matches an earlier thrown value's message binds the
+    // message, not that value
+    assert_eq!(
+        eval("(try* (throw (ex-info \"Symbol 'x' not found in environment\" {})) (catch* _ nil)) \
+              (try* x (catch* e e))"),
+        message
+    );
+    assert_eq!(eval("(try* (nope) (catch* e e))"), "\"Symbol 'nope' not found in environment\"");
+}
+
+#[test]
+fn uncaught_throws_report_their_message() {
+    let output = run(&[], "(throw {:a 1})");
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim_end(), "Error: {:a 1}");
+    let output = run(&[], "(nope)");
+    assert_ne!(output.status.code(), Some(3));
+}
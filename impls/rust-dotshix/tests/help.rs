@@ -0,0 +1,34 @@
+mod common;
+use common::{error, eval, eval_after};
+
+#[test]
+fn apropos_matches_names_and_docs_as_regular_expressions() {
+    assert_eq!(eval("(apropos \"^zip/(up|down)$\")"), "(zip/down zip/up)");
+    assert_eq!(eval("(apropos \"BYTE ARRAY\")"), eval("(apropos \"byte array\")"));
+    assert!(eval("(apropos \"byte array\")").contains("slurp-bytes"));
+    // Special forms and top-level definitions are found too
+    let found = eval_after("(def! my-letter 1)", "(apropos \"^let|letter\")");
+    assert_eq!(found, "(let* letrec* my-letter)");
+    assert!(error("(apropos \"(\")").starts_with("apropos: regex parse error"));
+    assert_eq!(error("(apropos 'x)"), "apropos requires a string");
+}
+
+#[test]
+fn apropos_is_a_function() {
+    assert_eq!(eval("(map count (map apropos [\"^zip/up$\" \"^zip/(up|down)$\"]))"), "(1 2)");
+}
+
+#[test]
+fn completions_describe_each_top_level_name() {
+    let defs = "(def! my-fn (fn* [] 1)) (def! my-val 2) (defmacro! my-mac (fn* [] 1))";
+    assert_eq!(
+        eval_after(defs, "(map (fn* [c] [(:name c) (:kind c) (:doc c)]) (completions \"my-\"))"),
+        "([\"my-fn\" :function nil] [\"my-mac\" :macro nil] [\"my-val\" :value nil])"
+    );
+    assert_eq!(
+        eval("(first (completions \"bytes?\"))"),
+        "{:doc \"True if the argument is a byte array\" :kind :builtin :name \"bytes?\"}"
+    );
+    assert_eq!(eval("(:kind (first (completions \"if\")))"), ":special-form");
+    assert_eq!(eval("(= (count (completions)) (count (completions \"\")))"), "true");
+}
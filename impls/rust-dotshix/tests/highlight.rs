@@ -0,0 +1,68 @@
+use rust_dotshix::highlight::highlight;
+use rust_dotshix::reader::{tokenize, TokenKind};
+
+// Each token's kind and text
+fn tokens(input: &str) -> Vec<(TokenKind, &str)> {
+    tokenize(input)
+        .into_iter()
+        .map(|token| (token.kind, &input[token.span.start..token.span.end]))
+        .collect()
+}
+
+#[test]
+fn tokenize_classifies_like_the_reader() {
+    use TokenKind::*;
+    assert_eq!(
+        tokens("(def! x [1 2.5 ##Inf]) ; note"),
+        [
+            (Open, "("),
+            (Symbol, "def!"),
+            (Symbol, "x"),
+            (Open, "["),
+            (Number, "1"),
+            (Number, "2.5"),
+            (Number, "##Inf"),
+            (Close, "]"),
+            (Close, ")"),
+            (Comment, "; note"),
+        ]
+    );
+    assert_eq!(
+        tokens("'{:a \"s\", :b nil} @x ~@y true"),
+        [
+            (ReaderMacro, "'"),
+            (Open, "{"),
+            (Keyword, ":a"),
+            (String, "\"s\""),
+            (Keyword, ":b"),
+            (Nil, "nil"),
+            (Close, "}"),
+            (ReaderMacro, "@"),
+            (Symbol, "x"),
+            (ReaderMacro, "~@"),
+            (Symbol, "y"),
+            (Boolean, "true"),
+        ]
+    );
+}
+
+#[test]
+fn tokenize_never_fails_on_unfinished_input() {
+    use TokenKind::*;
+    assert_eq!(
+        tokens("(+ 1 (f"),
+        [(Open, "("), (Symbol, "+"), (Number, "1"), (Open, "("), (Symbol, "f")]
+    );
+    assert_eq!(tokens("))"), [(Close, ")"), (Close, ")")]);
+    assert_eq!(tokens("(str \"abc"), [(Open, "("), (Symbol, "str"), (Invalid, "\"abc")]);
+    assert!(tokens("").is_empty());
+}
+
+#[test]
+fn highlight_colors_tokens_and_keeps_the_text() {
+    assert_eq!(
+        highlight("(str \"a\"  :k, 4 nil)"),
+        "(str \x1b[32m\"a\"\x1b[0m  \x1b[35m:k\x1b[0m, \x1b[36m4\x1b[0m \x1b[33mnil\x1b[0m)"
+    );
+    assert_eq!(highlight("(f x)"), "(f x)");
+}
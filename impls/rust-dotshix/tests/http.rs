@@ -0,0 +1,121 @@
+#![cfg(feature = "stdlib")]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+// Echoes the request back, except for the paths that make the handler fail
+const HANDLER: &str = r#"(fn* [r]
+  (cond (= (get r :path) "/throw") (throw "boom")
+        (= (get r :path) "/newline") {:headers {"x-bad" "a\nb"}}
+        :else {:status 201
+               :headers {"x-method" (get r :method)}
+               :body (str (get r :path) "?" (get r :query) " "
+                          (get (get r :headers) "x-name") " " (get r :body))}))"#;
+
+fn serve(requests: &[Vec<u8>]) -> (Vec<String>, Output) {
+    serve_with(&[], Duration::ZERO, requests)
+}
+
+// Serves each request in turn with HANDLER, after the server has idled for
+// idle, returning the raw responses and the interpreter's output once it has
+// stopped after the last one
+fn serve_with(flags: &[&str], idle: Duration, requests: &[Vec<u8>]) -> (Vec<String>, Output) {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let source = format!(
+        "(http/serve {} {} {{:max-requests {}}})",
+        port,
+        HANDLER,
+        requests.len()
+    );
+    let server = Command::new(env!("CARGO_BIN_EXE_step7_quote"))
+        .args(flags)
+        .arg("-e")
+        .arg(source)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run the interpreter");
+    thread::sleep(idle);
+    let responses = requests.iter().map(|request| send(port, request)).collect();
+    (responses, server.wait_with_output().unwrap())
+}
+
+fn send(port: u16, request: &[u8]) -> String {
+    let mut stream = (0..500)
+        .find_map(|_| {
+            TcpStream::connect(("127.0.0.1", port))
+                .map_err(|_| thread::sleep(Duration::from_millis(10)))
+                .ok()
+        })
+        .expect("the server never started");
+    stream.write_all(request).unwrap();
+    let mut response = Vec::new();
+    // The server may close without reading the rest of a request it refused
+    let _ = stream.read_to_end(&mut response);
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+fn get(headers: &str) -> Vec<u8> {
+    format!("GET /echo HTTP/1.1\r\n{}\r\n", headers).into_bytes()
+}
+
+#[test]
+fn requests_reach_the_handler_and_its_response_is_sent() {
+    let request = b"POST /items?a=1 HTTP/1.1\r\nX-Name: ada\r\nContent-Length: 5\r\n\r\nhello";
+    let (responses, output) = serve(&[request.to_vec()]);
+    assert_eq!(
+        responses[0],
+        "HTTP/1.1 201 Created\r\nx-method: POST\r\nContent-Length: 20\r\n\
+         Connection: close\r\n\r\n/items?a=1 ada hello"
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "nil");
+}
+
+#[test]
+fn malformed_requests_are_refused_with_400() {
+    let long = get(&format!("X-Long: {}\r\n", "a".repeat(9000)));
+    let many = get(&"X-A: 1\r\n".repeat(101));
+    let length = get("Content-Length: ten\r\n");
+    let (responses, output) = serve(&[long, many, length, get("X-Name: ok\r\n")]);
+    for (response, message) in responses.iter().zip([
+        "request line or header too long",
+        "too many headers",
+        "bad content-length: ten",
+    ]) {
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+        assert!(response.ends_with(message), "{}", response);
+    }
+    // The server carries on after each one
+    assert!(responses[3].ends_with("/echo? ok "), "{}", responses[3]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("http/serve: too many headers"), "{}", stderr);
+    assert!(output.status.success());
+}
+
+#[test]
+fn handler_errors_and_unsendable_responses_are_500() {
+    let request = |path: &str| format!("GET {} HTTP/1.1\r\n\r\n", path).into_bytes();
+    let (responses, output) = serve(&[request("/throw"), request("/newline"), request("/ok")]);
+    for response in &responses[..2] {
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "{}", response);
+        assert!(!response.contains("x-bad"), "{}", response);
+    }
+    assert!(responses[2].starts_with("HTTP/1.1 201 Created\r\n"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("http/serve: boom"), "{}", stderr);
+    assert!(stderr.contains("http/serve: invalid response header x-bad"), "{}", stderr);
+    assert!(output.status.success());
+}
+
+#[test]
+fn waiting_for_a_request_uses_no_fuel() {
+    let request = b"GET /idle HTTP/1.1\r\n\r\n".to_vec();
+    let (responses, output) = serve_with(&["--fuel=250"], Duration::from_secs(3), &[request]);
+    assert!(responses[0].ends_with("/idle? nil "), "{}", responses[0]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,18 @@
+mod common;
+use common::{error, eval};
+
+#[test]
+fn namespaced_keywords_split_into_namespace_and_name() {
+    let source = "[(namespace :app/id) (name :app/id) (namespace :id)]";
+    assert_eq!(eval(source), "[\"app\" \"id\" nil]");
+    assert_eq!(eval("::id"), ":user/id");
+    assert!(error("::a/b").contains("Invalid keyword: ::a/b"));
+}
+
+#[test]
+fn auto_resolved_keywords_follow_an_earlier_in_ns() {
+    let source = "(in-ns 'app.events) [::id '::id {::id 1}]";
+    assert_eq!(eval(source), "[:app.events/id :app.events/id {:app.events/id 1}]");
+    assert_eq!(eval("[::a (in-ns 'b) ::a]"), "[:user/a b :user/a]");
+    assert_eq!(eval("(in-ns 'b) (def! k ::a) (in-ns 'c) [k ::a]"), "[:b/a :c/a]");
+}
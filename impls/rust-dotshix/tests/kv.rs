@@ -0,0 +1,62 @@
+mod common;
+use common::{error, eval};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A store file of its own for each test, not there yet
+fn store_path(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-dotshix-kv-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.edn", test));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+// Source that opens the store at path as s, then runs body
+fn with_store(path: &Path, body: &str) -> String {
+    format!("(def! s (kv/open {:?})) {}", path.to_str().unwrap(), body)
+}
+
+#[test]
+fn stores_persist_across_runs() {
+    let path = store_path("persist");
+    let first = "(kv/put s :a [1 \"x\" {:b nil}]) (kv/put s \"k\" 2.5) (kv/put s 3 'sym)";
+    assert_eq!(eval(&with_store(&path, &format!("{} (kv/keys s)", first))), "(\"k\" :a 3)");
+    let second = "(kv/delete s 3) [(kv/get s :a) (kv/get s \"k\") (kv/get s 3 :gone) (kv/keys s)]";
+    assert_eq!(eval(&with_store(&path, second)), "[[1 \"x\" {:b nil}] 2.5 :gone (\"k\" :a)]");
+    assert_eq!(fs::read_to_string(&path).unwrap(), "{\"k\" 2.5 :a [1 \"x\" {:b nil}]}");
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn values_the_file_cannot_hold_are_refused() {
+    let path = store_path("refused");
+    for (put, message) in [
+        ("(kv/put s [1] 2)", "kv/put: [1] cannot be used as a map key or set member"),
+        ("(kv/put s :a (fn* [] 1))", "kv/put: Cannot write value as EDN: <#function>"),
+        ("(kv/put s :a [(bytes 1)])", "kv/put: Cannot write value as EDN: #bytes[1]"),
+        ("(kv/put s :a (atom 1))", "kv/put: Cannot write value as EDN: (atom 1)"),
+    ] {
+        assert_eq!(error(&with_store(&path, put)), message);
+        // Neither the store in memory nor its file changes
+        let refused = format!("(try* {} (catch* e nil)) [(kv/get s :a) (kv/keys s)]", put);
+        assert_eq!(eval(&with_store(&path, &refused)), "[nil ()]");
+        assert!(!path.exists());
+    }
+}
+
+#[test]
+fn a_file_that_is_not_an_edn_map_does_not_open() {
+    let path = store_path("corrupt");
+    let open = format!("(kv/open {:?})", path.to_str().unwrap());
+    fs::write(&path, "{:a 1").unwrap();
+    assert!(error(&open).starts_with(&format!("kv/open: {}: ", path.display())));
+    fs::write(&path, "[1 2]").unwrap();
+    assert_eq!(error(&open), format!("kv/open: {} does not contain a map", path.display()));
+    fs::write(&path, "{:a 1} {:b 2}").unwrap();
+    assert!(error(&open).ends_with("edn/read expects a single value"));
+    // An empty file is an empty store
+    fs::write(&path, "").unwrap();
+    assert_eq!(eval(&with_store(&path, "(kv/keys s)")), "()");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,48 @@
+use rust_dotshix::interpreter::Interpreter;
+use rust_dotshix::reader::MalValue;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+// Binds probe to a fresh atom and returns a weak handle on it, which stops
+// upgrading once nothing in the interpreter holds the atom
+fn probe(interpreter: &Interpreter) -> Weak<RefCell<MalValue>> {
+    let atom = Rc::new(RefCell::new(MalValue::Nil));
+    interpreter.env().borrow().set("probe".to_string(), MalValue::Ref(Rc::clone(&atom)));
+    Rc::downgrade(&atom)
+}
+
+// Runs source, which must not return anything holding the probe, then unbinds
+// probe; returns whether the atom was freed
+fn freed_after(source: &str) -> bool {
+    let interpreter = Interpreter::new();
+    let probe = probe(&interpreter);
+    assert!(!interpreter.rep(source).starts_with("Error: "), "{}", source);
+    interpreter.rep("(def! probe nil)");
+    probe.upgrade().is_none()
+}
+
+const EVEN_ODD: &str = "[p probe
+                         even? (fn* [n] (if (= n 0) p (odd? (- n 1))))
+                         odd? (fn* [n] (if (= n 0) false (even? (- n 1))))]";
+
+#[test]
+fn letrec_environments_are_freed_when_no_function_escapes() {
+    assert!(freed_after(&format!("(letrec* {} (even? 10) nil)", EVEN_ODD)));
+    assert!(freed_after(&format!("(try* (letrec* {} (throw 1)) (catch* e nil))", EVEN_ODD)));
+    assert!(freed_after("(try* (letrec* [p probe f (fn* [] p) x (throw 1)] 2) (catch* e nil))"));
+}
+
+#[test]
+fn an_escaped_letrec_function_leaks_only_its_own_environment() {
+    assert!(!freed_after(&format!("(def! f (letrec* {} even?)) (def! f nil)", EVEN_ODD)));
+    // What its calls bind is still freed
+    let escaped = "(def! f (letrec* [f (fn* [x n] (if (= n 0) nil (f x (- n 1))))] f))";
+    assert!(freed_after(&format!("{} (f probe 50) nil", escaped)));
+}
+
+#[test]
+fn named_functions_do_not_hold_themselves() {
+    assert!(freed_after("(let* [p probe] (fn* f [n] (if (= n 0) p (f (- n 1)))) nil)"));
+    let called = "(let* [p probe] ((fn* f [n] (if (= n 0) p (f (- n 1)))) 5) nil)";
+    assert!(freed_after(called));
+}
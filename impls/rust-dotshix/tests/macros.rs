@@ -0,0 +1,54 @@
+mod common;
+use common::{error, eval, eval_after};
+
+const UNLESS: &str = "(defmacro! unless (fn* [c a b] `(if ~c ~b ~a)))";
+
+#[test]
+fn macros_receive_their_arguments_unevaluated() {
+    assert_eq!(eval_after(UNLESS, "[(unless false 1 2) (unless true 1 2)]"), "[1 2]");
+    assert_eq!(eval_after(UNLESS, "(unless true (undefined-fn) :ok)"), ":ok");
+    assert_eq!(eval_after(UNLESS, "(macroexpand (unless x 1 2))"), "(if x 2 1)");
+}
+
+#[test]
+fn expansions_expand_again_until_no_macro_call_is_left() {
+    let source = "(defmacro! m1 (fn* [] `(m2))) (defmacro! m2 (fn* [] 42)) (m1)";
+    assert_eq!(eval(source), "42");
+    assert_eq!(eval("[(cond false 1 nil 2 :else 3) (cond)]"), "[3 nil]");
+}
+
+#[test]
+fn expansions_are_evaluated_like_written_code() {
+    let twice = "(defmacro! twice (fn* [x] `(do ~x ~x)))";
+    assert_eq!(eval_after(twice, "(def! n (atom 0)) (twice (swap! n inc)) @n"), "2");
+}
+
+const COUNTED: &str = "(def! c (atom 0)) (defmacro! m (fn* [x] (do (swap! c inc) x)))";
+
+#[test]
+fn a_call_form_expands_once_per_macro_definition() {
+    let source = "(def! f (fn* [] (m :f))) [(f) (f) (m 1) (m 1) (m 2) @c]";
+    assert_eq!(eval_after(COUNTED, source), "[:f :f 1 1 2 3]");
+}
+
+#[test]
+fn redefining_a_macro_drops_its_cached_expansions() {
+    let prelude = format!("{} (def! f (fn* [] (m :old))) (f)", COUNTED);
+    let redefined = "(defmacro! m (fn* [x] :new)) [(f) (m :old) @c]";
+    assert_eq!(eval_after(&prelude, redefined), "[:new :new 1]");
+    assert_eq!(eval_after(&prelude, "(def! m (fn* [x] [:fn x])) (f)"), "[:fn :old]");
+    // A local binding is a different definition, so it doesn't see the cached expansion
+    let shadowed = "(let* [m (fn* [x] :local)] (m :old))";
+    assert_eq!(eval_after(&prelude, shadowed), ":local");
+}
+
+#[test]
+fn local_bindings_shadow_macros() {
+    let source = "(let* [unless (fn* [a b c] :shadowed)] (unless 1 2 3))";
+    assert_eq!(eval_after(UNLESS, source), ":shadowed");
+}
+
+#[test]
+fn defmacro_requires_a_function() {
+    assert_eq!(error("(defmacro! bad 1)"), "defmacro!: 1 is not a fn* function");
+}
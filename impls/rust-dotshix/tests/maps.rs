@@ -0,0 +1,38 @@
+mod common;
+use common::{error, eval};
+
+#[test]
+fn literals_read_into_maps_ordered_by_key() {
+    assert_eq!(eval("{:b 2 :a 1}"), "{:a 1 :b 2}");
+    assert_eq!(eval("{:a 1 :a 2}"), "{:a 2}");
+    assert_eq!(eval("{2 :n \"s\" 1}"), "{\"s\" 1 2 :n}");
+    assert_eq!(eval("[(count {:a 1 :b 2}) (keys {}) (vals {:a 1})]"), "[2 () (1)]");
+}
+
+#[test]
+fn literal_keys_and_values_are_evaluated() {
+    assert_eq!(eval("{:a (+ 1 2)}"), "{:a 3}");
+    assert_eq!(eval("(let* [k :x] {k 1})"), "{:x 1}");
+}
+
+#[test]
+fn malformed_literals_are_reader_errors() {
+    assert!(error("{:a}").contains("Map literal must contain an even number of forms"));
+    assert!(error("{[1] 2}").contains("[1] cannot be used as a map key or set member"));
+}
+
+#[test]
+fn equality_ignores_entry_order() {
+    assert_eq!(eval("(= {:a 1 :b 2} {:b 2 :a 1})"), "true");
+    assert_eq!(eval("[(= {:a 1} {:a 2}) (= {:a 1} {:a 1 :b 2})]"), "[false false]");
+}
+
+#[test]
+fn builtins_read_and_update_maps() {
+    assert_eq!(eval("(assoc {:a 1} :b 2 :a 3)"), "{:a 3 :b 2}");
+    assert_eq!(eval("(dissoc {:a 1 :b 2} :a :c)"), "{:b 2}");
+    assert_eq!(eval("[(get {:a 1} :a) (get {:a 1} :b :none)]"), "[1 :none]");
+    assert_eq!(eval("[(contains? {:a nil} :a) (:a {:a 5}) ({:a 5} :a)]"), "[true 5 5]");
+    assert_eq!(eval("(hash-map :b 2 :a 1)"), "{:a 1 :b 2}");
+    assert_eq!(error("(hash-map :a 1 :b)"), "hash-map requires an even number of arguments");
+}
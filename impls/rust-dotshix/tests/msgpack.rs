@@ -0,0 +1,30 @@
+#![cfg(feature = "stdlib")]
+
+mod common;
+use common::{error, eval};
+
+// Bytes for n single-element arrays nested around nil
+fn nested_arrays(n: usize) -> String {
+    format!("(bytes {} 192)", vec!["145"; n].join(" "))
+}
+
+#[test]
+fn values_round_trip() {
+    let value = "[1 -300 \"s\" :k 'sym nil true {:a [1.5 (tagged \"t\" [2])]} (bytes 0 255)]";
+    assert_eq!(
+        eval(&format!("(msgpack/decode (msgpack/encode {}))", value)),
+        "[1 -300 \"s\" :k sym nil true {:a [1.5 #t [2]]} #bytes[0 255]]"
+    );
+}
+
+#[test]
+fn decoding_stops_at_the_reader_nesting_limit() {
+    assert_eq!(eval(&format!("(count (msgpack/decode {}))", nested_arrays(256))), "1");
+    assert_eq!(
+        error(&format!("(msgpack/decode {})", nested_arrays(257))),
+        "MessagePack value nests more than 256 levels deep"
+    );
+    // Sets and tagged values nest through their extension payloads
+    let tagged = "(msgpack/encode (tagged \"t\" (tagged \"u\" 1)))";
+    assert_eq!(eval(&format!("(msgpack/decode {})", tagged)), "#t #u 1");
+}
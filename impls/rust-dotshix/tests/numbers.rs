@@ -0,0 +1,92 @@
+mod common;
+
+use common::{error, eval, eval_with};
+
+#[test]
+fn integers_stay_integers() {
+    assert_eq!(eval("(+ 1 2)"), "3");
+    assert_eq!(eval("(* 4 -3)"), "-12");
+    assert_eq!(eval("(- 10 4 3)"), "3");
+    assert_eq!(eval("(quot 7 2)"), "3");
+}
+
+#[test]
+fn a_float_operand_makes_a_float() {
+    assert_eq!(eval("(+ 1 2.5)"), "3.5");
+    assert_eq!(eval("(* 2 1.5)"), "3.0");
+    assert_eq!(eval("(- 1.5 1)"), "0.5");
+    assert_eq!(eval("(+ 0.5 0.25)"), "0.75");
+    assert_eq!(eval("(quot 7.5 2)"), "3.0");
+}
+
+#[test]
+fn integer_division_truncates_unless_float_division_is_set() {
+    assert_eq!(eval("(/ 7 2)"), "3");
+    assert_eq!(eval("(/ -7 2)"), "-3");
+    assert_eq!(eval("(/ 7 2.0)"), "3.5");
+    assert_eq!(eval("(/ 7.0 2)"), "3.5");
+    assert_eq!(eval_with(&["--float-division"], "(/ 7 2)"), "3.5");
+    assert_eq!(eval_with(&["--float-division"], "(/ 6 2)"), "3");
+}
+
+#[test]
+fn integer_errors_are_reported() {
+    assert_eq!(error("(/ 1 0)"), "Division by 0");
+    assert_eq!(error("(+ 9223372036854775807 1)"), "Integer overflow in add");
+}
+
+#[test]
+fn float_edge_cases_print_readably() {
+    assert_eq!(eval("(/ 1.0 0)"), "##Inf");
+    assert_eq!(eval("(- (/ 1.0 0))"), "##-Inf");
+    assert_eq!(eval("(/ 0.0 0.0)"), "##NaN");
+    assert_eq!(eval("1e3"), "1000.0");
+    assert_eq!(eval("2.5E-1"), "0.25");
+}
+
+#[test]
+fn comparisons_span_integers_and_floats() {
+    assert_eq!(eval("(< 1 1.5 2)"), "true");
+    assert_eq!(eval("(<= 1 1.0 2)"), "true");
+    assert_eq!(eval("(> 2.5 2)"), "true");
+    assert_eq!(eval("(>= 2 2.5)"), "false");
+    assert_eq!(eval("(< 1 (/ 0.0 0.0))"), "false");
+    assert_eq!(eval("(= 1 1.0)"), "false");
+}
+
+#[test]
+fn integers_compare_exactly_beyond_float_precision() {
+    assert_eq!(eval("(< 9007199254740992 9007199254740993)"), "true");
+    assert_eq!(eval("(> 9007199254740993 9007199254740992)"), "true");
+    assert_eq!(eval("(<= 9007199254740993 9007199254740992)"), "false");
+    assert_eq!(eval("(max 9007199254740992 9007199254740993)"), "9007199254740993");
+    assert_eq!(eval("(min 9007199254740993 9007199254740992)"), "9007199254740992");
+}
+
+#[test]
+fn min_and_max_return_the_winning_argument() {
+    assert_eq!(eval("(max 1 2.5 2)"), "2.5");
+    assert_eq!(eval("(min 1 2.5)"), "1");
+    assert_eq!(eval("(max 3)"), "3");
+    assert_eq!(eval("(max 1 (/ 0.0 0.0))"), "1");
+}
+
+#[test]
+fn non_finite_floats_read_back() {
+    assert_eq!(eval("[##Inf ##-Inf ##NaN]"), "[##Inf ##-Inf ##NaN]");
+    assert_eq!(eval("(float? (read-string \"##Inf\"))"), "true");
+    assert_eq!(eval("(= (read-string (pr-str (/ -1.0 0))) (/ -1.0 0))"), "true");
+}
+
+#[test]
+fn floats_round_trip_through_edn() {
+    assert_eq!(eval("(edn/write 1.5)"), "\"1.5\"");
+    assert_eq!(eval("(edn/read \"[1.5 2e3 ##Inf 1.25M]\")"), "[1.5 2000.0 ##Inf 1.25]");
+}
+
+#[cfg(feature = "stdlib")]
+#[test]
+fn floats_round_trip_through_msgpack() {
+    assert_eq!(eval("(msgpack/decode (msgpack/encode [1.5 -2.25 ##-Inf]))"), "[1.5 -2.25 ##-Inf]");
+    assert_eq!(eval("(msgpack/decode (bytes 202 63 192 0 0))"), "1.5");
+}
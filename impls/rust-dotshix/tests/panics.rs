@@ -0,0 +1,35 @@
+use rust_dotshix::env::Function;
+use rust_dotshix::error::MalError;
+use rust_dotshix::interpreter::Interpreter;
+use rust_dotshix::reader::MalValue;
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+fn boom(_: &[MalValue]) -> Result<MalValue, MalError> {
+    panic!("boom")
+}
+
+// The panic hook is global, so this file has a single test
+#[test]
+fn panics_in_builtins_are_errors_that_print_nothing() {
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&reported);
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        seen.lock().unwrap().push(info.to_string());
+        report(info);
+    }));
+
+    let interpreter = Interpreter::new();
+    let builtin = MalValue::BuiltinFunction(Function::Builtin(boom));
+    interpreter.env().borrow().set("boom".to_string(), builtin);
+    assert_eq!(interpreter.rep("(boom)"), "Error: Internal error in builtin: boom");
+    let caught = "(try* (if (boom) 1 2) (catch* e (ex-message e)))";
+    assert_eq!(interpreter.rep(caught), "\"Internal error in builtin: boom\"");
+    assert_eq!(interpreter.rep("(+ 1 2)"), "3");
+    assert!(reported.lock().unwrap().is_empty());
+
+    // A panic nothing catches still reaches the hook
+    let _ = panic::catch_unwind(|| panic!("elsewhere"));
+    assert_eq!(reported.lock().unwrap().len(), 1);
+}
@@ -0,0 +1,36 @@
+mod common;
+use common::{error, eval, eval_after};
+use rust_dotshix::interpreter::Interpreter;
+
+const CLASSIFY: &str = "(def! classify (fn* [v] (match v
+    [a b] (+ a b)
+    [x & more] :when (> x 10) more
+    {:k x} x
+    'q :quoted
+    _ :other)))";
+
+#[test]
+fn clauses_match_in_order() {
+    let classified = "(map classify [[1 2] '(3 4) {:k 5 :j 6} 'q 7])";
+    assert_eq!(eval_after(CLASSIFY, classified), "(3 7 5 :quoted :other)");
+    let classified = "(map classify [[11 1 2] [1 1 2] [] {:j 1}])";
+    assert_eq!(eval_after(CLASSIFY, classified), "((1 2) :other :other :other)");
+    assert_eq!(eval("(match [1 1] [x x] :same [x y] :different)"), ":same");
+    assert_eq!(eval("(match [1 2] [x x] :same [x y] :different)"), ":different");
+}
+
+#[test]
+fn malformed_patterns_are_reported_before_matching() {
+    assert_eq!(error("(match 1 _ :any (f x) :never)"), "match: unsupported pattern (f x)");
+    assert_eq!(error("(match 1 x)"), "match: pattern without a result");
+    assert_eq!(error("(match 3 [x] x)"), "match: no clause matched 3");
+}
+
+#[test]
+fn each_form_keeps_its_own_patterns() {
+    let interpreter = Interpreter::new();
+    for _ in 0..50 {
+        assert_eq!(interpreter.rep("(match 'x (quote x) :literal _ :other)"), ":literal");
+        assert!(interpreter.rep("(match 'x [quote x] :literal _ :other)").contains("quote"));
+    }
+}
@@ -0,0 +1,24 @@
+mod common;
+use common::{error, eval};
+
+#[test]
+fn print_readably_switches_prn_and_the_echo() {
+    assert_eq!(eval("(prn \"a\\nb\") \"c\""), "\"a\\nb\"\n\"c\"");
+    assert_eq!(eval("(def! *print-readably* false) (prn \"a\") \"c\""), "a\nc");
+}
+
+#[test]
+fn binding_sets_a_var_only_while_its_body_runs() {
+    let show = "(def! show (fn* [] (pr-str \"s\")))";
+    let source = format!("{} [(binding [*print-readably* false] (show)) (show)]", show);
+    assert_eq!(eval(&source), "[\"s\" \"\\\"s\\\"\"]");
+    // A local of the same name is lexical, so printing doesn't see it
+    assert_eq!(eval("(let* [*print-readably* false] (pr-str \"s\"))"), "\"\\\"s\\\"\"");
+}
+
+#[test]
+fn binding_restores_vars_when_its_body_fails() {
+    let source = "(def! x 1) (try* (binding [x 2] (throw x)) (catch* e [e x]))";
+    assert_eq!(eval(source), "[2 1]");
+    assert_eq!(error("(binding [undefined-var 1] 2)"), "binding: undefined-var is not defined");
+}
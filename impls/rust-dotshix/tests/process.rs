@@ -0,0 +1,36 @@
+mod common;
+use common::{eval, eval_after};
+use std::time::{Duration, Instant};
+
+// log collects the lines the callbacks made by note see
+const LOG: &str = "(def! log (atom [])) (def! note (fn* [k] (fn* [l] (swap! log conj [k l]))))";
+
+#[test]
+fn output_lines_reach_their_callbacks() {
+    let spawn = "(def! p (process/spawn \"echo a; echo b >&2; echo c\"
+                   {:on-stdout (note :out) :on-stderr (note :err)}))
+                 [(process/wait p) (sort @log)]";
+    assert_eq!(eval_after(LOG, spawn), "[0 ([:err \"b\"] [:out \"a\"] [:out \"c\"])]");
+    // Lines from one stream arrive in order
+    let ordered = "(process/wait (process/spawn [\"seq\" \"5\"] {:on-stdout (note :n)})) @log";
+    let lines = "[[:n \"1\"] [:n \"2\"] [:n \"3\"] [:n \"4\"] [:n \"5\"]]";
+    assert_eq!(eval_after(LOG, ordered), lines);
+}
+
+#[test]
+fn wait_returns_the_exit_code() {
+    assert_eq!(eval("(process/wait (process/spawn \"exit 3\"))"), "3");
+    let twice = "(def! p (process/spawn [\"true\"])) [(process/wait p) (process/wait p)]";
+    assert_eq!(eval(twice), "[0 0]");
+    assert_eq!(eval("(process/wait (process/spawn \"kill -9 $$\"))"), "nil");
+}
+
+#[test]
+fn a_throwing_callback_kills_the_child() {
+    let spawn = "(def! p (process/spawn \"echo started; exec sleep 30\"
+                   {:on-stdout (fn* [l] (throw l))}))
+                 [(try* (process/wait p) (catch* e e)) (process/wait p)]";
+    let started = Instant::now();
+    assert_eq!(eval(spawn), "[\"started\" nil]");
+    assert!(started.elapsed() < Duration::from_secs(20));
+}
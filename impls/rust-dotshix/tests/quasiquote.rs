@@ -0,0 +1,28 @@
+mod common;
+use common::eval_after;
+
+const XS: &str = "(def! xs '(1 2))";
+
+#[test]
+fn splices_into_lists() {
+    assert_eq!(eval_after(XS, "`(0 ~@xs 3)"), "(0 1 2 3)");
+    assert_eq!(eval_after(XS, "`(~@xs)"), "(1 2)");
+    assert_eq!(eval_after(XS, "`(~@xs ~@xs)"), "(1 2 1 2)");
+    assert_eq!(eval_after(XS, "`(0 ~@[3 4] ~@())"), "(0 3 4)");
+    assert_eq!(eval_after(XS, "(list? `(~@[1]))"), "true");
+}
+
+#[test]
+fn splices_into_vectors() {
+    assert_eq!(eval_after(XS, "`[0 ~@xs 3]"), "[0 1 2 3]");
+    assert_eq!(eval_after(XS, "`[~@xs ~@xs]"), "[1 2 1 2]");
+    assert_eq!(eval_after(XS, "`[~@()]"), "[]");
+    assert_eq!(eval_after(XS, "`[[~@xs] (~@xs)]"), "[[1 2] (1 2)]");
+    assert_eq!(eval_after(XS, "(vector? `[~@xs])"), "true");
+}
+
+#[test]
+fn nested_quasiquotes_splice_only_at_their_own_level() {
+    assert_eq!(eval_after(XS, "`(a `(b ~@xs))"), "(a (quasiquote (b (splice-unquote xs))))");
+    assert_eq!(eval_after(XS, "`(a `(b ~(c ~@xs)))"), "(a (quasiquote (b (unquote (c 1 2)))))");
+}
@@ -0,0 +1,27 @@
+mod common;
+use common::eval_after;
+
+const POINT: &str = "(defrecord Point [x y]) (def! p (->Point 1 2))";
+
+#[test]
+fn records_are_maps_of_their_fields() {
+    assert_eq!(eval_after(POINT, "[(map? p) (count p) (empty? p)]"), "[true 2 false]");
+    assert_eq!(eval_after(POINT, "[(:x p) (get p :y)]"), "[1 2]");
+    assert_eq!(eval_after(POINT, "[(keys p) (vals p) (contains? p :x)]"), "[(:x :y) (1 2) true]");
+    assert_eq!(eval_after(POINT, "(count (->Point 1 2))"), "2");
+}
+
+#[test]
+fn records_are_sequences_of_entries() {
+    assert_eq!(eval_after(POINT, "(seq p)"), "([:x 1] [:y 2])");
+    assert_eq!(eval_after(POINT, "[(first p) (rest p)]"), "[[:x 1] ([:y 2])]");
+    assert_eq!(eval_after(POINT, "(map first p)"), "(:x :y)");
+    assert_eq!(eval_after(POINT, "(into {} p)"), "{:x 1 :y 2}");
+}
+
+#[test]
+fn updates_keep_the_record_type() {
+    assert_eq!(eval_after(POINT, "(assoc p :y 5)"), "#Point {:x 1 :y 5}");
+    assert_eq!(eval_after(POINT, "(conj p [:z 3])"), "#Point {:x 1 :y 2 :z 3}");
+    assert_eq!(eval_after(POINT, "(= p (->Point 1 2))"), "true");
+}
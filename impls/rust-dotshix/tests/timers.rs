@@ -0,0 +1,42 @@
+mod common;
+use common::{eval, eval_after, eval_with};
+
+const DETERMINISTIC: &[&str] = &["--deterministic"];
+
+// log collects what the callbacks made by note record
+const LOG: &str = "(def! log (atom [])) (def! note (fn* [x] (fn* [] (swap! log conj x))))";
+
+#[test]
+fn timers_fire_in_due_order_with_ties_in_scheduling_order() {
+    let timers = "(set-timeout 30 (note :c)) (set-timeout 10 (note :a)) (set-timeout 10 (note :b))
+                  (set-timeout 0 (note :now)) (set-interval 12 (note :tick))
+                  (set-timeout 40 (fn* [] (clear-timer 5))) (run-scheduler) @log";
+    let fired = "[:now :a :b :tick :tick :c :tick]";
+    assert_eq!(eval_with(DETERMINISTIC, &format!("{} {}", LOG, timers)), fired);
+    // Real waits keep the same order
+    assert_eq!(eval_after(LOG, timers), fired);
+}
+
+#[test]
+fn an_interval_can_clear_itself() {
+    let interval = "(def! n (atom 0))
+                    (def! id (set-interval 5 (fn* []
+                      (swap! log conj (swap! n inc))
+                      (if (= @n 3) (swap! log conj (clear-timer id))))))
+                    [(run-scheduler) @log (clear-timer id) (time-ms)]";
+    let source = format!("{} {}", LOG, interval);
+    assert_eq!(eval_with(DETERMINISTIC, &source), "[nil [1 2 3 true] false 15]");
+}
+
+#[test]
+fn a_throwing_callback_leaves_later_timers_queued() {
+    let timers = "(set-timeout 5 (fn* [] (throw \"boom\"))) (set-timeout 10 (note :later))
+                  [(try* (run-scheduler) (catch* e e)) @log (run-scheduler) @log]";
+    assert_eq!(eval_after(LOG, timers), "[\"boom\" [] nil [:later]]");
+}
+
+#[test]
+fn timers_only_run_inside_run_scheduler() {
+    assert_eq!(eval_after(LOG, "(set-timeout 0 (note :a)) @log"), "[]");
+    assert_eq!(eval("(run-scheduler)"), "nil");
+}
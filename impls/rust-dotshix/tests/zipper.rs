@@ -0,0 +1,45 @@
+mod common;
+use common::{error, eval, eval_after};
+
+const TREE: &str = "(def! z (zip/zipper '[1 (2 3) {:a 4}]))";
+
+#[test]
+fn moves_through_lists_vectors_and_maps() {
+    assert_eq!(eval_after(TREE, "(zip/node (zip/right (zip/down z)))"), "(2 3)");
+    assert_eq!(eval_after(TREE, "(zip/node (zip/down (zip/right (zip/down z))))"), "2");
+    let map = "(zip/right (zip/right (zip/down z)))";
+    assert_eq!(eval_after(TREE, &format!("(zip/node (zip/down {}))", map)), ":a");
+    assert_eq!(eval_after(TREE, &format!("(zip/node (zip/left {}))", map)), "(2 3)");
+    assert_eq!(eval_after(TREE, "(zip/node (zip/up (zip/down z)))"), "[1 (2 3) {:a 4}]");
+}
+
+#[test]
+fn moves_past_the_edges_are_nil() {
+    assert_eq!(
+        eval_after(TREE, "[(zip/up z) (zip/left (zip/down z)) (zip/down (zip/down z))]"),
+        "[nil nil nil]"
+    );
+    assert_eq!(eval("(zip/down (zip/zipper []))"), "nil");
+}
+
+#[test]
+fn edits_rebuild_each_collection_kind() {
+    let list = "(zip/down (zip/right (zip/down z)))";
+    let edited = eval_after(TREE, &format!("(zip/root (zip/edit {} + 10))", list));
+    assert_eq!(edited, "[1 (12 3) {:a 4}]");
+    let key = "(zip/down (zip/right (zip/right (zip/down z))))";
+    let replaced = eval_after(TREE, &format!("(zip/root (zip/replace {} :b))", key));
+    assert_eq!(replaced, "[1 (2 3) {:b 4}]");
+    // Earlier locations are unaffected by edits made from them
+    assert_eq!(
+        eval_after(TREE, "(def! one (zip/down z)) (zip/replace one 9) (zip/root one)"),
+        "[1 (2 3) {:a 4}]"
+    );
+}
+
+#[test]
+fn locations_are_host_objects() {
+    assert_eq!(eval_after(TREE, "[(tag z) (tagged? z \"zip/loc\")]"), "[\"zip/loc\" true]");
+    assert_eq!(eval_after(TREE, "(pr-str z)"), "\"<#object zip/loc>\"");
+    assert_eq!(error("(zip/node [1])"), "zip/node: [1] is not a zipper location");
+}